@@ -0,0 +1,347 @@
+//! This module renames identifiers to placeholders so that code can be shared without revealing names.
+//!
+//! The renaming is token-based: identifiers outside of string literals and comments are replaced by
+//! placeholders such as `id_1`, consistently across all the files of a run. Keywords are kept, so the
+//! structure of the code stays readable. Comments keep their text, with the identifiers already renamed
+//! in code replaced as well; string and character literals are left untouched. The mapping from placeholders
+//! to the original names is kept so that responses can be de-anonymized.
+//!
+//! Placeholders never take the name of an identifier of the input, e.g. an existing `id_2`: that identifier
+//! is renamed like any other, and the placeholders skip its number.
+
+use crate::language::{comment_syntax, language_name};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+static IDENTIFIER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b[A-Za-z_][A-Za-z0-9_]*\b").unwrap());
+
+/// The names that have the form of a placeholder.
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\bid_([0-9]+)\b").unwrap());
+
+/// The start of a Rust raw string literal, e.g. `r#"`, with its hashes.
+static RUST_RAW_STRING: Lazy<Regex> = Lazy::new(|| Regex::new(r##"^b?r(#*)""##).unwrap());
+
+/// Keywords and literals of the supported languages, which are never renamed.
+static KEYWORDS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+    [
+        // Shared
+        "if", "else", "for", "while", "do", "break", "continue", "return", "switch", "case",
+        "default", "try", "catch", "finally", "throw", "throws", "new", "delete", "class",
+        "interface", "enum", "struct", "union", "static", "const", "final", "public", "private",
+        "protected", "internal", "import", "export", "from", "as", "in", "is", "not", "and", "or",
+        "true", "false", "null", "nil", "void", "this", "self", "Self", "super", "type", "typeof",
+        "instanceof", "extends", "implements", "package", "goto", "sizeof", "extern", "inline",
+        "volatile", "register", "signed", "unsigned", "abstract", "override", "virtual", "async",
+        "await", "yield", "with", "where", "let", "var", "val", "fun", "func", "fn", "def",
+        "lambda", "match", "when", "object", "module", "namespace", "using", "template",
+        "typename", "operator", "friend", "explicit", "mutable", "constexpr", "auto",
+        // Rust
+        "mod", "pub", "crate", "use", "impl", "trait", "mut", "ref", "move", "unsafe", "dyn",
+        "loop", "macro_rules",
+        // Python
+        "elif", "except", "raise", "pass", "global", "nonlocal", "assert", "del", "None", "True",
+        "False",
+        // JavaScript / TypeScript
+        "function", "undefined", "of", "declare", "readonly", "keyof", "infer", "satisfies",
+        // Go
+        "chan", "go", "defer", "select", "range", "map", "fallthrough",
+        // Java / C# / Kotlin / Swift
+        "synchronized", "transient", "native", "strictfp", "sealed", "partial", "out", "get",
+        "set", "init", "guard", "protocol", "extension", "deinit", "inout",
+        // Ruby / PHP / shell
+        "end", "begin", "rescue", "ensure", "unless", "until", "then", "elsif", "echo", "fi",
+        "esac", "done", "local",
+        // Primitive types
+        "int", "long", "short", "char", "float", "double", "bool", "boolean", "byte", "string",
+        "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128",
+        "isize", "f32", "f64",
+        // Common standard library names
+        "main", "std", "Some", "Ok", "Err", "Option", "Result", "Vec", "String", "Box",
+        "println", "print", "printf", "console", "len", "Object", "Array", "Math", "System",
+    ]
+    .into_iter()
+    .collect()
+});
+
+/// The languages whose identifiers are renamed.
+const SUPPORTED_LANGUAGES: &[&str] = &[
+    "rust", "python", "javascript", "typescript", "go", "java", "kotlin", "swift", "c", "cpp",
+    "csharp", "ruby", "php", "scala",
+];
+
+/// Renames identifiers consistently across the files of a run.
+#[derive(Debug, Default)]
+pub struct Anonymizer {
+    placeholders: HashMap<String, String>,
+    originals: BTreeMap<String, String>,
+    /// The numbers of the placeholders found in the input, which are never assigned.
+    reserved: HashSet<usize>,
+    /// The number of the last placeholder assigned.
+    last: usize,
+}
+
+/// A region of source code.
+enum Segment<'a> {
+    Code(&'a str),
+    Comment(&'a str),
+    Literal(&'a str),
+}
+
+impl Anonymizer {
+    /// Creates an anonymizer with an empty mapping.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an anonymizer that keeps the placeholders of a previous mapping and assigns new ones after them.
+    pub fn with_mapping(mapping: BTreeMap<String, String>) -> Self {
+        let placeholders = mapping
+            .iter()
+            .map(|(placeholder, original)| (original.clone(), placeholder.clone()))
+            .collect();
+        let last = mapping.keys().filter_map(|placeholder| placeholder_number(placeholder)).max().unwrap_or(0);
+        Self {
+            placeholders,
+            originals: mapping,
+            reserved: HashSet::new(),
+            last,
+        }
+    }
+
+    /// Returns whether the identifiers of files with the given extension are renamed.
+    pub fn supports(extension: &str) -> bool {
+        language_name(extension).is_some_and(|language| SUPPORTED_LANGUAGES.contains(&language))
+    }
+
+    /// Renames the identifiers of a file, reusing the placeholders assigned in previous files.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The content of the file.
+    /// * `extension` - The file extension used to select the language.
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The anonymized content, or the content unchanged for unsupported languages.
+    pub fn anonymize(&mut self, code: &str, extension: &str) -> String {
+        if !Self::supports(extension) {
+            return code.to_string();
+        }
+        self.reserve(code);
+        let segments = segments(code, extension);
+
+        // Assign placeholders from the code first, so comments only reuse existing ones
+        let mut output = String::with_capacity(code.len());
+        let mut rendered: Vec<Option<String>> = Vec::with_capacity(segments.len());
+        for (index, segment) in segments.iter().enumerate() {
+            let before_literal = matches!(segments.get(index + 1), Some(Segment::Literal(_)));
+            rendered.push(match segment {
+                Segment::Code(text) => Some(self.rename(text, true, before_literal)),
+                _ => None,
+            });
+        }
+        for (segment, rendered) in segments.iter().zip(rendered) {
+            match segment {
+                Segment::Code(_) => output.push_str(&rendered.unwrap_or_default()),
+                Segment::Comment(text) => output.push_str(&self.rename(text, false, false)),
+                Segment::Literal(text) => output.push_str(text),
+            }
+        }
+        output
+    }
+
+    /// Renames the identifiers of a prose text, e.g. the note of a file, like the comments of the code: only
+    /// the identifiers that already have a placeholder are replaced.
+    pub fn anonymize_text(&mut self, text: &str) -> String {
+        self.reserve(text);
+        self.rename(text, false, false)
+    }
+
+    /// Renames the identifiers of the components of a path, keeping their extensions, e.g. `src/load_user.py`
+    /// to `id_1/id_2.py`.
+    ///
+    /// The placeholders are shared with the code, so a Rust module and its file get the same one.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path, with `/` or the platform separator between its components.
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The anonymized path, with `/` between its components.
+    pub fn anonymize_path(&mut self, path: &str) -> String {
+        self.reserve(path);
+        path.split(['/', std::path::MAIN_SEPARATOR])
+            .map(|component| match component.rfind('.') {
+                Some(dot) if dot > 0 => format!("{}{}", self.rename(&component[..dot], true, false), &component[dot..]),
+                _ => self.rename(component, true, false),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Restores the original identifiers of a text, e.g. of a response, replacing all the placeholders at once.
+    pub fn deanonymize(&self, text: &str) -> String {
+        PLACEHOLDER
+            .replace_all(text, |caps: &regex::Captures| {
+                let placeholder = &caps[0];
+                self.originals.get(placeholder).cloned().unwrap_or_else(|| placeholder.to_string())
+            })
+            .into_owned()
+    }
+
+    /// Returns the mapping from placeholders to the original identifiers.
+    pub fn mapping(&self) -> &BTreeMap<String, String> {
+        &self.originals
+    }
+
+    /// Keeps the placeholders from taking the names of the identifiers of a text.
+    fn reserve(&mut self, text: &str) {
+        for caps in PLACEHOLDER.captures_iter(text) {
+            if !self.originals.contains_key(&caps[0]) {
+                self.reserved.extend(placeholder_number(&caps[0]));
+            }
+        }
+    }
+
+    /// Replaces the identifiers of a text, assigning new placeholders only when `assign` is set.
+    ///
+    /// When the text is followed by a string literal, a trailing string prefix such as `r` or `b` is kept.
+    fn rename(&mut self, text: &str, assign: bool, before_literal: bool) -> String {
+        IDENTIFIER
+            .replace_all(text, |caps: &regex::Captures| {
+                let found = caps.get(0).unwrap();
+                let name = found.as_str();
+                let is_string_prefix = before_literal
+                    && found.end() == text.len()
+                    && name.len() <= 2
+                    && name.chars().all(|c| "rbfuRBFU".contains(c));
+                if is_string_prefix {
+                    return name.to_string();
+                }
+                if let Some(placeholder) = self.placeholders.get(name) {
+                    return placeholder.clone();
+                }
+                if !assign || name == "_" || KEYWORDS.contains(name) {
+                    return name.to_string();
+                }
+                self.last += 1;
+                while self.reserved.contains(&self.last) {
+                    self.last += 1;
+                }
+                let placeholder = format!("id_{}", self.last);
+                self.placeholders.insert(name.to_string(), placeholder.clone());
+                self.originals.insert(placeholder.clone(), name.to_string());
+                placeholder
+            })
+            .into_owned()
+    }
+}
+
+/// Returns the number of a placeholder, e.g. `2` for `id_2`.
+fn placeholder_number(placeholder: &str) -> Option<usize> {
+    placeholder.strip_prefix("id_")?.parse().ok()
+}
+
+/// Returns the length of the Rust character literal at the start of a text, e.g. `'a'`, `'\n'` or `'\u{1F600}'`.
+///
+/// Lifetimes and labels, such as `'a` or `'outer:`, aren't literals and give `None`.
+fn rust_char_literal_len(text: &str) -> Option<usize> {
+    let rest = text.strip_prefix('\'')?;
+    if let Some(escaped) = rest.strip_prefix('\\') {
+        // The longest escape is `\u{10FFFF}`
+        let close = escaped.char_indices().skip(1).take(9).find(|(_, c)| *c == '\'')?.0;
+        return Some(2 + close + 1);
+    }
+    let c = rest.chars().next().filter(|c| *c != '\'')?;
+    rest[c.len_utf8()..].starts_with('\'').then(|| 1 + c.len_utf8() + 1)
+}
+
+/// Returns the length of the Rust raw string literal at the start of a text, e.g. `r#"a "quoted" word"#`.
+fn rust_raw_string_len(text: &str) -> Option<usize> {
+    let caps = RUST_RAW_STRING.captures(text)?;
+    let open = caps[0].len();
+    let close = format!("\"{}", &caps[1]);
+    Some(text[open..].find(&close).map_or(text.len(), |found| open + found + close.len()))
+}
+
+/// Splits source code into code, comment and string literal regions.
+fn segments<'a>(code: &'a str, extension: &str) -> Vec<Segment<'a>> {
+    let syntax = comment_syntax(extension);
+    let line_comment = syntax.and_then(|s| s.line);
+    let block_comment = syntax.and_then(|s| s.block);
+    let language = language_name(extension).unwrap_or_default();
+    // Single quotes also start lifetimes and labels in Rust, so character literals are matched on their own
+    let quotes: &[char] = match language {
+        "rust" => &['"'],
+        "javascript" | "typescript" => &['"', '\'', '`'],
+        _ => &['"', '\''],
+    };
+
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut index = 0;
+    let bytes = code.as_bytes();
+    while index < code.len() {
+        let rest = &code[index..];
+        let after_identifier = code[..index].chars().next_back().is_some_and(|c| c.is_alphanumeric() || c == '_');
+        let rust_literal = match language {
+            "rust" if !after_identifier => rust_raw_string_len(rest).or_else(|| rust_char_literal_len(rest)),
+            "rust" => rust_char_literal_len(rest),
+            _ => None,
+        };
+        let (end, is_comment) = if let Some(len) = rust_literal {
+            (index + len, false)
+        } else if line_comment.is_some_and(|prefix| rest.starts_with(prefix)) {
+            (index + rest.find('\n').unwrap_or(rest.len()), true)
+        } else if let Some((open, close)) = block_comment.filter(|(open, _)| rest.starts_with(open)) {
+            let end = rest[open.len()..]
+                .find(close)
+                .map(|found| index + open.len() + found + close.len())
+                .unwrap_or(code.len());
+            (end, true)
+        } else if let Some(quote) = quotes.iter().find(|quote| rest.starts_with(**quote)) {
+            let triple = quote.to_string().repeat(3);
+            let end = if language == "python" && rest.starts_with(&triple) {
+                rest[3..]
+                    .find(&triple)
+                    .map(|found| index + 3 + found + 3)
+                    .unwrap_or(code.len())
+            } else {
+                let mut end = index + 1;
+                while end < code.len() {
+                    match bytes[end] {
+                        b'\\' => end += 2,
+                        byte if byte == *quote as u8 => {
+                            end += 1;
+                            break;
+                        }
+                        _ => end += 1,
+                    }
+                }
+                end.min(code.len())
+            };
+            (end, false)
+        } else {
+            index += rest.chars().next().map(char::len_utf8).unwrap_or(1);
+            continue;
+        };
+
+        if start < index {
+            segments.push(Segment::Code(&code[start..index]));
+        }
+        let text = &code[index..end];
+        segments.push(if is_comment {
+            Segment::Comment(text)
+        } else {
+            Segment::Literal(text)
+        });
+        index = end;
+        start = end;
+    }
+    if start < code.len() {
+        segments.push(Segment::Code(&code[start..]));
+    }
+    segments
+}
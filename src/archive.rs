@@ -0,0 +1,197 @@
+//! This module reads `.zip` and `.tar.gz` archives into an in-memory tree, so prompts can be generated
+//! from an archive as if it were a directory.
+
+use crate::source::{FileMetadata, FileSource, MemorySource};
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use std::io::{self, Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// The total size of the decompressed entries of an archive, unless another limit is set.
+pub const DEFAULT_MAX_ARCHIVE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// The kinds of archives that can be traversed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// Returns the kind of archive of a path from its extension, or `None` when it isn't an archive.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// The entries of an archive file on disk, traversed as if the archive were a directory.
+#[derive(Debug, Clone)]
+pub struct ArchiveSource {
+    root: PathBuf,
+    files: MemorySource,
+}
+
+impl ArchiveSource {
+    /// Reads an archive file into memory, up to [`DEFAULT_MAX_ARCHIVE_BYTES`] decompressed bytes. The archive
+    /// itself becomes the root directory of the source.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to a `.zip`, `.tar.gz` or `.tgz` file.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<ArchiveSource>` - The files of the archive, or an error if it can't be read.
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::open_with_limit(path, DEFAULT_MAX_ARCHIVE_BYTES)
+    }
+
+    /// Reads an archive file into memory like [`ArchiveSource::open`], up to `max_bytes` decompressed bytes.
+    pub fn open_with_limit(path: &Path, max_bytes: u64) -> Result<Self> {
+        let kind = ArchiveKind::from_path(path)
+            .with_context(|| format!("Unsupported archive: {}", path.display()))?;
+        let root = std::fs::canonicalize(path)
+            .with_context(|| format!("Failed to read archive: {}", path.display()))?;
+        let bytes = std::fs::read(&root).with_context(|| format!("Failed to read archive: {}", path.display()))?;
+        let files = match kind {
+            ArchiveKind::Zip => read_zip_with_limit(&root, &bytes, max_bytes)?,
+            ArchiveKind::TarGz => read_tar_gz_with_limit(&root, &bytes, max_bytes)?,
+        };
+        Ok(ArchiveSource { root, files })
+    }
+}
+
+impl FileSource for ArchiveSource {
+    fn walk<'a>(&'a self, root: &Path) -> Box<dyn Iterator<Item = Result<PathBuf>> + 'a> {
+        self.files.walk(root)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files.read(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        self.files.metadata(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        // Entries of the archive don't exist on disk, only the archive does
+        if path.starts_with(&self.root) {
+            Ok(path.to_path_buf())
+        } else {
+            std::fs::canonicalize(path)
+        }
+    }
+}
+
+/// Reads the files of a zip archive into an in-memory tree, up to [`DEFAULT_MAX_ARCHIVE_BYTES`] decompressed bytes.
+///
+/// Directory entries are skipped, since directories are implied by the paths of the files, and so
+/// are hidden entries, like on the filesystem.
+///
+/// # Arguments
+///
+/// * `root` - The root of the tree, usually the path of the archive.
+/// * `bytes` - The content of the archive.
+///
+/// # Returns
+///
+/// * `Result<MemorySource>` - The files of the archive.
+pub fn read_zip(root: &Path, bytes: &[u8]) -> Result<MemorySource> {
+    read_zip_with_limit(root, bytes, DEFAULT_MAX_ARCHIVE_BYTES)
+}
+
+/// Reads the files of a zip archive like [`read_zip`], failing once the entries exceed `max_bytes` in total.
+///
+/// The sizes in the headers of the archive aren't trusted: the entries are read up to the bytes left.
+pub fn read_zip_with_limit(root: &Path, bytes: &[u8], max_bytes: u64) -> Result<MemorySource> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).context("Invalid zip archive")?;
+    let mut source = MemorySource::new(root);
+    let mut total = 0;
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index)?;
+        let Some(path) = entry.enclosed_name().filter(|path| !entry.is_dir() && !is_hidden(path)) else {
+            continue;
+        };
+        let content = read_entry(entry, &path, &mut total, max_bytes)?;
+        source = source.with_file(path, content);
+    }
+    Ok(source)
+}
+
+/// Reads the regular files of a gzip-compressed tar archive into an in-memory tree, up to
+/// [`DEFAULT_MAX_ARCHIVE_BYTES`] decompressed bytes.
+///
+/// # Arguments
+///
+/// * `root` - The root of the tree, usually the path of the archive.
+/// * `bytes` - The content of the archive.
+///
+/// # Returns
+///
+/// * `Result<MemorySource>` - The files of the archive.
+pub fn read_tar_gz(root: &Path, bytes: &[u8]) -> Result<MemorySource> {
+    read_tar_gz_with_limit(root, bytes, DEFAULT_MAX_ARCHIVE_BYTES)
+}
+
+/// Reads the regular files of a gzip-compressed tar archive like [`read_tar_gz`], failing once the entries
+/// exceed `max_bytes` in total.
+pub fn read_tar_gz_with_limit(root: &Path, bytes: &[u8], max_bytes: u64) -> Result<MemorySource> {
+    let mut total = 0;
+    let mut archive = tar::Archive::new(GzDecoder::new(bytes));
+    let mut source = MemorySource::new(root);
+    for entry in archive.entries().context("Invalid tar archive")? {
+        let entry = entry.context("Invalid tar archive")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let Some(path) = enclosed_path(&entry.path()?).filter(|path| !is_hidden(path)) else {
+            continue;
+        };
+        let content = read_entry(entry, &path, &mut total, max_bytes)?;
+        source = source.with_file(path, content);
+    }
+    Ok(source)
+}
+
+/// Reads an entry of an archive, adding its size to the `total` read so far, which can't exceed `max_bytes`.
+fn read_entry(entry: impl Read, path: &Path, total: &mut u64, max_bytes: u64) -> Result<Vec<u8>> {
+    let left = max_bytes - *total;
+    let mut content = Vec::new();
+    // One byte more than what is left tells an entry at the limit apart from one over it
+    entry
+        .take(left + 1)
+        .read_to_end(&mut content)
+        .with_context(|| format!("Failed to read archive entry: {}", path.display()))?;
+    if content.len() as u64 > left {
+        anyhow::bail!("The archive exceeds {} bytes once decompressed, at entry: {}", max_bytes, path.display());
+    }
+    *total += content.len() as u64;
+    Ok(content)
+}
+
+/// Returns the normal components of an entry path, or `None` when it would escape the root.
+fn enclosed_path(path: &Path) -> Option<PathBuf> {
+    let mut enclosed = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => enclosed.push(name),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!enclosed.as_os_str().is_empty()).then_some(enclosed)
+}
+
+/// Returns whether a component of a path is hidden.
+fn is_hidden(path: &Path) -> bool {
+    path.components()
+        .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+}
@@ -0,0 +1,397 @@
+//! This module contains the logic for keeping the rendered prompt within a size budget.
+//! Whole file blocks are dropped from the end of the files list before any content is cut mid-file,
+//! unless the files are sampled with [`BudgetStrategy::SampleEach`].
+
+use crate::template::render_template;
+use crate::token::Tokenizer;
+use anyhow::Result;
+use clap::ValueEnum;
+use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+
+/// The marker inserted wherever content is cut, so the model knows something is missing.
+pub const DEFAULT_TRUNCATION_MARKER: &str = "... [truncated] ...";
+
+/// How the files are cut down when the prompt exceeds its token budget.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetStrategy {
+    /// Drop whole file blocks from the end of the files list.
+    #[default]
+    DropTrailing,
+    /// Keep every file, cutting each one proportionally to its head and tail around the truncation marker.
+    SampleEach,
+}
+
+/// Describes the file blocks that were dropped or sampled to fit the rendered prompt within a budget.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Truncation {
+    pub kept_files: usize,
+    pub dropped_files: usize,
+    pub dropped_bytes: usize,
+    /// The number of kept files reduced to their head and tail.
+    pub sampled_files: usize,
+}
+
+/// Renders the template and drops trailing file blocks until the output fits within `max_bytes`.
+///
+/// A note starting with the truncation marker is appended to the output whenever something was dropped.
+/// If the output still doesn't fit without any file blocks, it is cut at a character boundary.
+///
+/// # Arguments
+///
+/// * `handlebars` - The configured Handlebars instance.
+/// * `template_name` - The name of the template.
+/// * `data` - The JSON data object, containing the `files` array.
+/// * `max_bytes` - The maximum size of the rendered output, in bytes.
+/// * `marker` - The truncation marker, e.g. [`DEFAULT_TRUNCATION_MARKER`].
+///
+/// # Returns
+///
+/// * `Result<(String, Option<Truncation>)>` - The rendered output and a description of what was dropped, if anything.
+pub fn render_within_byte_limit(
+    handlebars: &Handlebars,
+    template_name: &str,
+    data: &serde_json::Value,
+    max_bytes: usize,
+    marker: &str,
+) -> Result<(String, Option<Truncation>)> {
+    let full = render_template(handlebars, template_name, data)?;
+    if full.len() <= max_bytes {
+        return Ok((full, None));
+    }
+
+    let files = data
+        .get("files")
+        .and_then(|f| f.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let best = keep_leading_files(
+        handlebars,
+        template_name,
+        data,
+        &files,
+        full.len(),
+        &|candidate| candidate.len() <= max_bytes,
+        &|truncation| truncation_note(truncation, &format!("{} byte output", max_bytes), marker),
+    )?;
+    if let Some(best) = best {
+        return Ok((best.0, Some(best.1)));
+    }
+
+    // Even without file blocks the output is too large, so cut it
+    let mut data = data.clone();
+    data["files"] = serde_json::Value::Array(Vec::new());
+    let body = render_template(handlebars, template_name, &data)?;
+    let mut truncation = Truncation {
+        kept_files: 0,
+        dropped_files: files.len(),
+        dropped_bytes: full.len(),
+        sampled_files: 0,
+    };
+    let limit = format!("{} byte output", max_bytes);
+    let note = truncation_note(&truncation, &limit, marker);
+    let cut = floor_char_boundary(&body, max_bytes.saturating_sub(note.len()));
+    truncation.dropped_bytes = full.len() - cut;
+    let note = truncation_note(&truncation, &limit, marker);
+    let output = if cut + note.len() <= max_bytes {
+        format!("{}{}", &body[..cut], note)
+    } else {
+        // Not even the note fits, so keep the marker alone
+        cut_with_marker(&body, max_bytes, marker)
+    };
+    Ok((output, Some(truncation)))
+}
+
+/// Renders the template with the largest number of leading files whose output fits, followed by the truncation note.
+///
+/// Returns `None` when the output doesn't fit even without any file blocks.
+fn keep_leading_files(
+    handlebars: &Handlebars,
+    template_name: &str,
+    data: &serde_json::Value,
+    files: &[serde_json::Value],
+    full_bytes: usize,
+    fits: &dyn Fn(&str) -> bool,
+    note: &dyn Fn(&Truncation) -> String,
+) -> Result<Option<(String, Truncation)>> {
+    let render_with = |kept: usize| -> Result<(String, Truncation)> {
+        let mut data = data.clone();
+        data["files"] = serde_json::Value::Array(files[..kept].to_vec());
+        let rendered = render_template(handlebars, template_name, &data)?;
+        let truncation = Truncation {
+            kept_files: kept,
+            dropped_files: files.len() - kept,
+            dropped_bytes: full_bytes.saturating_sub(rendered.len()),
+            sampled_files: 0,
+        };
+        let note = note(&truncation);
+        Ok((format!("{}{}", rendered, note), truncation))
+    };
+
+    // Find the largest number of leading files that still fits
+    let (mut low, mut high) = (0, files.len());
+    let mut best = None;
+    while low < high {
+        let mid = (low + high) / 2;
+        let (candidate, truncation) = render_with(mid)?;
+        if fits(&candidate) {
+            best = Some((candidate, truncation));
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(best)
+}
+
+/// Renders the template and cuts the files down until the output fits within `max_tokens`.
+///
+/// With [`BudgetStrategy::DropTrailing`], trailing file blocks are dropped like in [`render_within_byte_limit`],
+/// and all of them when even the first one doesn't fit, the rest being cut when it still doesn't fit. With [`BudgetStrategy::SampleEach`], every file is kept
+/// and cut down proportionally to its size, to its first and last lines around the truncation marker, so that
+/// the whole codebase stays represented. When even the shortest samples don't fit, they are cut like the output
+/// of [`BudgetStrategy::DropTrailing`], so that the output never exceeds `max_tokens`.
+///
+/// # Arguments
+///
+/// * `handlebars` - The configured Handlebars instance.
+/// * `template_name` - The name of the template.
+/// * `data` - The JSON data object, containing the `files` array.
+/// * `max_tokens` - The maximum token count of the rendered output.
+/// * `tokenizer` - The tokenizer counting the tokens of the output.
+/// * `strategy` - How the files are cut down.
+/// * `marker` - The truncation marker, e.g. [`DEFAULT_TRUNCATION_MARKER`].
+///
+/// # Returns
+///
+/// * `Result<(String, Option<Truncation>)>` - The rendered output and a description of what was cut, if anything.
+pub fn render_within_token_limit(
+    handlebars: &Handlebars,
+    template_name: &str,
+    data: &serde_json::Value,
+    max_tokens: usize,
+    tokenizer: &dyn Tokenizer,
+    strategy: BudgetStrategy,
+    marker: &str,
+) -> Result<(String, Option<Truncation>)> {
+    let full = render_template(handlebars, template_name, data)?;
+    if tokenizer.count(&full) <= max_tokens {
+        return Ok((full, None));
+    }
+
+    let files = data
+        .get("files")
+        .and_then(|f| f.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let limit = format!("{} token", max_tokens);
+    let fits = |candidate: &str| tokenizer.count(candidate) <= max_tokens;
+
+    if strategy == BudgetStrategy::DropTrailing {
+        let best = keep_leading_files(
+            handlebars,
+            template_name,
+            data,
+            &files,
+            full.len(),
+            &fits,
+            &|truncation| truncation_note(truncation, &limit, marker),
+        )?;
+        return match best {
+            Some((output, truncation)) => Ok((output, Some(truncation))),
+            None => {
+                // Even without file blocks the output is too large, so cut it
+                let mut data = data.clone();
+                data["files"] = serde_json::Value::Array(Vec::new());
+                let body = render_template(handlebars, template_name, &data)?;
+                let mut truncation = Truncation {
+                    kept_files: 0,
+                    dropped_files: files.len(),
+                    dropped_bytes: full.len(),
+                    sampled_files: 0,
+                };
+                let note = truncation_note(&truncation, &limit, marker);
+                let cut = longest_fitting_prefix(&body, &|prefix| fits(&format!("{}{}", prefix, note)));
+                truncation.dropped_bytes = full.len() - cut;
+                let note = truncation_note(&truncation, &limit, marker);
+                let output = format!("{}{}", &body[..cut], note);
+                if fits(&output) {
+                    Ok((output, Some(truncation)))
+                } else {
+                    // Not even the note fits, so keep as much of the marker as fits
+                    let cut = longest_fitting_prefix(marker, &fits);
+                    Ok((marker[..cut].to_string(), Some(truncation)))
+                }
+            }
+        };
+    }
+
+    // Shrink the share of each file kept until the output fits, starting from the share of the budget
+    // left once the files are reduced to their shortest samples
+    let render_sampled = |share: f64| -> Result<(String, Truncation)> {
+        let mut data = data.clone();
+        let mut sampled_files = 0;
+        let sampled: Vec<serde_json::Value> = files
+            .iter()
+            .map(|file| {
+                let mut file = file.clone();
+                if let Some(code) = file["code"].as_str() {
+                    let sample = sample_head_and_tail(code, (code.len() as f64 * share) as usize, marker);
+                    if sample.len() != code.len() {
+                        sampled_files += 1;
+                    }
+                    file["code"] = serde_json::Value::String(sample);
+                }
+                file
+            })
+            .collect();
+        data["files"] = serde_json::Value::Array(sampled);
+        let rendered = render_template(handlebars, template_name, &data)?;
+        let truncation = Truncation {
+            kept_files: files.len(),
+            dropped_files: 0,
+            dropped_bytes: full.len().saturating_sub(rendered.len()),
+            sampled_files,
+        };
+        Ok((rendered, truncation))
+    };
+    let (shortest, mut shortest_truncation) = render_sampled(0.0)?;
+    // Even the shortest samples may be too large, so they are cut as a last resort
+    let cut_shortest = || {
+        let output = cut_to_fit(&shortest, marker, &fits);
+        shortest_truncation.dropped_bytes = full.len().saturating_sub(output.len());
+        Ok((output, Some(shortest_truncation)))
+    };
+    let shortest_tokens = tokenizer.count(&shortest);
+    if shortest_tokens >= max_tokens {
+        return cut_shortest();
+    }
+    let full_tokens = tokenizer.count(&full);
+    let mut share = (max_tokens - shortest_tokens) as f64 / full_tokens.saturating_sub(shortest_tokens).max(1) as f64;
+    for _ in 0..SAMPLING_ATTEMPTS {
+        let (output, truncation) = render_sampled(share)?;
+        let tokens = tokenizer.count(&output);
+        if tokens <= max_tokens {
+            return Ok((output, Some(truncation)));
+        }
+        share *= 0.9 * max_tokens as f64 / tokens as f64;
+    }
+    cut_shortest()
+}
+
+/// Returns a text unchanged when it fits, or else its longest fitting prefix followed by the truncation marker.
+///
+/// When not even the marker fits on its own line, as much of the marker as fits is kept.
+fn cut_to_fit(text: &str, marker: &str, fits: &dyn Fn(&str) -> bool) -> String {
+    if fits(text) {
+        return text.to_string();
+    }
+    let suffix = format!("\n{}", marker);
+    let cut = longest_fitting_prefix(text, &|prefix| fits(&format!("{}{}", prefix, suffix)));
+    let output = format!("{}{}", &text[..cut], suffix);
+    if fits(&output) {
+        output
+    } else {
+        marker[..longest_fitting_prefix(marker, fits)].to_string()
+    }
+}
+
+/// The number of shrinking shares tried by [`render_within_token_limit`] before falling back to the shortest samples.
+const SAMPLING_ATTEMPTS: usize = 8;
+
+/// Keeps the first and last lines of a text within about `max_bytes`, with the truncation marker on a line between them.
+///
+/// At least the first and last lines are kept, such as the fences of a code block.
+fn sample_head_and_tail(text: &str, max_bytes: usize, marker: &str) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let (mut head, mut tail) = (1, 1);
+    let mut kept_bytes = lines.first().map_or(0, |line| line.len()) + lines.last().map_or(0, |line| line.len());
+    while head + tail < lines.len() {
+        let next = if head <= tail { lines[head] } else { lines[lines.len() - 1 - tail] };
+        if kept_bytes + next.len() + 1 > max_bytes {
+            break;
+        }
+        kept_bytes += next.len() + 1;
+        if head <= tail {
+            head += 1;
+        } else {
+            tail += 1;
+        }
+    }
+    if head + tail >= lines.len() {
+        return text.to_string();
+    }
+    format!(
+        "{}\n{}\n{}",
+        lines[..head].join("\n"),
+        marker,
+        lines[lines.len() - tail..].join("\n")
+    )
+}
+
+/// Formats the note appended to a truncated prompt, e.g. for a `4096 token` limit.
+fn truncation_note(truncation: &Truncation, limit: &str, marker: &str) -> String {
+    format!(
+        "\n\n{} {} file(s) omitted ({} bytes) to stay within the {} limit",
+        marker, truncation.dropped_files, truncation.dropped_bytes, limit
+    )
+}
+
+/// Cuts a text to at most `max_bytes`, ending it with the truncation marker.
+///
+/// When even the marker doesn't fit, the marker itself is shortened.
+///
+/// # Arguments
+///
+/// * `text` - The text to cut.
+/// * `max_bytes` - The maximum size of the result, in bytes.
+/// * `marker` - The truncation marker.
+///
+/// # Returns
+///
+/// * `String` - The text, unchanged when it fits, or else cut at a character boundary and followed by the marker.
+pub fn elide(text: &str, max_bytes: usize, marker: &str) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    cut_with_marker(text, max_bytes, marker)
+}
+
+/// Cuts a text so that it fits in `max_bytes` together with the marker that ends it.
+fn cut_with_marker(text: &str, max_bytes: usize, marker: &str) -> String {
+    let suffix = format!("\n{}", marker);
+    if suffix.len() > max_bytes {
+        return marker[..floor_char_boundary(marker, max_bytes)].to_string();
+    }
+    let cut = floor_char_boundary(text, max_bytes - suffix.len());
+    format!("{}{}", &text[..cut], suffix)
+}
+
+/// Returns the length of the longest prefix of a text that fits, cut at a character boundary.
+///
+/// The prefixes are searched by bisection, as the fit of a prefix, e.g. its token count, grows with its length.
+fn longest_fitting_prefix(text: &str, fits: &dyn Fn(&str) -> bool) -> usize {
+    let (mut low, mut high) = (0, text.len());
+    while low < high {
+        let mid = floor_char_boundary(text, (low + high).div_ceil(2));
+        if mid > low && fits(&text[..mid]) {
+            low = mid;
+        } else {
+            high = (low + high).div_ceil(2) - 1;
+        }
+    }
+    low
+}
+
+/// Returns the largest index not greater than `index` that lies on a character boundary of `s`.
+fn floor_char_boundary(s: &str, index: usize) -> usize {
+    if index >= s.len() {
+        return s.len();
+    }
+    (0..=index).rev().find(|i| s.is_char_boundary(*i)).unwrap_or(0)
+}
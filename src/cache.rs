@@ -0,0 +1,121 @@
+//! This module contains the cache of formatted file blocks, which lets unchanged files be reused across runs.
+//!
+//! Entries are keyed by the path, modification time and size of a file along with the options that affect
+//! its formatting, so changing any of them invalidates the entry. The cache is best-effort: entries that
+//! can't be read or written are simply regenerated.
+
+use crate::Code2PromptConfig;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The result of processing a file, as stored in the cache.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "kebab-case")]
+pub enum CachedBlock {
+    /// The formatted file block, with the hash of the file as read when `include_hashes` is set.
+    Included {
+        code: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sha256: Option<String>,
+    },
+    /// The file matched one of the content deny patterns.
+    DeniedContent,
+    /// The file has no non-whitespace content.
+    Empty,
+    /// The file is not valid UTF-8.
+    InvalidUtf8,
+    /// The base64-encoded content of a binary file, with `binary_as_base64`, and its hash like `Included`.
+    Base64 {
+        content: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sha256: Option<String>,
+    },
+    /// The binary file exceeds the size cap of `binary_as_base64`.
+    BinaryTooLarge,
+}
+
+/// A directory of cached file blocks.
+#[derive(Debug, Clone)]
+pub struct BlockCache {
+    dir: PathBuf,
+    options: String,
+}
+
+impl BlockCache {
+    /// Creates a cache in the given directory for the formatting options of the configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The cache directory, created when the first entry is stored.
+    /// * `config` - The configuration whose formatting options are part of every key.
+    pub fn new(dir: &Path, config: &Code2PromptConfig) -> Self {
+        let options = format!(
+            "v{}|line_number={}|no_codeblock={}|strip_leading_comment_block={}|header_pattern={:?}|content_deny={:?}|binary_as_base64={}|max_binary_bytes={}|language_override={:?}|pub_only={}|strip_ansi={}|fence_info_template={:?}|line_numbers_outside={}|line_ending={:?}|mark_changes_since={}|include_hashes={}",
+            env!("CARGO_PKG_VERSION"),
+            config.line_number,
+            config.no_codeblock,
+            config.strip_leading_comment_block,
+            config.header_pattern,
+            config.content_deny,
+            config.binary_as_base64,
+            config.max_binary_bytes(),
+            config.language_override.iter().collect::<BTreeMap<_, _>>(),
+            config.pub_only,
+            config.strip_ansi,
+            config.fence_info_template,
+            config.line_numbers_outside,
+            config.line_ending,
+            // Headers aren't stripped when marking changes, even from the unchanged files that are cached
+            config.mark_changes_since.is_some(),
+            config.include_hashes,
+        );
+        Self {
+            dir: dir.to_path_buf(),
+            options,
+        }
+    }
+
+    /// Returns the key of a file, or `None` when its modification time is unknown.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file.
+    /// * `modified` - The last modification time of the file.
+    /// * `len` - The size of the file, in bytes.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<String>` - The hex-encoded key.
+    pub fn key(&self, path: &Path, modified: Option<SystemTime>, len: u64) -> Option<String> {
+        let modified = modified?.duration_since(UNIX_EPOCH).ok()?;
+        let digest = Sha256::new()
+            .chain_update(path.to_string_lossy().as_bytes())
+            .chain_update(b"\0")
+            .chain_update(modified.as_nanos().to_le_bytes())
+            .chain_update(len.to_le_bytes())
+            .chain_update(self.options.as_bytes())
+            .finalize();
+        Some(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    /// Returns the cached block of a key, if any.
+    pub fn get(&self, key: &str) -> Option<CachedBlock> {
+        let content = std::fs::read(self.dir.join(key)).ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    /// Stores the block of a key, ignoring failures since entries can always be regenerated.
+    pub fn put(&self, key: &str, block: &CachedBlock) {
+        let result = std::fs::create_dir_all(&self.dir).and_then(|_| {
+            let content = serde_json::to_vec(block)?;
+            std::fs::write(self.dir.join(key), content)
+        });
+        if let Err(e) = result {
+            debug!("Failed to store cached file block: {}", e);
+        }
+    }
+}
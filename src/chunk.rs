@@ -0,0 +1,342 @@
+//! This module splits the files of a prompt into chunks within a token budget, e.g. for RAG ingestion.
+//!
+//! Chunks are made of whole files where possible. A file larger than a chunk is split between lines, and
+//! consecutive chunks may share overlapping lines so that no context is lost at their boundary.
+//!
+//! With a tokenizer exposing its tokens, the rendered prompt can instead be cut from its token stream, so
+//! that each chunk holds exactly as many tokens as the budget allows, at the cost of splitting files and lines.
+
+use crate::token::Tokenizer;
+use anyhow::Result;
+use serde::Serialize;
+
+/// A chunk of the files of a prompt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PromptChunk {
+    /// The position of the chunk, starting at 0.
+    pub index: usize,
+    pub content: String,
+    /// The paths of the files the chunk holds, in whole or in part.
+    pub files: Vec<String>,
+    /// The offset of the first token of the chunk in the concatenated files, overlap included.
+    pub start_token: usize,
+    /// The offset just after the last token of the chunk in the concatenated files.
+    pub end_token: usize,
+}
+
+/// A line of a file, the unit chunks are split at.
+struct Line {
+    file: usize,
+    text: String,
+    start_token: usize,
+    tokens: usize,
+}
+
+/// The lines of the chunk being built, the ones carried over from the previous chunk first.
+#[derive(Default)]
+struct ChunkBuilder {
+    lines: Vec<usize>,
+    /// The number of lines carried over from the previous chunk.
+    carried: usize,
+    tokens: usize,
+    /// Whether the chunk has lines beyond the overlap, so that it is worth emitting.
+    has_new_lines: bool,
+}
+
+/// Splits the files into chunks of at most `max_tokens` tokens, sharing up to `overlap` tokens
+///
+/// Each file is written as its path followed by its content, like in the default template. Files are
+/// packed whole while they fit, and a file that doesn't fit in the current chunk starts a new one, unless
+/// it is too large for any chunk: then it is split between lines, and a line too large for any chunk is
+/// itself cut into windows of the tokens left by the overlap. Each chunk after the first starts with the
+/// last lines of the previous one, up to `overlap` tokens. Lines are packed by the sums of their counts,
+/// and the text of each chunk is counted as a whole before it is emitted, giving its last lines to the next
+/// chunk when it exceeds the budget. The token offsets are the sums of the counts of the lines before.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files, with their `path` and `code`.
+/// * `max_tokens` - The token budget of a chunk.
+/// * `overlap` - The tokens shared by consecutive chunks, lower than `max_tokens`.
+/// * `tokenizer` - The tokenizer counting the tokens of each line and chunk.
+///
+/// # Returns
+///
+/// * `Result<Vec<PromptChunk>>` - The chunks, or an error when the overlap doesn't leave room for new content.
+pub fn chunk_files(
+    files: &[serde_json::Value],
+    max_tokens: usize,
+    overlap: usize,
+    tokenizer: &dyn Tokenizer,
+) -> Result<Vec<PromptChunk>> {
+    check_overlap(max_tokens, overlap)?;
+
+    let paths: Vec<&str> = files
+        .iter()
+        .map(|file| file["path"].as_str().unwrap_or_default())
+        .collect();
+    let mut lines = Vec::new();
+    let mut file_tokens = vec![0; files.len()];
+    let mut offset = 0;
+    for (index, file) in files.iter().enumerate() {
+        let text = file_text(paths[index], file);
+        for line in text.split_inclusive('\n') {
+            for piece in token_windows(line, max_tokens - overlap, tokenizer) {
+                let tokens = tokenizer.count(piece);
+                lines.push(Line {
+                    file: index,
+                    text: piece.to_string(),
+                    start_token: offset,
+                    tokens,
+                });
+                file_tokens[index] += tokens;
+                offset += tokens;
+            }
+        }
+    }
+
+    let mut chunks = Vec::new();
+    let mut builder = ChunkBuilder::default();
+    let mut index = 0;
+    while index < lines.len() || builder.has_new_lines {
+        let starts_new_chunk = index == lines.len()
+            || builder.has_new_lines && {
+                let line = &lines[index];
+                let is_file_start = index == 0 || lines[index - 1].file != line.file;
+                // A file that fits in a fresh chunk isn't split, while a larger one fills the current chunk first
+                if is_file_start && file_tokens[line.file] + overlap <= max_tokens {
+                    builder.tokens + file_tokens[line.file] > max_tokens
+                } else {
+                    builder.tokens + line.tokens > max_tokens
+                }
+            };
+        if starts_new_chunk {
+            let (next, returned) = flush(&mut chunks, builder, &lines, &paths, max_tokens, overlap, tokenizer);
+            builder = next;
+            index -= returned;
+            continue;
+        }
+        builder.lines.push(index);
+        builder.tokens += lines[index].tokens;
+        builder.has_new_lines = true;
+        index += 1;
+    }
+    Ok(chunks)
+}
+
+/// Checks that the overlap of consecutive chunks leaves room for new content.
+fn check_overlap(max_tokens: usize, overlap: usize) -> Result<()> {
+    if overlap >= max_tokens {
+        return Err(anyhow::anyhow!(
+            "The chunk overlap ({} tokens) must be lower than the chunk size ({} tokens).",
+            overlap,
+            max_tokens
+        ));
+    }
+    Ok(())
+}
+
+/// Cuts a line into pieces of at most `budget` tokens, or returns it whole when it fits.
+///
+/// Each piece is the longest prefix of the rest of the line within the budget, found by doubling its length
+/// and then by bisection, so that a long line is only counted in pieces of about the budget. A single
+/// character over the budget is kept whole.
+fn token_windows<'a>(line: &'a str, budget: usize, tokenizer: &dyn Tokenizer) -> Vec<&'a str> {
+    let mut pieces = Vec::new();
+    let mut rest = line;
+    while !rest.is_empty() && tokenizer.count(rest) > budget {
+        let fits = |length: usize| tokenizer.count(&rest[..length]) <= budget;
+        let (mut low, mut high) = (0, rest.len());
+        let mut probe = budget.max(1);
+        while probe < rest.len() {
+            let length = ceil_char_boundary(rest, probe);
+            if !fits(length) {
+                high = length;
+                break;
+            }
+            low = length;
+            probe *= 2;
+        }
+        while high - low > 1 {
+            let mid = ceil_char_boundary(rest, low + (high - low) / 2);
+            if mid >= high {
+                break;
+            }
+            if fits(mid) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        let cut = if low == 0 { ceil_char_boundary(rest, 1) } else { low };
+        pieces.push(&rest[..cut]);
+        rest = &rest[cut..];
+    }
+    if !rest.is_empty() {
+        pieces.push(rest);
+    }
+    pieces
+}
+
+/// Returns the smallest index not lower than `index` that lies on a character boundary of `s`.
+fn ceil_char_boundary(s: &str, index: usize) -> usize {
+    (index..s.len()).find(|i| s.is_char_boundary(*i)).unwrap_or(s.len())
+}
+
+/// Emits the chunk being built, returning the next one seeded with its last lines up to `overlap` tokens,
+/// and the number of trailing lines given back to be packed again.
+///
+/// The text of the chunk is counted as a whole: while it exceeds `max_tokens`, its last new lines are given
+/// back, and then its carried lines are dropped, keeping at least one new line.
+fn flush(
+    chunks: &mut Vec<PromptChunk>,
+    mut builder: ChunkBuilder,
+    lines: &[Line],
+    paths: &[&str],
+    max_tokens: usize,
+    overlap: usize,
+    tokenizer: &dyn Tokenizer,
+) -> (ChunkBuilder, usize) {
+    let content = |kept: &[usize]| -> String { kept.iter().map(|&index| lines[index].text.as_str()).collect() };
+    let mut returned = 0;
+    while builder.lines.len() > builder.carried + 1 && tokenizer.count(&content(&builder.lines)) > max_tokens {
+        builder.lines.pop();
+        returned += 1;
+    }
+    while builder.carried > 0 && tokenizer.count(&content(&builder.lines)) > max_tokens {
+        builder.lines.remove(0);
+        builder.carried -= 1;
+    }
+
+    let first = &lines[builder.lines[0]];
+    let last = &lines[*builder.lines.last().unwrap()];
+    let mut files: Vec<String> = Vec::new();
+    for &index in &builder.lines {
+        let path = paths[lines[index].file];
+        if files.last().map(String::as_str) != Some(path) {
+            files.push(path.to_string());
+        }
+    }
+    chunks.push(PromptChunk {
+        index: chunks.len(),
+        content: content(&builder.lines),
+        files,
+        start_token: first.start_token,
+        end_token: last.start_token + last.tokens,
+    });
+
+    let mut next = ChunkBuilder::default();
+    if overlap == 0 {
+        return (next, returned);
+    }
+    for &index in builder.lines.iter().rev() {
+        if next.tokens + lines[index].tokens > overlap {
+            break;
+        }
+        next.lines.insert(0, index);
+        next.tokens += lines[index].tokens;
+    }
+    next.carried = next.lines.len();
+    (next, returned)
+}
+
+/// Returns the text of a file in the chunks: its path followed by its content.
+fn file_text(path: &str, file: &serde_json::Value) -> String {
+    format!("`{}`:\n\n{}\n\n", path, file["code"].as_str().unwrap_or_default())
+}
+
+/// Splits a rendered prompt into chunks of at most `max_tokens` tokens cut from its token stream
+///
+/// The prompt is encoded once, and each chunk decodes a range of its tokens, so that the chunks without
+/// overlap concatenate back to the exact prompt. A range is shortened to end between two characters, or
+/// extended when a single character is encoded in more tokens than the budget. Each chunk after the first
+/// starts `overlap` tokens before the end of the previous one. The token offsets are positions in the
+/// token stream of the whole prompt, and the files of a chunk are the ones whose code it overlaps, found in
+/// the prompt in their order.
+///
+/// # Arguments
+///
+/// * `prompt` - The rendered prompt.
+/// * `files` - The JSON representations of the files of the prompt, with their `path` and `code`.
+/// * `max_tokens` - The token budget of a chunk; a single character may exceed it.
+/// * `overlap` - The tokens shared by consecutive chunks, lower than `max_tokens`.
+/// * `tokenizer` - The tokenizer encoding and decoding the prompt, such as a tiktoken encoding.
+///
+/// # Returns
+///
+/// * `Result<Vec<PromptChunk>>` - The chunks, or an error when the overlap doesn't leave room for new content
+///   or the tokenizer only counts tokens.
+pub fn chunk_prompt_exact(
+    prompt: &str,
+    files: &[serde_json::Value],
+    max_tokens: usize,
+    overlap: usize,
+    tokenizer: &dyn Tokenizer,
+) -> Result<Vec<PromptChunk>> {
+    check_overlap(max_tokens, overlap)?;
+
+    let unsupported =
+        || anyhow::anyhow!("Exact chunking needs a tokenizer that exposes its tokens, such as a tiktoken encoding.");
+    let tokens = tokenizer.encode(prompt).ok_or_else(unsupported)?;
+    // The byte offset of each token in the prompt, and of its end
+    let mut offsets = vec![0];
+    for token in &tokens {
+        let bytes = tokenizer.decode(std::slice::from_ref(token)).ok_or_else(unsupported)?;
+        offsets.push(offsets.last().unwrap() + bytes.len());
+    }
+    let is_boundary = |index: usize| prompt.is_char_boundary(offsets[index]);
+
+    // The byte range of the code of each file, searched after the previous one
+    let mut file_ranges = Vec::new();
+    let mut position = 0;
+    for file in files {
+        let (Some(path), Some(code)) = (file["path"].as_str(), file["code"].as_str()) else {
+            continue;
+        };
+        if let Some(found) = prompt[position..].find(code) {
+            let start = position + found;
+            position = start + code.len();
+            file_ranges.push((path, start, position));
+        }
+    }
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < tokens.len() {
+        let mut end = (start + max_tokens).min(tokens.len());
+        while end > start + 1 && !is_boundary(end) {
+            end -= 1;
+        }
+        // A character encoded in more tokens than the budget is kept whole
+        while !is_boundary(end) {
+            end += 1;
+        }
+
+        let content = tokenizer.decode(&tokens[start..end]).ok_or_else(unsupported)?;
+        let (start_byte, end_byte) = (offsets[start], offsets[end]);
+        chunks.push(PromptChunk {
+            index: chunks.len(),
+            content: String::from_utf8(content)?,
+            files: file_ranges
+                .iter()
+                .filter(|(_, file_start, file_end)| *file_start < end_byte && start_byte < *file_end)
+                .map(|(path, _, _)| path.to_string())
+                .collect(),
+            start_token: start,
+            end_token: end,
+        });
+        if end == tokens.len() {
+            break;
+        }
+        // The overlap starts between two characters too, after the start of the previous chunk
+        let mut next = end.saturating_sub(overlap).max(start + 1);
+        while next > start + 1 && !is_boundary(next) {
+            next -= 1;
+        }
+        while !is_boundary(next) {
+            next += 1;
+        }
+        start = next;
+    }
+    Ok(chunks)
+}
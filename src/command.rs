@@ -0,0 +1,172 @@
+//! This module runs user-provided shell commands and captures their output as prompt context.
+//!
+//! Commands are executed as-is through the platform shell, so they must only ever come from the
+//! user's own configuration. Each command is subject to a timeout, and the values of sensitive
+//! environment variables are redacted from the captured output.
+
+use anyhow::{Context, Result};
+use log::debug;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::json;
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// The timeout applied to each command when none is configured.
+pub const DEFAULT_COMMAND_TIMEOUT_SECS: u64 = 60;
+
+/// Environment variable names whose values are redacted from command output.
+static SENSITIVE_ENV_NAME: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)(TOKEN|SECRET|PASSWORD|PASSWD|API_?KEY|ACCESS_?KEY|PRIVATE|CREDENTIAL|AUTH|SESSION)").unwrap()
+});
+
+/// Runs each command in the given directory and captures its output.
+///
+/// # Arguments
+///
+/// * `commands` - The shell commands to run, in order.
+/// * `working_dir` - The directory the commands are run in.
+/// * `timeout` - The maximum duration of each command before it is killed.
+///
+/// # Returns
+///
+/// * `Result<Vec<serde_json::Value>>` - One JSON object per command with `command`, `exit_code`, `stdout`, `stderr` and `timed_out`.
+pub fn run_commands(
+    commands: &[String],
+    working_dir: &Path,
+    timeout: Duration,
+) -> Result<Vec<serde_json::Value>> {
+    commands
+        .iter()
+        .map(|command| run_command(command, working_dir, timeout))
+        .collect()
+}
+
+/// Runs a single command through the platform shell, killing it after `timeout`.
+fn run_command(command: &str, working_dir: &Path, timeout: Duration) -> Result<serde_json::Value> {
+    let mut child = shell(command)
+        .current_dir(working_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run command: {}", command))?;
+
+    // Drain the pipes on separate threads so a chatty command can't block on a full pipe
+    let stdout = capture(child.stdout.take().context("Failed to capture stdout")?);
+    let stderr = capture(child.stderr.take().context("Failed to capture stderr")?);
+
+    let start = Instant::now();
+    let mut timed_out = false;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if start.elapsed() >= timeout {
+            timed_out = true;
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+
+    // A timed out command may have left descendants holding the pipes open, so only wait for
+    // the readers to reach the end of the output when the command exited on its own
+    let (stdout, stdout_reader) = stdout;
+    let (stderr, stderr_reader) = stderr;
+    if !timed_out {
+        let _ = stdout_reader.join();
+        let _ = stderr_reader.join();
+    }
+    debug!("Command `{}` finished with status {:?}", command, status);
+
+    Ok(json!({
+        "command": command,
+        "exit_code": status.and_then(|s| s.code()),
+        "stdout": redact_env(&snapshot(&stdout)),
+        "stderr": redact_env(&snapshot(&stderr)),
+        "timed_out": timed_out,
+    }))
+}
+
+/// Reads a pipe to its end on a separate thread, collecting the bytes read so far in a shared buffer.
+fn capture<R: Read + Send + 'static>(mut pipe: R) -> (Arc<Mutex<Vec<u8>>>, JoinHandle<()>) {
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    let shared = Arc::clone(&buffer);
+    let handle = std::thread::spawn(move || {
+        let mut chunk = [0u8; 4096];
+        while let Ok(read) = pipe.read(&mut chunk) {
+            if read == 0 {
+                break;
+            }
+            shared.lock().unwrap().extend_from_slice(&chunk[..read]);
+        }
+    });
+    (buffer, handle)
+}
+
+/// Returns the output captured so far as a string.
+fn snapshot(buffer: &Arc<Mutex<Vec<u8>>>) -> String {
+    String::from_utf8_lossy(&buffer.lock().unwrap()).into_owned()
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.args(["/C", command]);
+    cmd
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", command]);
+    cmd
+}
+
+/// Replaces the values of sensitive environment variables with a `[REDACTED:NAME]` marker.
+///
+/// # Arguments
+///
+/// * `text` - The captured command output.
+///
+/// # Returns
+///
+/// * `String` - The output with sensitive values redacted.
+pub fn redact_env(text: &str) -> String {
+    redact_vars(text, std::env::vars_os())
+}
+
+/// Replaces the values of the sensitive variables among `vars` with a `[REDACTED:NAME]` marker, like
+/// [`redact_env`] does for the variables of the process environment.
+///
+/// # Arguments
+///
+/// * `text` - The captured command output.
+/// * `vars` - The names and values of the variables.
+///
+/// # Returns
+///
+/// * `String` - The output with sensitive values redacted.
+pub fn redact_vars(text: &str, vars: impl IntoIterator<Item = (OsString, OsString)>) -> String {
+    // Entries that aren't valid UTF-8 can't appear in the text, so they are skipped
+    let mut secrets: Vec<(String, String)> = vars
+        .into_iter()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .filter(|(name, value)| SENSITIVE_ENV_NAME.is_match(name) && value.len() >= 4)
+        .collect();
+    // Replace longer values first so overlapping secrets are fully redacted
+    secrets.sort_by_key(|(_, value)| std::cmp::Reverse(value.len()));
+
+    let mut redacted = text.to_string();
+    for (name, value) in secrets {
+        redacted = redacted.replace(&value, &format!("[REDACTED:{}]", name));
+    }
+    redacted
+}
@@ -0,0 +1,203 @@
+//! This module parses unified diffs into structured file diffs and hunks.
+
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+/// The kind of a line of a hunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffLineKind {
+    /// A line present in both versions, prefixed by a space.
+    Context,
+    /// A line only present in the new version, prefixed by `+`.
+    Added,
+    /// A line only present in the old version, prefixed by `-`.
+    Removed,
+}
+
+/// A line of a hunk, without its prefix.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    pub content: String,
+}
+
+/// A hunk of a file diff, with the line ranges it covers in the old and new versions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffHunk {
+    /// The first line of the hunk in the old version, starting at 1, or 0 when the range is empty.
+    pub old_start: usize,
+    /// The number of lines of the hunk in the old version.
+    pub old_lines: usize,
+    /// The first line of the hunk in the new version, starting at 1, or 0 when the range is empty.
+    pub new_start: usize,
+    /// The number of lines of the hunk in the new version.
+    pub new_lines: usize,
+    /// The text after the closing `@@` of the hunk header, e.g. the enclosing function.
+    pub section: String,
+    pub lines: Vec<DiffLine>,
+}
+
+/// The diff of a file, made of hunks.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FileDiff {
+    /// The path of the old version, or `None` when the file was added.
+    pub old_path: Option<String>,
+    /// The path of the new version, or `None` when the file was deleted.
+    pub new_path: Option<String>,
+    /// The hunks of the diff, empty for binary files and mode changes.
+    pub hunks: Vec<DiffHunk>,
+}
+
+impl FileDiff {
+    /// Returns the path of the file: the new path, or the old one when the file was deleted.
+    pub fn path(&self) -> &str {
+        self.new_path.as_deref().or(self.old_path.as_deref()).unwrap_or("")
+    }
+
+    /// Returns the numbers of the lines added or changed in the new version, starting at 1.
+    pub fn added_lines(&self) -> BTreeSet<usize> {
+        let mut added = BTreeSet::new();
+        for hunk in &self.hunks {
+            let mut number = hunk.new_start;
+            for line in &hunk.lines {
+                match line.kind {
+                    DiffLineKind::Context => number += 1,
+                    DiffLineKind::Added => {
+                        added.insert(number);
+                        number += 1;
+                    }
+                    DiffLineKind::Removed => {}
+                }
+            }
+        }
+        added
+    }
+}
+
+/// Parses a unified diff, as printed by `git diff`, into the diffs of its files
+///
+/// Lines outside of the file headers and hunks, e.g. `index` or `similarity` lines, are ignored.
+///
+/// # Arguments
+///
+/// * `text` - The unified diff
+///
+/// # Returns
+///
+/// * `Vec<FileDiff>` - The diffs of the files, in the order of the diff
+pub fn parse_unified_diff(text: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    // The lines left in the current hunk, in the old and new versions
+    let mut remaining = (0usize, 0usize);
+
+    for line in text.lines() {
+        if remaining != (0, 0) {
+            let Some(hunk) = files.last_mut().and_then(|file| file.hunks.last_mut()) else {
+                remaining = (0, 0);
+                continue;
+            };
+            let (kind, content) = match line.split_at_checked(1) {
+                Some(("+", content)) => (DiffLineKind::Added, content),
+                Some(("-", content)) => (DiffLineKind::Removed, content),
+                Some((" ", content)) => (DiffLineKind::Context, content),
+                // `\ No newline at end of file`
+                Some(("\\", _)) => continue,
+                // Some tools strip the space of empty context lines
+                _ if line.is_empty() => (DiffLineKind::Context, ""),
+                _ => {
+                    remaining = (0, 0);
+                    continue;
+                }
+            };
+            if kind != DiffLineKind::Added {
+                remaining.0 = remaining.0.saturating_sub(1);
+            }
+            if kind != DiffLineKind::Removed {
+                remaining.1 = remaining.1.saturating_sub(1);
+            }
+            hunk.lines.push(DiffLine {
+                kind,
+                content: content.to_string(),
+            });
+            continue;
+        }
+
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            let (old_path, new_path) = match paths.split_once(" b/") {
+                Some((old, new)) => (strip_path_prefix(old), Some(new.to_string())),
+                None => (None, None),
+            };
+            files.push(FileDiff {
+                old_path,
+                new_path,
+                hunks: Vec::new(),
+            });
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            // A diff without `diff --git` headers starts a file at its `---` line
+            let starts_file = files.last().is_none_or(|file| !file.hunks.is_empty());
+            if starts_file {
+                files.push(FileDiff::default());
+            }
+            files.last_mut().unwrap().old_path = strip_path_prefix(path);
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            if let Some(file) = files.last_mut() {
+                file.new_path = strip_path_prefix(path);
+            }
+        } else if line.starts_with("new file mode") {
+            if let Some(file) = files.last_mut() {
+                file.old_path = None;
+            }
+        } else if line.starts_with("deleted file mode") {
+            if let Some(file) = files.last_mut() {
+                file.new_path = None;
+            }
+        } else if let Some(hunk) = parse_hunk_header(line) {
+            if files.is_empty() {
+                files.push(FileDiff::default());
+            }
+            remaining = (hunk.old_lines, hunk.new_lines);
+            files.last_mut().unwrap().hunks.push(hunk);
+        }
+    }
+
+    files
+}
+
+/// Parses a hunk header, e.g. `@@ -1,3 +1,4 @@ fn main() {`, into an empty hunk.
+fn parse_hunk_header(line: &str) -> Option<DiffHunk> {
+    let rest = line.strip_prefix("@@ -")?;
+    let (ranges, section) = rest.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let (old_start, old_lines) = parse_range(old)?;
+    let (new_start, new_lines) = parse_range(new)?;
+    Some(DiffHunk {
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        section: section.trim_start().to_string(),
+        lines: Vec::new(),
+    })
+}
+
+/// Parses the range of a hunk header, e.g. `12,5`, where the count defaults to 1.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// Removes the `a/` or `b/` prefix of a path of a diff header, returning `None` for `/dev/null`.
+fn strip_path_prefix(path: &str) -> Option<String> {
+    let path = path.split('\t').next().unwrap_or(path).trim_end();
+    if path == "/dev/null" {
+        return None;
+    }
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    Some(path.to_string())
+}
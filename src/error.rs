@@ -0,0 +1,52 @@
+//! This module defines the errors of the public API, so that callers can tell the main failure modes apart.
+
+use std::path::PathBuf;
+
+/// An error generating a prompt.
+///
+/// The functions of the crate build their errors with `anyhow` internally; converting an `anyhow::Error`
+/// recovers the `Code2PromptError` it was created from, or else wraps it in [`Code2PromptError::Other`].
+/// An error with context added on top of it is wrapped too, so that the context isn't lost.
+#[derive(Debug, thiserror::Error)]
+pub enum Code2PromptError {
+    /// The custom template file can't be read, e.g. because it doesn't exist.
+    #[error("Failed to read custom template file {}: {error}", path.display())]
+    TemplateNotFound { path: PathBuf, error: std::io::Error },
+    /// A template doesn't parse or fails to render.
+    #[error("{0}")]
+    Template(String),
+    /// The path isn't inside a git repository, although an option requires one.
+    #[error("Not inside a git repository: {} (a git repository is required by {option})", path.display())]
+    NotAGitRepository { path: PathBuf, option: &'static str },
+    /// A glob or regular expression pattern of the configuration is invalid.
+    #[error("Invalid {kind} pattern: {pattern} ({error})")]
+    InvalidPattern {
+        /// The option the pattern comes from, e.g. `header`.
+        kind: &'static str,
+        pattern: String,
+        error: String,
+    },
+    /// An option expecting two branches, e.g. `git_diff_branch`, got another number of them.
+    #[error("Please provide exactly two branches separated by a comma.")]
+    InvalidBranches { option: &'static str, value: String },
+    /// The options can't be combined, or have an invalid value.
+    #[error("{0}")]
+    InvalidOptions(String),
+    /// Any other failure, e.g. a file that can't be read or written.
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl From<anyhow::Error> for Code2PromptError {
+    fn from(error: anyhow::Error) -> Self {
+        // The outermost message is the error's own unless some context was added on top of it
+        let is_outermost = error
+            .downcast_ref::<Code2PromptError>()
+            .is_some_and(|inner| inner.to_string() == error.to_string());
+        if is_outermost {
+            error.downcast().unwrap_or_else(Code2PromptError::Other)
+        } else {
+            Code2PromptError::Other(error)
+        }
+    }
+}
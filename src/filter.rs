@@ -1,11 +1,306 @@
 //! This module contains the logic for filtering files based on include and exclude patterns.
 
+use crate::Code2PromptConfig;
+use anyhow::{Context, Result};
 use colored::*;
 use glob::Pattern;
 use log::{debug, error};
 use std::fs;
 use std::path::Path;
 
+/// A named set of include and exclude patterns for a common stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilterPreset {
+    pub name: &'static str,
+    pub include: &'static [&'static str],
+    pub exclude: &'static [&'static str],
+}
+
+/// The filter presets, which only exclude build outputs, dependencies and lock files so that docs stay included.
+pub const FILTER_PRESETS: &[FilterPreset] = &[
+    FilterPreset {
+        name: "rust",
+        include: &[],
+        exclude: &["**/target/**", "*.lock", "**/*.rs.bk"],
+    },
+    FilterPreset {
+        name: "node",
+        include: &[],
+        exclude: &[
+            "**/node_modules/**",
+            "**/dist/**",
+            "**/build/**",
+            "**/coverage/**",
+            "**/.next/**",
+            "*.lock",
+            "**/package-lock.json",
+            "**/pnpm-lock.yaml",
+            "*.min.js",
+            "*.map",
+        ],
+    },
+    FilterPreset {
+        name: "python",
+        include: &[],
+        exclude: &[
+            "**/__pycache__/**",
+            "*.pyc",
+            "**/.venv/**",
+            "**/venv/**",
+            "**/.tox/**",
+            "**/.mypy_cache/**",
+            "**/.pytest_cache/**",
+            "**/*.egg-info/**",
+            "**/dist/**",
+            "**/build/**",
+            "*.lock",
+        ],
+    },
+    FilterPreset {
+        name: "go",
+        include: &[],
+        exclude: &["**/vendor/**", "**/bin/**", "**/go.sum"],
+    },
+];
+
+/// Looks up a filter preset by name.
+///
+/// # Arguments
+///
+/// * `name` - The name of the preset, e.g. `rust` or `node`.
+///
+/// # Returns
+///
+/// * `Result<&'static FilterPreset>` - The preset, or an error listing the available presets.
+pub fn filter_preset(name: &str) -> Result<&'static FilterPreset> {
+    FILTER_PRESETS
+        .iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| {
+            let names: Vec<&str> = FILTER_PRESETS.iter().map(|preset| preset.name).collect();
+            anyhow::anyhow!(
+                "Unknown filter preset: {} (available presets: {})",
+                name,
+                names.join(", ")
+            )
+        })
+}
+
+/// Reads newline-separated patterns from a file, ignoring blank lines and `#` comments.
+///
+/// # Arguments
+///
+/// * `path` - The path to the pattern file.
+///
+/// # Returns
+///
+/// * `Result<Vec<String>>` - The patterns, in the order of the file.
+pub fn read_pattern_file(path: &Path) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read pattern file: {}", path.display()))?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// File name patterns of files that likely contain secrets, such as keys and credentials.
+pub const SECRET_FILE_PATTERNS: &[&str] = &[
+    ".env",
+    ".env.*",
+    "*.env",
+    "id_rsa",
+    "id_dsa",
+    "id_ecdsa",
+    "id_ed25519",
+    "*.pem",
+    "*.key",
+    "*.p12",
+    "*.pfx",
+    "*.jks",
+    "*.keystore",
+    "credentials.json",
+    "credentials",
+    "secrets.json",
+    "secrets.yml",
+    "secrets.yaml",
+    ".netrc",
+    ".npmrc",
+    ".pypirc",
+    ".htpasswd",
+];
+
+/// Determines whether a file name looks like it holds secrets.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file to be checked.
+/// * `extra_patterns` - Additional file name patterns to check on top of [`SECRET_FILE_PATTERNS`].
+///
+/// # Returns
+///
+/// * `bool` - `true` if the file name matches one of the secret patterns, `false` otherwise.
+pub fn is_likely_secret(path: &Path, extra_patterns: &[String]) -> bool {
+    let file_name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+    SECRET_FILE_PATTERNS
+        .iter()
+        .copied()
+        .chain(extra_patterns.iter().map(String::as_str))
+        .any(|pattern| {
+            Pattern::new(pattern)
+                .map(|p| p.matches(file_name))
+                .unwrap_or(false)
+        })
+}
+
+/// Directory names whose contents are considered tests.
+pub const TEST_DIRECTORIES: &[&str] = &["test", "tests", "__tests__", "spec", "specs"];
+
+/// File name patterns of test files, by language naming convention.
+pub const TEST_FILE_PATTERNS: &[&str] = &[
+    // Go
+    "*_test.go",
+    // Python
+    "test_*.py",
+    "*_test.py",
+    "conftest.py",
+    // JavaScript / TypeScript
+    "*.test.js",
+    "*.test.jsx",
+    "*.test.ts",
+    "*.test.tsx",
+    "*.test.mjs",
+    "*.spec.js",
+    "*.spec.jsx",
+    "*.spec.ts",
+    "*.spec.tsx",
+    "*.spec.mjs",
+    // Ruby
+    "*_spec.rb",
+    "*_test.rb",
+    // Java / Kotlin / C#
+    "*Test.java",
+    "*Tests.java",
+    "*Test.kt",
+    "*Tests.cs",
+    // Rust
+    "*_test.rs",
+];
+
+/// Determines whether a path looks like a test file, from its directories and its file name.
+///
+/// # Arguments
+///
+/// * `relative_path` - The path relative to the root directory, so that the root itself is never considered.
+///
+/// # Returns
+///
+/// * `bool` - `true` if a directory is in [`TEST_DIRECTORIES`] or the file name matches [`TEST_FILE_PATTERNS`].
+pub fn is_test_file(relative_path: &str) -> bool {
+    let components: Vec<&str> = relative_path.split(['/', '\\']).filter(|c| !c.is_empty()).collect();
+    let Some((file_name, directories)) = components.split_last() else {
+        return false;
+    };
+    directories.iter().any(|directory| TEST_DIRECTORIES.contains(directory))
+        || TEST_FILE_PATTERNS.iter().any(|pattern| {
+            Pattern::new(pattern)
+                .map(|p| p.matches(file_name))
+                .unwrap_or(false)
+        })
+}
+
+/// Determines whether a file is excluded by its language, like an exclude pattern, see [`should_include_file_with_options`].
+fn is_excluded_language(
+    language: Option<&str>,
+    path_str: &str,
+    exclude_languages: &[String],
+    include_patterns: &[String],
+    include_priority: bool,
+) -> bool {
+    let Some(language) = language else {
+        return false;
+    };
+    exclude_languages.iter().any(|excluded| excluded.eq_ignore_ascii_case(language))
+        && !(include_priority && is_explicitly_included(path_str, include_patterns))
+}
+
+/// Determines whether a file is excluded as a test file, unless an explicit include pattern matches it.
+///
+/// # Arguments
+///
+/// * `relative_path` - The path relative to the root directory.
+/// * `path_str` - The absolute path, matched against the include patterns.
+/// * `include_patterns` - A slice of strings representing the include patterns.
+///
+/// # Returns
+///
+/// * `bool` - `true` if the file is a test file that no include pattern matches.
+pub fn is_excluded_test_file(relative_path: &str, path_str: &str, include_patterns: &[String]) -> bool {
+    is_test_file(relative_path) && !is_explicitly_included(path_str, include_patterns)
+}
+
+/// Determines whether an include pattern matches a file, which then overrides the options leaving it out.
+///
+/// # Arguments
+///
+/// * `path_str` - The absolute path, matched against the include patterns.
+/// * `include_patterns` - A slice of strings representing the include patterns.
+///
+/// # Returns
+///
+/// * `bool` - `true` if any include pattern matches the file.
+pub fn is_explicitly_included(path_str: &str, include_patterns: &[String]) -> bool {
+    include_patterns
+        .iter()
+        .any(|pattern| Pattern::new(pattern).map(|p| p.matches(path_str)).unwrap_or(false))
+}
+
+/// Determines whether a file passes the filters of the traversal: the include and exclude patterns, then
+/// the options leaving files out
+///
+/// The options are checked in order, after the patterns:
+///
+/// 1. `no_tests` leaves out the test files, unless an include pattern matches them, see [`is_excluded_test_file`].
+/// 2. `exclude_languages` leaves out the files whose language is one of them, compared case-insensitively with
+///    the names of [`crate::language::language_name`], e.g. `sql` or `protobuf`. Like for exclude patterns, an
+///    include pattern matching the file only keeps it with `include_priority`.
+///
+/// # Arguments
+///
+/// * `path_str` - The absolute path, matched against the patterns.
+/// * `relative_path` - The path relative to the root directory, matched against the test conventions.
+/// * `language` - The language of the file, detected from its extension or overridden.
+/// * `include_patterns` - A slice of strings representing the include patterns.
+/// * `exclude_patterns` - A slice of strings representing the exclude patterns.
+/// * `config` - The configuration, with `include_priority`, `no_tests` and `exclude_languages`.
+///
+/// # Returns
+///
+/// * `bool` - `true` if the file should be included, `false` otherwise.
+pub fn should_include_file_with_options(
+    path_str: &str,
+    relative_path: &str,
+    language: Option<&str>,
+    include_patterns: &[String],
+    exclude_patterns: &[String],
+    config: &Code2PromptConfig,
+) -> bool {
+    matches_filters(path_str, include_patterns, exclude_patterns, config.include_priority)
+        && !(config.no_tests && is_excluded_test_file(relative_path, path_str, include_patterns))
+        && !is_excluded_language(
+            language,
+            path_str,
+            &config.exclude_languages,
+            include_patterns,
+            config.include_priority,
+        )
+}
+
 /// Determines whether a file should be included based on include and exclude patterns.
 ///
 /// # Arguments
@@ -33,7 +328,27 @@ pub fn should_include_file(
         }
     };
     let path_str = canonical_path.to_str().unwrap();
+    matches_filters(path_str, include_patterns, exclude_patterns, include_priority)
+}
 
+/// Determines whether a path string passes the include and exclude patterns, without touching the filesystem.
+///
+/// # Arguments
+///
+/// * `path_str` - The absolute path to be checked.
+/// * `include_patterns` - A slice of strings representing the include patterns.
+/// * `exclude_patterns` - A slice of strings representing the exclude patterns.
+/// * `include_priority` - A boolean indicating whether to give priority to include patterns if both include and exclude patterns match.
+///
+/// # Returns
+///
+/// * `bool` - `true` if the path should be included, `false` otherwise.
+pub fn matches_filters(
+    path_str: &str,
+    include_patterns: &[String],
+    exclude_patterns: &[String],
+    include_priority: bool,
+) -> bool {
     // ~~~ Check glob patterns ~~~
     let included = include_patterns
         .iter()
@@ -1,11 +1,93 @@
 //! This module handles git operations.
 
+use crate::diff::{parse_unified_diff, FileDiff};
+use crate::error::Code2PromptError;
 use anyhow::{Context, Result};
-use git2::{DiffOptions, Repository};
+use git2::{
+    Delta, Diff, DiffOptions, DiffStatsFormat, IndexEntryExtendedFlag, Patch, Repository, Sort, Status,
+    StatusOptions,
+};
 use log::info;
-use std::path::Path;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-/// Generates a git diff for the repository at the provided path
+/// A file changed since a git reference, along with its patch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    /// The path of the file, relative to the path the changes were requested for.
+    pub path: PathBuf,
+    /// The kind of change: `added`, `modified`, `deleted`, `renamed` or `typechange`.
+    pub status: String,
+    /// The unified diff of the file.
+    pub diff: String,
+}
+
+/// An author of commits of a repository, as listed by `git shortlog -sn`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Contributor {
+    pub name: String,
+    /// The number of commits reachable from HEAD authored under this name.
+    pub commits: usize,
+}
+
+/// The algorithm used to compute diffs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiffAlgorithm {
+    #[default]
+    Myers,
+    Minimal,
+    Patience,
+}
+
+impl FromStr for DiffAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "myers" | "default" => Ok(DiffAlgorithm::Myers),
+            "minimal" => Ok(DiffAlgorithm::Minimal),
+            "patience" => Ok(DiffAlgorithm::Patience),
+            "histogram" => Err(anyhow::anyhow!(
+                "The histogram diff algorithm isn't supported by libgit2, use patience instead"
+            )),
+            _ => Err(anyhow::anyhow!(
+                "Unknown diff algorithm: {} (expected myers, minimal or patience)",
+                name
+            )),
+        }
+    }
+}
+
+/// The options of the generated diffs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffSettings {
+    /// Ignore whitespace when comparing lines, like `git diff -w`.
+    pub ignore_whitespace: bool,
+    /// The diff algorithm, like `git diff --diff-algorithm=`.
+    pub algorithm: DiffAlgorithm,
+}
+
+impl DiffSettings {
+    /// Builds the libgit2 diff options for these settings.
+    fn options(&self) -> DiffOptions {
+        let mut options = DiffOptions::new();
+        options
+            .ignore_whitespace(self.ignore_whitespace)
+            .minimal(self.algorithm == DiffAlgorithm::Minimal)
+            .patience(self.algorithm == DiffAlgorithm::Patience);
+        options
+    }
+}
+
+/// The settings the diff functions used before diff settings were configurable.
+const LEGACY_DIFF_SETTINGS: DiffSettings = DiffSettings {
+    ignore_whitespace: true,
+    algorithm: DiffAlgorithm::Myers,
+};
+
+/// Generates a git diff for the repository at the provided path, ignoring whitespace
 ///
 /// # Arguments
 ///
@@ -15,18 +97,23 @@ use std::path::Path;
 ///
 /// * `Result<String, git2::Error>` - The generated git diff as a string or an error
 pub fn get_git_diff(repo_path: &Path) -> Result<String> {
+    get_git_diff_with_settings(repo_path, &LEGACY_DIFF_SETTINGS)
+}
+
+/// Generates a git diff of the staged changes for the repository at the provided path
+///
+/// # Arguments
+///
+/// * `repo_path` - A reference to the path of the git repository
+/// * `settings` - The whitespace handling and algorithm of the diff
+///
+/// # Returns
+///
+/// * `Result<String>` - The generated git diff as a string or an error
+pub fn get_git_diff_with_settings(repo_path: &Path, settings: &DiffSettings) -> Result<String> {
     info!("Opening repository at path: {:?}", repo_path);
     let repo = Repository::open(repo_path).context("Failed to open repository")?;
-    let head = repo.head().context("Failed to get repository head")?;
-    let head_tree = head.peel_to_tree().context("Failed to peel to tree")?;
-
-    let diff = repo
-        .diff_tree_to_index(
-            Some(&head_tree),
-            None,
-            Some(DiffOptions::new().ignore_whitespace(true)),
-        )
-        .context("Failed to generate diff")?;
+    let diff = staged_diff(&repo, settings)?;
 
     let mut diff_text = Vec::new();
     diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
@@ -39,7 +126,35 @@ pub fn get_git_diff(repo_path: &Path) -> Result<String> {
     Ok(String::from_utf8_lossy(&diff_text).into_owned())
 }
 
-/// Generates a git diff between two branches for the repository at the provided path
+/// Generates the git diff of the staged changes for the repository at the provided path, as structured
+/// file diffs with their hunks
+///
+/// # Arguments
+///
+/// * `repo_path` - A reference to the path of the git repository
+/// * `settings` - The whitespace handling and algorithm of the diff
+///
+/// # Returns
+///
+/// * `Result<Vec<FileDiff>>` - The diffs of the changed files or an error
+pub fn get_git_diff_structured(repo_path: &Path, settings: &DiffSettings) -> Result<Vec<FileDiff>> {
+    info!("Opening repository at path: {:?}", repo_path);
+    let repo = Repository::open(repo_path).context("Failed to open repository")?;
+    let diff = staged_diff(&repo, settings)?;
+
+    // The patches keep the `+`, `-` and space prefixes of the lines, which the parser relies on
+    let mut diff_text = Vec::new();
+    for index in 0..diff.deltas().len() {
+        if let Some(mut patch) = Patch::from_diff(&diff, index)? {
+            diff_text.extend_from_slice(&patch.to_buf()?);
+        }
+    }
+
+    info!("Generated structured git diff successfully");
+    Ok(parse_unified_diff(&String::from_utf8_lossy(&diff_text)))
+}
+
+/// Generates a git diff between two branches for the repository at the provided path, ignoring whitespace
 ///
 /// # Arguments
 ///
@@ -54,12 +169,103 @@ pub fn get_git_diff_between_branches(
     repo_path: &Path,
     branch1: &str,
     branch2: &str,
+) -> Result<String> {
+    get_git_diff_between_branches_with_settings(repo_path, branch1, branch2, &LEGACY_DIFF_SETTINGS)
+}
+
+/// Generates a git diff between two branches for the repository at the provided path
+///
+/// # Arguments
+///
+/// * `repo_path` - A reference to the path of the git repository
+/// * `branch1` - The name of the first branch
+/// * `branch2` - The name of the second branch
+/// * `settings` - The whitespace handling and algorithm of the diff
+///
+/// # Returns
+///
+/// * `Result<String>` - The generated git diff as a string or an error
+pub fn get_git_diff_between_branches_with_settings(
+    repo_path: &Path,
+    branch1: &str,
+    branch2: &str,
+    settings: &DiffSettings,
 ) -> Result<String> {
     info!("Opening repository at path: {:?}", repo_path);
     let repo = Repository::open(repo_path).context("Failed to open repository")?;
 
+    let diff = branches_diff(&repo, branch1, branch2, settings)?;
+
+    let mut diff_text = Vec::new();
+    diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
+        diff_text.extend_from_slice(line.content());
+        true
+    })
+    .context("Failed to print diff")?;
+
+    info!("Generated git diff between branches successfully");
+    Ok(String::from_utf8_lossy(&diff_text).into_owned())
+}
+
+/// Generates the `git diff --stat` summary of the staged changes for the repository at the provided path
+///
+/// # Arguments
+///
+/// * `repo_path` - A reference to the path of the git repository
+/// * `settings` - The whitespace handling and algorithm of the diff
+///
+/// # Returns
+///
+/// * `Result<String>` - The changed files with their insertions and deletions, followed by the totals, or an error
+pub fn get_git_diff_stat(repo_path: &Path, settings: &DiffSettings) -> Result<String> {
+    info!("Opening repository at path: {:?}", repo_path);
+    let repo = Repository::open(repo_path).context("Failed to open repository")?;
+    let diff = staged_diff(&repo, settings)?;
+    diff_stat(&diff)
+}
+
+/// Generates the `git diff --stat` summary between two branches for the repository at the provided path
+///
+/// # Arguments
+///
+/// * `repo_path` - A reference to the path of the git repository
+/// * `branch1` - The name of the first branch
+/// * `branch2` - The name of the second branch
+/// * `settings` - The whitespace handling and algorithm of the diff
+///
+/// # Returns
+///
+/// * `Result<String>` - The changed files with their insertions and deletions, followed by the totals, or an error
+pub fn get_git_diff_stat_between_branches(
+    repo_path: &Path,
+    branch1: &str,
+    branch2: &str,
+    settings: &DiffSettings,
+) -> Result<String> {
+    info!("Opening repository at path: {:?}", repo_path);
+    let repo = Repository::open(repo_path).context("Failed to open repository")?;
+    let diff = branches_diff(&repo, branch1, branch2, settings)?;
+    diff_stat(&diff)
+}
+
+/// Computes the diff between the tree of HEAD and the index.
+fn staged_diff<'repo>(repo: &'repo Repository, settings: &DiffSettings) -> Result<Diff<'repo>> {
+    let head = repo.head().context("Failed to get repository head")?;
+    let head_tree = head.peel_to_tree().context("Failed to peel to tree")?;
+
+    repo.diff_tree_to_index(Some(&head_tree), None, Some(&mut settings.options()))
+        .context("Failed to generate diff")
+}
+
+/// Computes the diff between the trees of two branches, which must exist.
+fn branches_diff<'repo>(
+    repo: &'repo Repository,
+    branch1: &str,
+    branch2: &str,
+    settings: &DiffSettings,
+) -> Result<Diff<'repo>> {
     for branch in [branch1, branch2].iter() {
-        if !branch_exists(&repo, branch) {
+        if !branch_exists(repo, branch) {
             return Err(anyhow::anyhow!("Branch {} doesn't exist!", branch));
         }
     }
@@ -70,23 +276,21 @@ pub fn get_git_diff_between_branches(
     let branch1_tree = branch1_commit.tree()?;
     let branch2_tree = branch2_commit.tree()?;
 
-    let diff = repo
-        .diff_tree_to_tree(
-            Some(&branch1_tree),
-            Some(&branch2_tree),
-            Some(DiffOptions::new().ignore_whitespace(true)),
-        )
-        .context("Failed to generate diff between branches")?;
-
-    let mut diff_text = Vec::new();
-    diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
-        diff_text.extend_from_slice(line.content());
-        true
-    })
-    .context("Failed to print diff")?;
+    repo.diff_tree_to_tree(
+        Some(&branch1_tree),
+        Some(&branch2_tree),
+        Some(&mut settings.options()),
+    )
+    .context("Failed to generate diff between branches")
+}
 
-    info!("Generated git diff between branches successfully");
-    Ok(String::from_utf8_lossy(&diff_text).into_owned())
+/// Formats the statistics of a diff like `git diff --stat`.
+fn diff_stat(diff: &Diff) -> Result<String> {
+    let stats = diff.stats().context("Failed to compute diff stats")?;
+    let buf = stats
+        .to_buf(DiffStatsFormat::FULL, 80)
+        .context("Failed to format diff stats")?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
 }
 
 /// Retrieves the git log between two branches for the repository at the provided path
@@ -137,6 +341,451 @@ pub fn get_git_log(repo_path: &Path, branch1: &str, branch2: &str) -> Result<Str
     Ok(log_text)
 }
 
+/// Lists the files changed between a reference and the working tree (including staged and untracked files),
+/// with the patch of each file
+///
+/// # Arguments
+///
+/// * `repo_path` - A reference to a path inside the git repository; only changes below it are returned
+/// * `base_ref` - The reference to compare the working tree against (e.g. "main" or "HEAD~3")
+///
+/// # Returns
+///
+/// * `Result<Vec<FileChange>>` - The changed files, with paths relative to `repo_path`, or an error
+pub fn get_git_changes(repo_path: &Path, base_ref: &str) -> Result<Vec<FileChange>> {
+    info!("Discovering repository from path: {:?}", repo_path);
+    let repo = Repository::discover(repo_path).context("Failed to discover repository")?;
+    let workdir = repo
+        .workdir()
+        .context("Repository has no working directory")?
+        .canonicalize()?;
+    let scope = repo_path
+        .canonicalize()?
+        .strip_prefix(&workdir)
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    let base_tree = repo
+        .revparse_single(base_ref)
+        .with_context(|| format!("Failed to resolve reference: {}", base_ref))?
+        .peel_to_tree()
+        .context("Failed to peel to tree")?;
+
+    let mut options = DiffOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true);
+    let diff = repo
+        .diff_tree_to_workdir_with_index(Some(&base_tree), Some(&mut options))
+        .context("Failed to generate diff")?;
+
+    let mut changes = Vec::new();
+    for (index, delta) in diff.deltas().enumerate() {
+        let file = if delta.status() == Delta::Deleted {
+            delta.old_file()
+        } else {
+            delta.new_file()
+        };
+        let relative = match file.path().and_then(|path| path.strip_prefix(&scope).ok()) {
+            Some(relative) => relative,
+            None => continue,
+        };
+
+        let status = match delta.status() {
+            Delta::Added | Delta::Untracked => "added",
+            Delta::Deleted => "deleted",
+            Delta::Renamed => "renamed",
+            Delta::Typechange => "typechange",
+            _ => "modified",
+        };
+        let patch = match Patch::from_diff(&diff, index)? {
+            Some(mut patch) => String::from_utf8_lossy(&patch.to_buf()?).into_owned(),
+            None => String::new(),
+        };
+
+        changes.push(FileChange {
+            path: relative.to_path_buf(),
+            status: status.to_string(),
+            diff: patch,
+        });
+    }
+
+    info!("Retrieved {} changed files successfully", changes.len());
+    Ok(changes)
+}
+
+/// Lists the lines added or changed since a reference in each file of the working tree, like the `+` lines
+/// of `git diff <ref>`
+///
+/// # Arguments
+///
+/// * `repo_path` - A reference to a path inside the git repository; only files below it are listed
+/// * `base_ref` - The reference to compare the working tree against (e.g. "main" or "HEAD~3")
+///
+/// # Returns
+///
+/// * `Result<HashMap<PathBuf, BTreeSet<usize>>>` - The numbers of the changed lines per file, starting at 1, with
+///   paths relative to `repo_path`, or an error
+pub fn get_changed_lines(repo_path: &Path, base_ref: &str) -> Result<HashMap<PathBuf, BTreeSet<usize>>> {
+    let changed_lines = get_git_changes(repo_path, base_ref)?
+        .into_iter()
+        .filter(|change| change.status != "deleted")
+        .map(|change| {
+            let lines = parse_unified_diff(&change.diff)
+                .iter()
+                .flat_map(FileDiff::added_lines)
+                .collect();
+            (change.path, lines)
+        })
+        .collect();
+    Ok(changed_lines)
+}
+
+/// Lists the files tracked by git, like `git ls-files`
+///
+/// Files outside of the sparse checkout are left out, as they aren't in the working tree.
+///
+/// # Arguments
+///
+/// * `repo_path` - A reference to a path inside the git repository; only files below it are listed
+///
+/// # Returns
+///
+/// * `Result<HashSet<PathBuf>>` - The tracked files, with paths relative to `repo_path`, or an error
+pub fn get_tracked_files(repo_path: &Path) -> Result<HashSet<PathBuf>> {
+    info!("Discovering repository from path: {:?}", repo_path);
+    let repo = Repository::discover(repo_path).context("Failed to discover repository")?;
+    let workdir = repo
+        .workdir()
+        .context("Repository has no working directory")?
+        .canonicalize()?;
+    let scope = repo_path
+        .canonicalize()?
+        .strip_prefix(&workdir)
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    let index = repo.index().context("Failed to read the index")?;
+    let files: HashSet<PathBuf> = index
+        .iter()
+        .filter(|entry| !IndexEntryExtendedFlag::from_bits_truncate(entry.flags_extended).is_skip_worktree())
+        .filter_map(|entry| {
+            let path = PathBuf::from(String::from_utf8_lossy(&entry.path).into_owned());
+            path.strip_prefix(&scope).ok().map(Path::to_path_buf)
+        })
+        .collect();
+
+    info!("Listed {} tracked files successfully", files.len());
+    Ok(files)
+}
+
+/// Gets the status of the changed and untracked files, like `git status --porcelain`
+///
+/// Each status is a two-letter code: the state of the file in the index, then in the working tree, e.g.
+/// `M ` for a staged modification, ` M` for an unstaged one, `A ` for a staged new file, `??` for an
+/// untracked file and `UU` for a file with merge conflicts. Ignored and unchanged files are left out.
+///
+/// # Arguments
+///
+/// * `repo_path` - A reference to a path inside the git repository; only files below it are listed
+///
+/// # Returns
+///
+/// * `Result<HashMap<PathBuf, String>>` - The status code of each file, with paths relative to `repo_path`, or an error
+pub fn get_git_statuses(repo_path: &Path) -> Result<HashMap<PathBuf, String>> {
+    info!("Discovering repository from path: {:?}", repo_path);
+    let repo = Repository::discover(repo_path).context("Failed to discover repository")?;
+    let workdir = repo
+        .workdir()
+        .context("Repository has no working directory")?
+        .canonicalize()?;
+    let scope = repo_path
+        .canonicalize()?
+        .strip_prefix(&workdir)
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+    let statuses = repo.statuses(Some(&mut options)).context("Failed to get statuses")?;
+
+    let mut codes = HashMap::new();
+    for entry in statuses.iter() {
+        let relative = match entry.path().map(Path::new).and_then(|path| path.strip_prefix(&scope).ok()) {
+            Some(relative) => relative.to_path_buf(),
+            None => continue,
+        };
+        if let Some(code) = status_code(entry.status()) {
+            codes.insert(relative, code);
+        }
+    }
+
+    info!("Retrieved the status of {} files successfully", codes.len());
+    Ok(codes)
+}
+
+/// Converts a status to its `git status --porcelain` code, or `None` for an ignored or unchanged file.
+fn status_code(status: Status) -> Option<String> {
+    if status.is_conflicted() {
+        return Some("UU".to_string());
+    }
+    if status.is_wt_new() {
+        return Some("??".to_string());
+    }
+    let index = if status.is_index_new() {
+        'A'
+    } else if status.is_index_modified() {
+        'M'
+    } else if status.is_index_deleted() {
+        'D'
+    } else if status.is_index_renamed() {
+        'R'
+    } else if status.is_index_typechange() {
+        'T'
+    } else {
+        ' '
+    };
+    let worktree = if status.is_wt_modified() {
+        'M'
+    } else if status.is_wt_deleted() {
+        'D'
+    } else if status.is_wt_renamed() {
+        'R'
+    } else if status.is_wt_typechange() {
+        'T'
+    } else {
+        ' '
+    };
+    (index != ' ' || worktree != ' ').then(|| format!("{}{}", index, worktree))
+}
+
+/// Returns whether a status code matches one of the codes of a filter
+///
+/// A two-letter code like `??` or `UU` must match exactly, while a single letter like `M` matches a file
+/// with that state in either the index or the working tree.
+///
+/// # Arguments
+///
+/// * `code` - The two-letter status code of the file, as returned by `get_git_statuses`.
+/// * `filter` - The comma-separated status codes to match, e.g. `M,??`.
+pub fn status_matches(code: &str, filter: &str) -> bool {
+    filter.split(',').map(str::trim).filter(|wanted| !wanted.is_empty()).any(|wanted| {
+        if wanted.chars().count() == 1 {
+            code.contains(wanted)
+        } else {
+            code == wanted
+        }
+    })
+}
+
+/// Counts the commits reachable from HEAD that touched each file, like `git log --oneline -- <file> | wc -l`
+///
+/// The history is walked once for all files. Merge commits are not counted, as their changes are
+/// already counted on the commits they merge.
+///
+/// # Arguments
+///
+/// * `repo_path` - A reference to a path inside the git repository; only files below it are counted
+///
+/// # Returns
+///
+/// * `Result<HashMap<PathBuf, usize>>` - The number of commits per file, with paths relative to `repo_path`, or an error
+pub fn get_commit_counts(repo_path: &Path) -> Result<HashMap<PathBuf, usize>> {
+    info!("Discovering repository from path: {:?}", repo_path);
+    let repo = Repository::discover(repo_path).context("Failed to discover repository")?;
+    let workdir = repo
+        .workdir()
+        .context("Repository has no working directory")?
+        .canonicalize()?;
+    let scope = repo_path
+        .canonicalize()?
+        .strip_prefix(&workdir)
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    let mut revwalk = repo.revwalk().context("Failed to create revwalk")?;
+    revwalk.push_head().context("Failed to push HEAD to revwalk")?;
+
+    let mut counts = HashMap::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?).context("Failed to find commit")?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let tree = commit.tree().context("Failed to get commit tree")?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree().context("Failed to get parent tree")?),
+            Err(_) => None,
+        };
+
+        let mut options = DiffOptions::new();
+        if !scope.as_os_str().is_empty() {
+            options.pathspec(&scope);
+        }
+        let diff = repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut options))
+            .context("Failed to generate diff")?;
+
+        let touched: HashSet<PathBuf> = diff
+            .deltas()
+            .filter_map(|delta| delta.new_file().path().or_else(|| delta.old_file().path()).map(Path::to_path_buf))
+            .filter_map(|path| path.strip_prefix(&scope).ok().map(Path::to_path_buf))
+            .collect();
+        for path in touched {
+            *counts.entry(path).or_insert(0) += 1;
+        }
+    }
+
+    info!("Counted commits for {} files successfully", counts.len());
+    Ok(counts)
+}
+
+/// Gets the time of the last commit reachable from HEAD that touched each file, like
+/// `git log -1 --format=%ct -- <file>`
+///
+/// The history is walked once for all files, newest first. Merge commits are skipped like in
+/// [`get_commit_counts`].
+///
+/// # Arguments
+///
+/// * `repo_path` - A reference to a path inside the git repository; only files below it are listed
+///
+/// # Returns
+///
+/// * `Result<HashMap<PathBuf, i64>>` - The Unix timestamp of the last commit per file, with paths relative to
+///   `repo_path`, or an error
+pub fn get_last_commit_times(repo_path: &Path) -> Result<HashMap<PathBuf, i64>> {
+    info!("Discovering repository from path: {:?}", repo_path);
+    let repo = Repository::discover(repo_path).context("Failed to discover repository")?;
+    let workdir = repo
+        .workdir()
+        .context("Repository has no working directory")?
+        .canonicalize()?;
+    let scope = repo_path
+        .canonicalize()?
+        .strip_prefix(&workdir)
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    let mut revwalk = repo.revwalk().context("Failed to create revwalk")?;
+    revwalk.set_sorting(Sort::TIME).context("Failed to sort revwalk")?;
+    revwalk.push_head().context("Failed to push HEAD to revwalk")?;
+
+    let mut times: HashMap<PathBuf, i64> = HashMap::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?).context("Failed to find commit")?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let tree = commit.tree().context("Failed to get commit tree")?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree().context("Failed to get parent tree")?),
+            Err(_) => None,
+        };
+
+        let mut options = DiffOptions::new();
+        if !scope.as_os_str().is_empty() {
+            options.pathspec(&scope);
+        }
+        let diff = repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut options))
+            .context("Failed to generate diff")?;
+
+        let time = commit.time().seconds();
+        for delta in diff.deltas() {
+            let path = delta.new_file().path().or_else(|| delta.old_file().path());
+            if let Some(relative) = path.and_then(|path| path.strip_prefix(&scope).ok()) {
+                let last = times.entry(relative.to_path_buf()).or_insert(time);
+                *last = (*last).max(time);
+            }
+        }
+    }
+
+    info!("Retrieved the last commit time of {} files successfully", times.len());
+    Ok(times)
+}
+
+/// Lists the authors of the commits reachable from HEAD, like `git shortlog -sn`
+///
+/// Authors are grouped by name, and sorted by decreasing commit count, then by name.
+///
+/// # Arguments
+///
+/// * `repo_path` - A reference to a path inside the git repository
+///
+/// # Returns
+///
+/// * `Result<Vec<Contributor>>` - The contributors with their commit counts, or an error
+pub fn get_contributors(repo_path: &Path) -> Result<Vec<Contributor>> {
+    info!("Discovering repository from path: {:?}", repo_path);
+    let repo = Repository::discover(repo_path).context("Failed to discover repository")?;
+
+    let mut revwalk = repo.revwalk().context("Failed to create revwalk")?;
+    revwalk.push_head().context("Failed to push HEAD to revwalk")?;
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?).context("Failed to find commit")?;
+        let name = String::from_utf8_lossy(commit.author().name_bytes()).into_owned();
+        *counts.entry(name).or_insert(0) += 1;
+    }
+
+    let mut contributors: Vec<Contributor> = counts
+        .into_iter()
+        .map(|(name, commits)| Contributor { name, commits })
+        .collect();
+    contributors.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.name.cmp(&b.name)));
+    info!("Retrieved {} contributors successfully", contributors.len());
+    Ok(contributors)
+}
+
+/// Checks that the provided path is inside a git repository, at or below its root
+///
+/// # Arguments
+///
+/// * `repo_path` - A reference to the path that should be inside a git repository
+///
+/// # Returns
+///
+/// * `Result<()>` - An empty result, or an error naming the path outside of any git repository
+pub fn ensure_git_repository(repo_path: &Path) -> Result<()> {
+    info!("Discovering repository from path: {:?}", repo_path);
+    Repository::discover(repo_path).map(|_| ()).map_err(|_| {
+        Code2PromptError::NotAGitRepository {
+            path: repo_path.to_path_buf(),
+            option: "require_git",
+        }
+        .into()
+    })
+}
+
+/// Retrieves the short SHA and the branch name of HEAD for the repository containing the provided path
+///
+/// # Arguments
+///
+/// * `repo_path` - A reference to a path inside the git repository
+///
+/// # Returns
+///
+/// * `Result<(String, String)>` - The short SHA of HEAD and the current branch name (empty when detached), or an error
+pub fn get_git_head(repo_path: &Path) -> Result<(String, String)> {
+    info!("Discovering repository from path: {:?}", repo_path);
+    let repo = Repository::discover(repo_path).context("Failed to discover repository")?;
+    let head = repo.head().context("Failed to get repository head")?;
+    let commit = head.peel_to_commit().context("Failed to peel to commit")?;
+
+    let short_sha = commit.id().to_string()[..7].to_string();
+    let branch = if head.is_branch() {
+        head.shorthand().unwrap_or_default().to_string()
+    } else {
+        String::new()
+    };
+
+    info!("Retrieved git head successfully");
+    Ok((short_sha, branch))
+}
+
 /// Checks if a local branch exists in the given repository
 ///
 /// # Arguments
@@ -148,8 +797,5 @@ pub fn get_git_log(repo_path: &Path, branch1: &str, branch2: &str) -> Result<Str
 ///
 /// * `bool` - `true` if the branch exists, `false` otherwise
 fn branch_exists(repo: &Repository, branch_name: &str) -> bool {
-    match repo.find_branch(branch_name, git2::BranchType::Local) {
-        Ok(_) => true,
-        Err(_) => false,
-    }
+    repo.find_branch(branch_name, git2::BranchType::Local).is_ok()
 }
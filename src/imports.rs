@@ -0,0 +1,389 @@
+//! This module contains the best-effort logic for extracting local imports from source files
+//! and ordering files so that dependencies precede the files that use them.
+
+use crate::path::root_relative_path;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Component, Path, PathBuf};
+
+/// Optional line-number gutter (`   1 | `) that may prefix each line of a formatted code block.
+const GUTTER: &str = r"^(?:\s*\d+ \| )?\s*";
+
+static RUST_MOD: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(r"{}(?:pub(?:\([^)]*\))?\s+)?mod\s+([A-Za-z_][A-Za-z0-9_]*)\s*;", GUTTER)).unwrap()
+});
+static RUST_USE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        r"{}(?:pub(?:\([^)]*\))?\s+)?use\s+((?:crate|self|super)(?:::[A-Za-z_][A-Za-z0-9_]*)+)",
+        GUTTER
+    ))
+    .unwrap()
+});
+static PYTHON_FROM: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(r"{}from\s+(\.*[A-Za-z_][A-Za-z0-9_.]*|\.+)\s+import\b", GUTTER)).unwrap()
+});
+static PYTHON_IMPORT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(r"{}import\s+([A-Za-z_][A-Za-z0-9_.]*)", GUTTER)).unwrap()
+});
+static JS_IMPORT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?:\bfrom\s+|\bimport\s+|\brequire\s*\(\s*|\bimport\s*\(\s*)['"](\.{1,2}/[^'"]+)['"]"#).unwrap()
+});
+static C_INCLUDE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(r#"{}#\s*include\s+"([^"]+)""#, GUTTER)).unwrap()
+});
+
+const JS_EXTENSIONS: &[&str] = &["js", "jsx", "ts", "tsx", "mjs", "cjs"];
+const C_EXTENSIONS: &[&str] = &["c", "h", "cc", "cpp", "cxx", "hpp", "hh", "hxx"];
+
+/// Extracts the import specifiers of a source file that may refer to other local files.
+///
+/// Rust imports are returned as module paths (`self::foo` for `mod foo;`, `crate::a::b`, `super::c`),
+/// Python imports as dotted module names, and JavaScript/TypeScript and C/C++ imports as the quoted path.
+///
+/// # Arguments
+///
+/// * `code` - The source code, optionally prefixed with a line-number gutter.
+/// * `extension` - The file extension used to select the language.
+///
+/// # Returns
+///
+/// * `Vec<String>` - The import specifiers, in order of appearance. Unsupported languages yield none.
+pub fn extract_imports(code: &str, extension: &str) -> Vec<String> {
+    let mut imports = Vec::new();
+    for line in code.lines() {
+        match extension {
+            "rs" => {
+                if let Some(cap) = RUST_MOD.captures(line) {
+                    imports.push(format!("self::{}", &cap[1]));
+                } else if let Some(cap) = RUST_USE.captures(line) {
+                    imports.push(cap[1].to_string());
+                }
+            }
+            "py" => {
+                if let Some(cap) = PYTHON_FROM.captures(line) {
+                    imports.push(cap[1].to_string());
+                } else if let Some(cap) = PYTHON_IMPORT.captures(line) {
+                    imports.push(cap[1].to_string());
+                }
+            }
+            ext if JS_EXTENSIONS.contains(&ext) => {
+                imports.extend(JS_IMPORT.captures_iter(line).map(|cap| cap[1].to_string()));
+            }
+            ext if C_EXTENSIONS.contains(&ext) => {
+                if let Some(cap) = C_INCLUDE.captures(line) {
+                    imports.push(cap[1].to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    imports
+}
+
+/// Resolves an import specifier to one of the known file paths.
+///
+/// # Arguments
+///
+/// * `file_path` - The path of the importing file.
+/// * `import` - The import specifier as returned by [`extract_imports`].
+/// * `known` - The set of paths the import may resolve to.
+///
+/// # Returns
+///
+/// * `Option<String>` - The resolved path, or `None` if the import is external or can't be resolved.
+pub fn resolve_import(file_path: &str, import: &str, known: &HashSet<String>) -> Option<String> {
+    let path = Path::new(file_path);
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let exists = |candidate: &Path| {
+        let candidate = normalize(candidate).to_string_lossy().to_string();
+        known.contains(&candidate).then_some(candidate)
+    };
+
+    match path.extension().and_then(|ext| ext.to_str()).unwrap_or("") {
+        "rs" => resolve_rust_import(path, dir, import, known),
+        "py" => {
+            let leading_dots = import.chars().take_while(|c| *c == '.').count();
+            let module = import[leading_dots..].replace('.', "/");
+            if leading_dots > 0 {
+                let mut base = dir.to_path_buf();
+                for _ in 1..leading_dots {
+                    base.pop();
+                }
+                let base = base.join(&module);
+                exists(&base.with_extension("py")).or_else(|| exists(&base.join("__init__.py")))
+            } else {
+                find_by_suffix(known, &[format!("{}.py", module), format!("{}/__init__.py", module)])
+            }
+        }
+        ext if JS_EXTENSIONS.contains(&ext) => {
+            let base = dir.join(import);
+            exists(&base)
+                .or_else(|| {
+                    JS_EXTENSIONS.iter().find_map(|ext| {
+                        exists(Path::new(&format!("{}.{}", base.display(), ext)))
+                    })
+                })
+                .or_else(|| {
+                    JS_EXTENSIONS
+                        .iter()
+                        .find_map(|ext| exists(&base.join(format!("index.{}", ext))))
+                })
+        }
+        ext if C_EXTENSIONS.contains(&ext) => {
+            exists(&dir.join(import)).or_else(|| find_by_suffix(known, &[import.to_string()]))
+        }
+        _ => None,
+    }
+}
+
+/// Sorts the files so that local dependencies precede the files that import them.
+///
+/// The incoming order, e.g. by path, churn or recency, breaks the ties: files whose imports can't be
+/// resolved keep their relative order, and cycles are broken deterministically by emitting the remaining
+/// file that came first.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files, as returned by the traversal.
+///
+/// # Returns
+///
+/// * `Vec<serde_json::Value>` - The files in dependency order.
+pub fn sort_topologically(files: Vec<serde_json::Value>) -> Vec<serde_json::Value> {
+    let dependencies = dependency_graph(&files);
+
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); files.len()];
+    let mut pending: Vec<usize> = vec![0; files.len()];
+    for (index, deps) in dependencies.iter().enumerate() {
+        pending[index] = deps.len();
+        for &dep in deps {
+            dependents[dep].push(index);
+        }
+    }
+
+    let mut remaining: BTreeSet<usize> = (0..files.len()).collect();
+    let mut ready: BTreeSet<usize> = remaining.iter().filter(|&&i| pending[i] == 0).copied().collect();
+    let mut order = Vec::with_capacity(files.len());
+
+    while let Some(&next) = ready.iter().next().or_else(|| remaining.iter().next()) {
+        ready.remove(&next);
+        remaining.remove(&next);
+        order.push(next);
+        for &dependent in &dependents[next] {
+            pending[dependent] = pending[dependent].saturating_sub(1);
+            if pending[dependent] == 0 && remaining.contains(&dependent) {
+                ready.insert(dependent);
+            }
+        }
+    }
+
+    let mut files: Vec<Option<serde_json::Value>> = files.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|i| files[i].take()).collect()
+}
+
+/// Builds the local dependency graph of the files.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files, as returned by the traversal.
+///
+/// # Returns
+///
+/// * `Vec<Vec<usize>>` - For each file, the indices of the files it imports, without duplicates or self-references.
+pub fn dependency_graph(files: &[serde_json::Value]) -> Vec<Vec<usize>> {
+    let paths: Vec<&str> = files
+        .iter()
+        .map(|file| file.get("path").and_then(|p| p.as_str()).unwrap_or(""))
+        .collect();
+    let known: HashSet<String> = paths.iter().map(|p| p.to_string()).collect();
+    let index: HashMap<&str, usize> = paths.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+
+    files
+        .iter()
+        .enumerate()
+        .map(|(i, file)| {
+            let code = file.get("code").and_then(|c| c.as_str()).unwrap_or("");
+            let extension = file.get("extension").and_then(|e| e.as_str()).unwrap_or("");
+            let mut deps: Vec<usize> = extract_imports(code, extension)
+                .iter()
+                .filter_map(|import| resolve_import(paths[i], import, &known))
+                .filter_map(|resolved| index.get(resolved.as_str()).copied())
+                .filter(|&dep| dep != i)
+                .collect();
+            deps.sort_unstable();
+            deps.dedup();
+            deps
+        })
+        .collect()
+}
+
+/// Renders the local dependency graph of the files in the Graphviz DOT language.
+///
+/// Each file is a node labeled with its path relative to the root directory, and each local import
+/// an edge from the importing file to the imported one. Cycles are kept as they are, while imports
+/// that don't resolve to another file, such as external packages, are left out.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files, as returned by the traversal.
+/// * `root_path` - The root directory of the traversal.
+/// * `relative_paths` - Whether the traversal produced relative paths.
+///
+/// # Returns
+///
+/// * `String` - The `digraph` of the dependencies.
+pub fn dependency_graph_dot(files: &[serde_json::Value], root_path: &Path, relative_paths: bool) -> String {
+    let labels: Vec<String> = files
+        .iter()
+        .map(|file| {
+            let path = file.get("path").and_then(|p| p.as_str()).unwrap_or("");
+            let label = root_relative_path(path, root_path, relative_paths);
+            format!("\"{}\"", label.replace('\\', "\\\\").replace('"', "\\\""))
+        })
+        .collect();
+
+    let mut dot = String::from("digraph dependencies {\n    rankdir=LR;\n");
+    for label in &labels {
+        dot.push_str(&format!("    {};\n", label));
+    }
+    for (index, deps) in dependency_graph(files).iter().enumerate() {
+        for &dep in deps {
+            dot.push_str(&format!("    {} -> {};\n", labels[index], labels[dep]));
+        }
+    }
+    dot.push('}');
+    dot
+}
+
+/// An import of a file that doesn't resolve to another file of the traversal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedImport {
+    /// The path of the importing file.
+    pub path: String,
+    /// The import specifier as returned by [`extract_imports`].
+    pub import: String,
+}
+
+/// Finds the files reachable from an entry file by following its local imports transitively.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files, as returned by the traversal.
+/// * `entry` - The index of the entry file.
+///
+/// # Returns
+///
+/// * `(BTreeSet<usize>, Vec<UnresolvedImport>)` - The indices of the reachable files, including the entry,
+///   and the imports of these files that couldn't be resolved, in the order they were found.
+pub fn reachable_files(files: &[serde_json::Value], entry: usize) -> (BTreeSet<usize>, Vec<UnresolvedImport>) {
+    let paths: Vec<&str> = files
+        .iter()
+        .map(|file| file.get("path").and_then(|p| p.as_str()).unwrap_or(""))
+        .collect();
+    let known: HashSet<String> = paths.iter().map(|p| p.to_string()).collect();
+    let index: HashMap<&str, usize> = paths.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+
+    let mut reachable = BTreeSet::from([entry]);
+    let mut unresolved = Vec::new();
+    let mut queue = VecDeque::from([entry]);
+    while let Some(i) = queue.pop_front() {
+        let code = files[i].get("code").and_then(|c| c.as_str()).unwrap_or("");
+        let extension = files[i].get("extension").and_then(|e| e.as_str()).unwrap_or("");
+        for import in extract_imports(code, extension) {
+            match resolve_import(paths[i], &import, &known).and_then(|resolved| index.get(resolved.as_str())) {
+                Some(&dep) => {
+                    if reachable.insert(dep) {
+                        queue.push_back(dep);
+                    }
+                }
+                None => unresolved.push(UnresolvedImport {
+                    path: paths[i].to_string(),
+                    import,
+                }),
+            }
+        }
+    }
+    (reachable, unresolved)
+}
+
+/// Resolves a Rust module path (`self::`, `super::` or `crate::`) to the deepest matching module file.
+fn resolve_rust_import(path: &Path, dir: &Path, import: &str, known: &HashSet<String>) -> Option<String> {
+    let is_root_module = matches!(
+        path.file_stem().and_then(|s| s.to_str()),
+        Some("lib") | Some("main") | Some("mod")
+    );
+    let module_dir = if is_root_module {
+        dir.to_path_buf()
+    } else {
+        dir.join(path.file_stem()?)
+    };
+
+    let mut segments = import.split("::");
+    let base = match segments.next()? {
+        "self" => module_dir,
+        "super" => {
+            let mut parent = module_dir;
+            parent.pop();
+            parent
+        }
+        "crate" => crate_root(dir, known)?,
+        _ => return None,
+    };
+
+    let mut current = base;
+    let mut resolved = None;
+    for segment in segments {
+        current = current.join(segment);
+        let candidates = [current.with_extension("rs"), current.join("mod.rs")];
+        match candidates
+            .iter()
+            .map(|c| normalize(c).to_string_lossy().to_string())
+            .find(|c| known.contains(c))
+        {
+            Some(found) => resolved = Some(found),
+            None => break,
+        }
+    }
+    resolved
+}
+
+/// Finds the nearest ancestor directory containing a `lib.rs` or `main.rs` crate root.
+fn crate_root(dir: &Path, known: &HashSet<String>) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|ancestor| {
+            ["lib.rs", "main.rs"]
+                .iter()
+                .any(|root| known.contains(&ancestor.join(root).to_string_lossy().to_string()))
+        })
+        .map(Path::to_path_buf)
+}
+
+/// Returns the first known path that ends with one of the given suffixes.
+fn find_by_suffix(known: &HashSet<String>, suffixes: &[String]) -> Option<String> {
+    let mut matches: Vec<&String> = known
+        .iter()
+        .filter(|path| {
+            suffixes
+                .iter()
+                .any(|suffix| path.as_str() == suffix || path.ends_with(&format!("/{}", suffix)))
+        })
+        .collect();
+    matches.sort();
+    matches.first().map(|path| path.to_string())
+}
+
+/// Lexically normalizes a path by resolving `.` and `..` components.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
@@ -0,0 +1,194 @@
+//! This module maps file extensions to the languages they are written in.
+
+/// Returns the name of the language of a file extension, e.g. `rust` for `rs`.
+///
+/// # Arguments
+///
+/// * `extension` - The file extension, without the leading dot.
+///
+/// # Returns
+///
+/// * `Option<&'static str>` - The language name, or `None` for unknown extensions.
+pub fn language_name(extension: &str) -> Option<&'static str> {
+    let language = match extension.to_ascii_lowercase().as_str() {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "js" | "mjs" | "cjs" | "jsx" => "javascript",
+        "ts" | "mts" | "cts" | "tsx" => "typescript",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "swift" => "swift",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => "cpp",
+        "cs" => "csharp",
+        "rb" => "ruby",
+        "php" => "php",
+        "scala" => "scala",
+        "sh" | "bash" | "zsh" => "shell",
+        "sql" => "sql",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" => "scss",
+        "md" | "markdown" => "markdown",
+        "json" => "json",
+        "toml" => "toml",
+        "yaml" | "yml" => "yaml",
+        "xml" => "xml",
+        "hbs" | "handlebars" => "handlebars",
+        "proto" => "protobuf",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// The comment syntax of a language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommentSyntax {
+    pub line: Option<&'static str>,
+    pub block: Option<(&'static str, &'static str)>,
+}
+
+/// Returns the comment syntax of a file extension.
+///
+/// # Arguments
+///
+/// * `extension` - The file extension, without the leading dot.
+///
+/// # Returns
+///
+/// * `Option<CommentSyntax>` - The comment syntax, or `None` when it is unknown.
+pub fn comment_syntax(extension: &str) -> Option<CommentSyntax> {
+    let (line, block) = match language_name(extension)? {
+        "rust" | "javascript" | "typescript" | "go" | "java" | "kotlin" | "swift" | "c" | "cpp"
+        | "csharp" | "php" | "scala" | "scss" => (Some("//"), Some(("/*", "*/"))),
+        "css" => (None, Some(("/*", "*/"))),
+        "python" | "ruby" | "shell" | "toml" | "yaml" => (Some("#"), None),
+        "sql" => (Some("--"), Some(("/*", "*/"))),
+        "html" | "xml" | "markdown" => (None, Some(("<!--", "-->"))),
+        _ => return None,
+    };
+    Some(CommentSyntax { line, block })
+}
+
+/// Removes the first comment block of a file, such as a license header.
+///
+/// A shebang line is kept, and blank lines around the block are removed along with it. Doc comments
+/// (`///`, `//!` and `/*!`) are not considered a header.
+///
+/// # Arguments
+///
+/// * `code` - The content of the file.
+/// * `extension` - The file extension used to select the comment syntax.
+///
+/// # Returns
+///
+/// * `Option<String>` - The content without its leading comment block, or `None` when it has none.
+pub fn strip_leading_comment_block(code: &str, extension: &str) -> Option<String> {
+    let syntax = comment_syntax(extension)?;
+    let lines: Vec<&str> = code.split_inclusive('\n').collect();
+    let is_doc = |line: &str| ["///", "//!", "/*!"].iter().any(|doc| line.starts_with(doc));
+
+    let mut start = 0;
+    if lines.first().is_some_and(|line| line.starts_with("#!")) {
+        start = 1;
+    }
+    let mut index = start;
+    while index < lines.len() && lines[index].trim().is_empty() {
+        index += 1;
+    }
+    let first = lines.get(index)?.trim_start();
+    if is_doc(first) {
+        return None;
+    }
+
+    match (syntax.block, syntax.line) {
+        (Some((open, close)), _) if first.starts_with(open) => {
+            let mut rest = &first[open.len()..];
+            while !rest.contains(close) {
+                index += 1;
+                rest = lines.get(index)?;
+            }
+            index += 1;
+        }
+        (_, Some(prefix)) if first.starts_with(prefix) => {
+            while index < lines.len() {
+                let line = lines[index].trim_start();
+                if !line.starts_with(prefix) || is_doc(line) {
+                    break;
+                }
+                index += 1;
+            }
+        }
+        _ => return None,
+    }
+    while index < lines.len() && lines[index].trim().is_empty() {
+        index += 1;
+    }
+
+    Some(format!("{}{}", lines[..start].concat(), lines[index..].concat()))
+}
+
+/// Returns the first line of the comments at the top of a file, e.g. of its doc comment, as a summary.
+///
+/// Shebangs, blank lines and the copyright lines of a license header are skipped, and comment markers
+/// are removed. Python docstrings count as comments. This is a heuristic: the first line of text is taken
+/// whatever it says.
+///
+/// # Arguments
+///
+/// * `code` - The content of the file.
+/// * `extension` - The file extension used to select the comment syntax.
+///
+/// # Returns
+///
+/// * `Option<String>` - The first comment line, or `None` when the file doesn't start with a comment.
+pub fn header_comment(code: &str, extension: &str) -> Option<String> {
+    let syntax = comment_syntax(extension)?;
+    let mut blocks: Vec<(&str, &str)> = syntax.block.into_iter().collect();
+    if language_name(extension) == Some("python") {
+        blocks.extend([("\"\"\"", "\"\"\""), ("'''", "'''")]);
+    }
+    let is_license = |text: &str| {
+        let text = text.to_ascii_lowercase();
+        ["copyright", "(c)", "spdx-license-identifier", "licensed under", "all rights reserved"]
+            .iter()
+            .any(|prefix| text.starts_with(prefix))
+    };
+
+    // The closing delimiter of the block comment being read
+    let mut open_block: Option<&str> = None;
+    for line in code.lines().map(str::trim) {
+        let text = match open_block {
+            Some(close) => match line.find(close) {
+                Some(end) => {
+                    open_block = None;
+                    &line[..end]
+                }
+                None => line,
+            },
+            None if line.is_empty() || line.starts_with("#!") => continue,
+            None => {
+                if let Some((open, close)) = blocks.iter().find(|(open, _)| line.starts_with(open)) {
+                    let rest = &line[open.len()..];
+                    match rest.find(close) {
+                        Some(end) => &rest[..end],
+                        None => {
+                            open_block = Some(close);
+                            rest
+                        }
+                    }
+                } else {
+                    // Code was reached before any comment line with text
+                    syntax.line.and_then(|prefix| line.strip_prefix(prefix))?
+                }
+            }
+        };
+        // Doc comment markers such as `///`, `//!` and `/**`, and the leading `*` of block comment lines
+        let text = text.trim_start_matches(['/', '!', '*', '#']).trim();
+        if !text.is_empty() && !is_license(text) {
+            return Some(text.to_string());
+        }
+    }
+    None
+}
@@ -3,23 +3,15 @@
 //! Author: Mufeed VH (@mufeedvh)
 //! Contributor: Olivier D'Ancona (@ODAncona)
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use clap::Parser;
 use code2prompt::{
-    copy_to_clipboard, get_git_diff, get_git_diff_between_branches, get_git_log, get_model_info,
-    get_tokenizer, handle_undefined_variables, handlebars_setup, label, render_template,
-    traverse_directory, write_to_file,
+    deliver_prompt_with_callback, file_source, render_prompt, repo_map, BudgetStrategy, Code2PromptConfig, FileSort, LineEnding, OutputFormat,
 };
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
-use log::{debug, error};
-use serde_json::json;
 use std::path::PathBuf;
 
-// Constants
-const DEFAULT_TEMPLATE_NAME: &str = "default";
-const CUSTOM_TEMPLATE_NAME: &str = "custom";
-
 // CLI Arguments
 #[derive(Parser)]
 #[clap(name = "code2prompt", version = "2.0.0", author = "Mufeed VH")]
@@ -28,6 +20,10 @@ struct Cli {
     #[arg()]
     path: PathBuf,
 
+    /// Read the files from the tree of a git reference instead of the working copy
+    #[clap(long, value_name = "REF")]
+    git_ref: Option<String>,
+
     /// Patterns to include
     #[clap(long)]
     include: Option<String>,
@@ -36,10 +32,146 @@ struct Cli {
     #[clap(long)]
     exclude: Option<String>,
 
+    /// A labeled set of include patterns, e.g. "Config=*.toml,*.yaml", tagging the files it matches; repeatable
+    #[clap(long, value_name = "LABEL=PATTERNS", value_parser = parse_include_group, conflicts_with = "group_by_dir")]
+    include_group: Vec<(String, Vec<String>)>,
+
+    /// File of include patterns, one per line, merged with --include
+    #[clap(long, value_name = "FILE")]
+    include_from: Option<PathBuf>,
+
+    /// File of exclude patterns, one per line, merged with --exclude
+    #[clap(long, value_name = "FILE")]
+    exclude_from: Option<PathBuf>,
+
+    /// Additional gitignore-style files to honor, e.g. ".dockerignore,.npmignore"
+    #[clap(long, value_name = "NAMES", value_delimiter = ',')]
+    extra_ignore_files: Vec<String>,
+
+    /// Named include/exclude patterns for a common stack: rust, node, python or go
+    #[clap(long, value_name = "PRESET")]
+    filter_preset: Option<String>,
+
+    /// Include only this file, relative to the codebase directory
+    #[clap(long, value_name = "FILE")]
+    entry: Option<PathBuf>,
+
+    /// With --entry, also include the local files the entry file imports, transitively
+    #[clap(long, requires = "entry")]
+    follow_imports: bool,
+
+    /// Add the dependency graph of the files between their local imports, in the Graphviz DOT language
+    #[clap(long)]
+    graph: bool,
+
+    /// Write the dependency graph of the files, in the Graphviz DOT language, to this file
+    #[clap(long, value_name = "FILE")]
+    graph_output: Option<PathBuf>,
+
+    /// Exclude test files, detected by directory and file naming conventions, unless explicitly included
+    #[clap(long)]
+    no_tests: bool,
+
+    /// Exclude the files written in these languages, e.g. "sql,protobuf"
+    #[clap(long, value_name = "LANGUAGES", value_delimiter = ',')]
+    exclude_languages: Vec<String>,
+
+    /// Descend into symlinked directories, annotating symlinks with their target in the tree
+    #[clap(long)]
+    follow_symlinks: bool,
+
+    /// Prepend to each file the content of its sidecar file with this suffix, e.g. ".notes" for foo.rs.notes
+    #[clap(long, value_name = "SUFFIX")]
+    sidecar_suffix: Option<String>,
+
+    /// Show the permissions of the files, marking the executable ones with a `*` in the tree
+    #[clap(long)]
+    show_permissions: bool,
+
+    /// Refuse to run outside of a git repository
+    #[clap(long)]
+    require_git: bool,
+
+    /// In a git repository, only include the files tracked by git
+    #[clap(long)]
+    git_tracked_only: bool,
+
+    /// Only include the files in these git states, as comma-separated status codes, e.g. "M,??"
+    #[clap(long, value_name = "CODES")]
+    git_status_filter: Option<String>,
+
+    /// Mark the lines changed since a git reference in the full content of the files, e.g. "main"
+    #[clap(long, value_name = "REF")]
+    mark_changes_since: Option<String>,
+
+    /// List the empty and whitespace-only files as skipped, e.g. in the JSON output
+    #[clap(long)]
+    skip_empty: bool,
+
+    /// Remove terminal escape sequences such as color codes from the files and command outputs
+    #[clap(long)]
+    strip_ansi: bool,
+
+    /// Rename the identifiers of the code, the tree and the paths to placeholders such as id_1
+    #[clap(long)]
+    anonymize: bool,
+
+    /// Reduce the Rust files to their public items and signatures, for a compact API overview
+    #[clap(long)]
+    pub_only: bool,
+
+    /// Skip the files smaller than this size, unless an include pattern matches them
+    #[clap(long, value_name = "BYTES")]
+    min_file_size: Option<u64>,
+
+    /// Skip the files with fewer tokens than this, unless an include pattern matches them
+    #[clap(long, value_name = "TOKENS")]
+    min_file_tokens: Option<usize>,
+
+    /// Include small binary files as base64, with an `encoding` of `base64` in the file data
+    #[clap(long)]
+    binary_as_base64: bool,
+
+    /// The size cap of the binary files included as base64, in bytes (default: 65536)
+    #[clap(long, value_name = "BYTES", requires = "binary_as_base64")]
+    max_binary_bytes: Option<usize>,
+
+    /// The total size of the decompressed entries of an archive, in bytes (default: 536870912)
+    #[clap(long, value_name = "BYTES")]
+    max_archive_bytes: Option<u64>,
+
+    /// Skip the files identical to the corresponding file of this reference tree, e.g. a project template
+    #[clap(long, value_name = "DIR")]
+    baseline: Option<PathBuf>,
+
     /// Include files in case of conflict between include and exclude patterns
     #[clap(long)]
     include_priority: bool,
 
+    /// Show the tree down to this level, where files at the root are on level 1
+    #[clap(long, value_name = "N")]
+    tree_max_depth: Option<usize>,
+
+    /// Show at most this many entries of each directory in the tree, collapsing the others
+    #[clap(long, value_name = "N")]
+    max_tree_entries: Option<usize>,
+
+    /// Annotate the files of the tree with their line count and percentage of the total, and the directories with their line count
+    #[clap(long)]
+    tree_line_stats: bool,
+
+    /// Prepend a legend of the annotations of the tree, such as line counts, symbolic links and executables
+    #[clap(long, conflicts_with = "no_tree")]
+    tree_legend: bool,
+
+    /// Skip the directory tree, e.g. for templates that don't use it
+    #[clap(long, conflicts_with_all = ["exclude_from_tree", "tree_max_depth", "max_tree_entries"])]
+    no_tree: bool,
+
+    /// Include the contents of the files down to this level only, while the tree may go deeper
+    #[clap(long, value_name = "N")]
+    content_max_depth: Option<usize>,
+
     /// Exclude files/folders from the source tree based on exclude patterns
     #[clap(long)]
     exclude_from_tree: bool,
@@ -48,20 +180,64 @@ struct Cli {
     #[clap(long)]
     tokens: bool,
 
+    /// Print the prompt statistics as a single `key=value` line to stderr, e.g. for scripts
+    #[clap(long)]
+    machine_stats: bool,
+
+    /// Suppress informational output such as the token statistics
+    #[clap(short, long)]
+    quiet: bool,
+
+    /// Estimate the per-language token breakdown instead of encoding every file, which is faster on huge trees
+    #[clap(long)]
+    estimate_tokens: bool,
+
     /// Optional tokenizer to use for token count
     ///
-    /// Supported tokenizers: cl100k (default), p50k, p50k_edit, r50k, gpt2
+    /// Supported tokenizers: cl100k (default), o200k, p50k, p50k_edit, r50k, gpt2, and claude, which
+    /// approximates the counts of Anthropic's Claude models.
+    /// Defaults to CODE2PROMPT_ENCODING, then the encoding of OPENAI_MODEL, when unset.
     #[clap(short = 'c', long)]
     encoding: Option<String>,
 
+    /// Count the markers of special tokens, e.g. <|endoftext|>, as single tokens (default: true)
+    ///
+    /// With false, they are counted as plain text, like most APIs count user content.
+    #[clap(long, value_name = "BOOL")]
+    count_special_tokens: Option<bool>,
+
+    /// The number of threads counting the tokens of large prompts (default: the number of cores)
+    #[clap(long, value_name = "N")]
+    concurrency: Option<usize>,
+
     /// Optional output file path
     #[clap(short, long)]
     output: Option<String>,
 
+    /// Create the missing parent directories of the output file (default: true)
+    #[clap(long, value_name = "BOOL")]
+    create_dirs: Option<bool>,
+
+    /// Leave the output file and the template out of the prompt when they are in the codebase (default: true)
+    #[clap(long, value_name = "BOOL")]
+    self_exclude: Option<bool>,
+
     /// Include git diff
     #[clap(short, long)]
     diff: bool,
 
+    /// Ignore whitespace changes in git diffs (default: true)
+    #[clap(long, value_name = "BOOL")]
+    diff_ignore_whitespace: Option<bool>,
+
+    /// Diff algorithm of git diffs: myers (default), minimal or patience
+    #[clap(long, value_name = "ALGORITHM")]
+    diff_algorithm: Option<String>,
+
+    /// Summarize the git diffs like `git diff --stat`, above the diffs
+    #[clap(long)]
+    diff_stat: bool,
+
     /// Generate git diff between two branches
     #[clap(long, value_name = "BRANCHES")]
     git_diff_branch: Option<String>,
@@ -74,14 +250,122 @@ struct Cli {
     #[clap(short, long)]
     line_number: bool,
 
+    /// Keep the line numbers out of the code, in a gutter that isn't copied along with it
+    #[clap(long, requires = "line_number")]
+    line_numbers_outside: bool,
+
+    /// Line endings of the file contents: converted to LF or CRLF, or preserved as they are
+    #[clap(long, value_enum, default_value_t = LineEnding::Lf)]
+    line_ending: LineEnding,
+
     /// Disable wrapping code inside markdown code blocks
     #[clap(long)]
     no_codeblock: bool,
 
+    /// The info string after the opening fence of code blocks, e.g. '{{language}} title="{{path}}"'
+    #[clap(long, value_name = "TEMPLATE", conflicts_with = "no_codeblock")]
+    fence_info_template: Option<String>,
+
+    /// Reuse the formatted blocks of unchanged files across runs, stored in this directory
+    #[clap(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// Keep the prompt within this many bytes, dropping trailing files and cutting the rest if needed
+    #[clap(long, value_name = "BYTES", conflicts_with = "max_tokens")]
+    max_output_bytes: Option<usize>,
+
+    /// Keep the prompt within this many tokens, cutting the files down according to --budget-strategy
+    #[clap(long, value_name = "N")]
+    max_tokens: Option<usize>,
+
+    /// How to cut the files down over --max-tokens: drop trailing files, or sample the head and tail of each file
+    #[clap(long, value_enum, default_value_t = BudgetStrategy::DropTrailing)]
+    budget_strategy: BudgetStrategy,
+
+    /// Split the files into chunks of at most this many tokens, listed in the JSON output
+    #[clap(long, value_name = "TOKENS", requires = "json")]
+    chunk_tokens: Option<usize>,
+
+    /// The tokens shared by consecutive chunks
+    #[clap(long, value_name = "TOKENS", requires = "chunk_tokens")]
+    chunk_overlap: Option<usize>,
+
+    /// Cut the chunks from the token stream of the prompt, splitting files and lines
+    #[clap(long, requires = "chunk_tokens")]
+    chunk_exact: bool,
+
+    /// Sort the files, e.g. by-recency for the most recently committed files first
+    #[clap(long, value_enum)]
+    sort_files: Option<FileSort>,
+
+    /// Order the files so that the files they import come first, following their local imports
+    #[clap(long)]
+    topological: bool,
+
+    /// Include at most this many files, after sorting, and report how many were omitted
+    #[clap(long, value_name = "N")]
+    max_files: Option<usize>,
+
+    /// Add the SHA-256 hash of each file to its metadata and to the JSON output
+    #[clap(long)]
+    include_hashes: bool,
+
+    /// Include one file of each group of near-identical files, listing the others
+    #[clap(long)]
+    collapse_similar: bool,
+
+    /// Similarity from which files are collapsed, between 0 and 1 [default: 0.8]
+    #[clap(long, value_name = "SIMILARITY", requires = "collapse_similar")]
+    similarity_threshold: Option<f64>,
+
+    /// Write the manifest of the files, with their content hash and token count, to this file
+    #[clap(long, value_name = "FILE")]
+    manifest: Option<PathBuf>,
+
+    /// Write a CSV report of the files, with their path, size, line count and token count, to this file
+    #[clap(long, value_name = "FILE")]
+    report_csv: Option<PathBuf>,
+
+    /// Report the files added, removed or changed since the manifest saved in this file
+    #[clap(long, value_name = "FILE")]
+    compare_manifest: Option<PathBuf>,
+
+    /// Handlebars snippet rendering the header of each file, e.g. "## {{path}} ({{token_count}} tokens)"
+    #[clap(long, value_name = "TEMPLATE")]
+    file_header_template: Option<String>,
+
+    /// Add an overview of the project from its Cargo.toml, package.json or pyproject.toml
+    #[clap(long)]
+    project_metadata: bool,
+
+    /// Scope the prompt to a member package of the Cargo or npm workspace, by its name
+    #[clap(long, value_name = "NAME")]
+    package: Option<String>,
+
+    /// With --package, also include the shared files of the workspace root, e.g. its Cargo.toml and Cargo.lock
+    #[clap(long, requires = "package")]
+    workspace_files: bool,
+
+    /// List the main contributors of the git repository with their commit counts
+    #[clap(long)]
+    contributors: bool,
+
+    /// Summarize each directory with the first header comment line of each of its files
+    #[clap(long)]
+    dir_summaries: bool,
+
+    /// Group the files by top-level directory, with a header per directory
+    #[clap(long)]
+    group_by_dir: bool,
+
     /// Use relative paths instead of absolute paths, including the parent directory
     #[clap(long)]
     relative_paths: bool,
 
+    /// Print the prompt to stdout, along with the other enabled destinations
+    #[clap(long)]
+    stdout: bool,
+
     /// Optional Disable copying to clipboard
     #[clap(long)]
     no_clipboard: bool,
@@ -90,40 +374,82 @@ struct Cli {
     #[clap(short, long)]
     template: Option<PathBuf>,
 
+    /// Template variables left empty rather than asked for when undefined, e.g. "extra_context,audience"
+    #[clap(long, value_name = "NAMES", value_delimiter = ',')]
+    optional_variables: Vec<String>,
+
+    /// A prompt spec (TOML, YAML or JSON) with the base options, e.g. a shared template, patterns and variables
+    #[clap(long, value_name = "FILE")]
+    spec: Option<PathBuf>,
+
+    /// Allow running the commands of the spec
+    #[clap(long)]
+    allow_commands: bool,
+
     /// Print output as JSON
     #[clap(long)]
     json: bool,
+
+    /// Render the prompt as an HTML document with escaped code blocks, for viewing in a browser
+    #[clap(long, conflicts_with = "json")]
+    html: bool,
+
+    /// Output format: the rendered prompt, or a JSON array with one object per file
+    #[clap(long, value_enum, default_value_t = OutputFormat::Prompt)]
+    output_format: OutputFormat,
+
+    /// Print the size and estimated token count of each file, with directory subtotals, instead of the prompt
+    #[clap(long)]
+    map: bool,
+
+    /// Keep running and regenerate the prompt whenever the included files change
+    #[cfg(feature = "watch")]
+    #[clap(long)]
+    watch: bool,
+
+    /// Make the output reproducible across runs, e.g. for snapshot tests; the seed orders the files of equal recency
+    #[clap(long, value_name = "SEED")]
+    seed: Option<u64>,
+
+    /// Make two runs over the same tree produce byte-identical output, with relative paths under a fixed root label
+    #[clap(long)]
+    stable: bool,
+
+    /// Print the resolved configuration as JSON to stderr before running
+    #[clap(long)]
+    print_config: bool,
 }
 
 fn main() -> Result<()> {
     env_logger::init();
     let args = Cli::parse();
+    #[cfg(feature = "watch")]
+    let watch = args.watch;
+    let config = build_config(args).resolve_spec()?;
 
-    // Handlebars Template Setup
-    let (template_content, template_name) = get_template(&args)?;
-    let handlebars = handlebars_setup(&template_content, template_name)?;
+    if config.print_config {
+        eprintln!("{}", config.to_redacted_json()?);
+    }
+
+    // Map of the files, without rendering the prompt
+    if config.map {
+        let source = file_source(&config)?;
+        let map = repo_map(source.as_ref(), &config)?;
+        if config.json {
+            println!("{}", serde_json::to_string_pretty(&map)?);
+        } else {
+            print!("{}", map.to_table());
+        }
+        return Ok(());
+    }
 
     // Progress Bar Setup
     let spinner = setup_spinner("Traversing directory and building tree...");
 
-    // Parse Patterns
-    let include_patterns = parse_patterns(&args.include);
-    let exclude_patterns = parse_patterns(&args.exclude);
-
-    // Traverse the directory
-    let create_tree = traverse_directory(
-        &args.path,
-        &include_patterns,
-        &exclude_patterns,
-        args.include_priority,
-        args.line_number,
-        args.relative_paths,
-        args.exclude_from_tree,
-        args.no_codeblock,
-    );
-
-    let (tree, files) = match create_tree {
-        Ok(result) => result,
+    // Traverse the directory and render the template
+    let rendered = file_source(&config).and_then(|source| render_prompt(source.as_ref(), &config));
+    let prompt = match rendered {
+        Ok(prompt) => prompt,
         Err(e) => {
             spinner.finish_with_message("Failed!".red().to_string());
             eprintln!(
@@ -131,201 +457,225 @@ fn main() -> Result<()> {
                 "[".bold().white(),
                 "!".bold().red(),
                 "]".bold().white(),
-                format!("Failed to build directory tree: {}", e).red()
+                format!("Failed to generate prompt: {}", e).red()
             );
             std::process::exit(1);
         }
     };
 
-    // Git Diff
-    let git_diff = if args.diff {
-        spinner.set_message("Generating git diff...");
-        get_git_diff(&args.path).unwrap_or_default()
-    } else {
-        String::new()
-    };
-
-    // git diff two get_git_diff_between_branches
-    let mut git_diff_branch: String = String::new();
-    if let Some(branches) = &args.git_diff_branch {
-        spinner.set_message("Generating git diff between two branches...");
-        let branches = parse_patterns(&Some(branches.to_string()));
-        if branches.len() != 2 {
-            error!("Please provide exactly two branches separated by a comma.");
-            std::process::exit(1);
-        }
-        git_diff_branch = get_git_diff_between_branches(&args.path, &branches[0], &branches[1])
-            .unwrap_or_default()
-    }
-
-    // git diff two get_git_diff_between_branches
-    let mut git_log_branch: String = String::new();
-    if let Some(branches) = &args.git_log_branch {
-        spinner.set_message("Generating git log between two branches...");
-        let branches = parse_patterns(&Some(branches.to_string()));
-        if branches.len() != 2 {
-            error!("Please provide exactly two branches separated by a comma.");
-            std::process::exit(1);
-        }
-        git_log_branch = get_git_log(&args.path, &branches[0], &branches[1]).unwrap_or_default()
-    }
-
     spinner.finish_with_message("Done!".green().to_string());
 
-    // Prepare JSON Data
-    let mut data = json!({
-        "absolute_code_path": label(&args.path),
-        "source_tree": tree,
-        "files": files,
-        "git_diff": git_diff,
-        "git_diff_branch": git_diff_branch,
-        "git_log_branch": git_log_branch
-    });
-
-    debug!(
-        "JSON Data: {}",
-        serde_json::to_string_pretty(&data).unwrap()
-    );
-
-    // Handle undefined variables
-    handle_undefined_variables(&mut data, &template_content)?;
-
-    // Render the template
-    let rendered = render_template(&handlebars, template_name, &data)?;
-
-    // Display Token Count
-    let token_count = if args.tokens {
-        let bpe = get_tokenizer(&args.encoding);
-        bpe.encode_with_special_tokens(&rendered).len()
-    } else {
-        0
-    };
-
-    let paths: Vec<String> = files
-        .iter()
-        .filter_map(|file| {
-            file.get("path")
-                .and_then(|p| p.as_str())
-                .map(|s| s.to_string())
-        })
-        .collect();
-
-    let model_info = get_model_info(&args.encoding);
-
-    if args.json {
-        let json_output = json!({
-            "prompt": rendered,
-            "directory_name": label(&args.path),
-            "token_count": token_count,
-            "model_info": model_info,
-            "files": paths,
-        });
-        println!("{}", serde_json::to_string_pretty(&json_output)?);
-        return Ok(());
-    } else {
-        if args.tokens {
-            println!(
-                "{}{}{} Token count: {}, Model info: {}",
-                "[".bold().white(),
-                "i".bold().blue(),
-                "]".bold().white(),
-                token_count.to_string().bold().yellow(),
-                model_info
-            );
+    // Display the token count, copy to clipboard and write the output file, with the progress of the counting
+    let progress = std::cell::OnceCell::new();
+    let on_progress = |encoded: usize, total: usize| {
+        if !config.quiet {
+            progress.get_or_init(|| setup_progress_bar("Counting tokens", total)).set_position(encoded as u64);
         }
+    };
+    let output = deliver_prompt_with_callback(&config, &prompt, Some(&on_progress));
+    if let Some(progress) = progress.get() {
+        progress.finish_and_clear();
+    }
+    let output = output?;
+    if config.json {
+        println!("{}", output);
     }
 
-    // Copy to Clipboard
-    if !args.no_clipboard {
-        match copy_to_clipboard(&rendered) {
-            Ok(_) => {
-                println!(
-                    "{}{}{} {}",
-                    "[".bold().white(),
-                    "✓".bold().green(),
-                    "]".bold().white(),
-                    "Copied to clipboard successfully.".green()
-                );
-            }
-            Err(e) => {
-                eprintln!(
+    #[cfg(feature = "watch")]
+    if watch {
+        eprintln!("{}", "Watching for changes, press Ctrl-C to stop...".blue());
+        code2prompt::watch(&config, |result| {
+            match result {
+                Ok(output) if config.json => println!("{}", output),
+                Ok(_) => {}
+                Err(e) => eprintln!(
                     "{}{}{} {}",
                     "[".bold().white(),
                     "!".bold().red(),
                     "]".bold().white(),
-                    format!("Failed to copy to clipboard: {}", e).red()
-                );
-                println!("{}", &rendered);
+                    format!("Failed to regenerate prompt: {}", e).red()
+                ),
             }
-        }
-    }
-
-    // Output File
-    if let Some(output_path) = &args.output {
-        write_to_file(output_path, &rendered)?;
+            std::ops::ControlFlow::Continue(())
+        })?;
     }
 
     Ok(())
 }
 
-/// Sets up a progress spinner with a given message
+/// Parses a labeled set of include patterns, as `LABEL=PATTERNS` with comma-separated patterns
+fn parse_include_group(value: &str) -> Result<(String, Vec<String>), String> {
+    match value.split_once('=') {
+        Some((label, patterns)) if !label.trim().is_empty() && !patterns.trim().is_empty() => Ok((
+            label.trim().to_string(),
+            patterns.split(',').map(|pattern| pattern.trim().to_string()).collect(),
+        )),
+        _ => Err(format!("expected LABEL=PATTERNS, got `{}`", value)),
+    }
+}
+
+/// Builds the configuration from the CLI arguments
 ///
 /// # Arguments
 ///
-/// * `message` - A message to display with the spinner
+/// * `args` - The parsed CLI arguments
 ///
 /// # Returns
 ///
-/// * `ProgressBar` - The configured progress spinner
-fn setup_spinner(message: &str) -> ProgressBar {
-    let spinner = ProgressBar::new_spinner();
-    spinner.enable_steady_tick(std::time::Duration::from_millis(120));
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .tick_strings(&["▹▹▹▹▹", "▸▹▹▹▹", "▹▸▹▹▹", "▹▹▸▹▹", "▹▹▹▸▹", "▹▹▹▹▸"])
-            .template("{spinner:.blue} {msg}")
-            .unwrap(),
-    );
-    spinner.set_message(message.to_string());
-    spinner
+/// * `Code2PromptConfig` - The configuration to generate the prompt with
+fn build_config(args: Cli) -> Code2PromptConfig {
+    Code2PromptConfig {
+        path: args.path,
+        git_ref: args.git_ref,
+        include: args.include,
+        exclude: args.exclude,
+        include_from: args.include_from,
+        include_groups: args.include_group,
+        exclude_from: args.exclude_from,
+        filter_preset: args.filter_preset,
+        extra_ignore_files: args.extra_ignore_files,
+        no_tests: args.no_tests,
+        exclude_languages: args.exclude_languages,
+        entry: args.entry,
+        follow_imports: args.follow_imports,
+        graph: args.graph,
+        graph_output: args.graph_output,
+        follow_symlinks: args.follow_symlinks,
+        show_permissions: args.show_permissions,
+        sidecar_suffix: args.sidecar_suffix,
+        baseline: args.baseline,
+        git_tracked_only: args.git_tracked_only,
+        git_status_filter: args.git_status_filter,
+        mark_changes_since: args.mark_changes_since,
+        require_git: args.require_git,
+        skip_empty: args.skip_empty,
+        strip_ansi: args.strip_ansi,
+        anonymize: args.anonymize,
+        pub_only: args.pub_only,
+        min_file_size: args.min_file_size,
+        min_file_tokens: args.min_file_tokens,
+        binary_as_base64: args.binary_as_base64,
+        max_binary_bytes: args.max_binary_bytes,
+        max_archive_bytes: args.max_archive_bytes,
+        include_priority: args.include_priority,
+        exclude_from_tree: args.exclude_from_tree,
+        tree_max_depth: args.tree_max_depth,
+        max_tree_entries: args.max_tree_entries,
+        no_tree: args.no_tree,
+        tree_line_stats: args.tree_line_stats,
+        tree_legend: args.tree_legend,
+        content_max_depth: args.content_max_depth,
+        tokens: args.tokens,
+        estimate_tokens: args.estimate_tokens,
+        machine_stats: args.machine_stats,
+        quiet: args.quiet,
+        encoding: args.encoding,
+        count_special_tokens: args.count_special_tokens,
+        self_exclude: args.self_exclude,
+        concurrency: args.concurrency,
+        output: args.output,
+        create_dirs: args.create_dirs,
+        diff: args.diff,
+        diff_ignore_whitespace: args.diff_ignore_whitespace,
+        diff_algorithm: args.diff_algorithm,
+        diff_stat: args.diff_stat,
+        git_diff_branch: args.git_diff_branch,
+        git_log_branch: args.git_log_branch,
+        line_number: args.line_number,
+        line_numbers_outside: args.line_numbers_outside,
+        line_ending: args.line_ending,
+        no_codeblock: args.no_codeblock,
+        fence_info_template: args.fence_info_template,
+        cache_dir: args.cache_dir,
+        max_files: args.max_files,
+        include_hashes: args.include_hashes,
+        collapse_similar: args.collapse_similar,
+        similarity_threshold: args.similarity_threshold,
+        sort_files: args.sort_files,
+        topological: args.topological,
+        max_output_bytes: args.max_output_bytes,
+        max_tokens: args.max_tokens,
+        budget_strategy: args.budget_strategy,
+        chunk_tokens: args.chunk_tokens,
+        chunk_overlap: args.chunk_overlap,
+        chunk_exact: args.chunk_exact,
+        manifest: args.manifest,
+        report_csv: args.report_csv,
+        compare_manifest: args.compare_manifest,
+        relative_paths: args.relative_paths,
+        group_by_dir: args.group_by_dir,
+        project_metadata: args.project_metadata,
+        package: args.package,
+        workspace_files: args.workspace_files,
+        contributors: args.contributors,
+        dir_summaries: args.dir_summaries,
+        file_header_template: args.file_header_template,
+        no_clipboard: args.no_clipboard,
+        stdout: args.stdout,
+        template: args.template,
+        user_template: user_template_path().filter(|template_path| template_path.is_file()),
+        spec: args.spec,
+        allow_commands: args.allow_commands,
+        optional_variables: args.optional_variables,
+        json: args.json,
+        html: args.html,
+        print_config: args.print_config,
+        seed: args.seed,
+        stable: args.stable,
+        map: args.map,
+        output_format: args.output_format,
+        ..Default::default()
+    }
+}
+
+/// Returns the path of the default template of the user, in the XDG configuration directory.
+fn user_template_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("code2prompt").join("default.hbs"))
 }
 
-/// Parses comma-separated patterns into a vector of strings
+/// Sets up a progress bar of a number of bytes with a given message
 ///
 /// # Arguments
 ///
-/// * `patterns` - An optional string containing comma-separated patterns
+/// * `message` - A message to display with the progress bar
+/// * `total_bytes` - The number of bytes to process
 ///
 /// # Returns
 ///
-/// * `Vec<String>` - A vector of parsed patterns
-fn parse_patterns(patterns: &Option<String>) -> Vec<String> {
-    match patterns {
-        Some(patterns) if !patterns.is_empty() => {
-            patterns.split(',').map(|s| s.trim().to_string()).collect()
-        }
-        _ => vec![],
-    }
+/// * `ProgressBar` - The configured progress bar
+fn setup_progress_bar(message: &str, total_bytes: usize) -> ProgressBar {
+    let progress = ProgressBar::new(total_bytes as u64);
+    progress.set_style(
+        ProgressStyle::default_bar()
+            .template("{msg} [{bar:30}] {bytes}/{total_bytes}")
+            .unwrap(),
+    );
+    progress.set_message(message.to_string());
+    progress
 }
 
-/// Retrieves the template content and name based on the CLI arguments
+/// Sets up a progress spinner with a given message
 ///
 /// # Arguments
 ///
-/// * `args` - The parsed CLI arguments
+/// * `message` - A message to display with the spinner
 ///
 /// # Returns
 ///
-/// * `Result<(String, &str)>` - A tuple containing the template content and name
-fn get_template(args: &Cli) -> Result<(String, &str)> {
-    if let Some(template_path) = &args.template {
-        let content = std::fs::read_to_string(template_path)
-            .context("Failed to read custom template file")?;
-        Ok((content, CUSTOM_TEMPLATE_NAME))
-    } else {
-        Ok((
-            include_str!("default_template.hbs").to_string(),
-            DEFAULT_TEMPLATE_NAME,
-        ))
-    }
+/// * `ProgressBar` - The configured progress spinner
+fn setup_spinner(message: &str) -> ProgressBar {
+    let spinner = ProgressBar::new_spinner();
+    spinner.enable_steady_tick(std::time::Duration::from_millis(120));
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .tick_strings(&["▹▹▹▹▹", "▸▹▹▹▹", "▹▸▹▹▹", "▹▹▸▹▹", "▹▹▹▸▹", "▹▹▹▹▸"])
+            .template("{spinner:.blue} {msg}")
+            .unwrap(),
+    );
+    spinner.set_message(message.to_string());
+    spinner
 }
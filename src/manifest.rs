@@ -0,0 +1,197 @@
+//! This module contains the manifests of the files of a prompt, which let successive runs be compared.
+//!
+//! A manifest records the content hash and token count of every file, keyed by its path relative to the
+//! root directory, so a later run can report the files that were added, removed or changed.
+
+use crate::output::content_hash;
+use crate::path::root_relative_path;
+use crate::token::{get_dyn_tokenizer, Tokenizer};
+use anyhow::{Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The hash and token count of a file of a manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub hash: String,
+    pub tokens: usize,
+}
+
+/// The files of a prompt, keyed by their path relative to the root directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub files: BTreeMap<String, ManifestEntry>,
+}
+
+/// A file whose content changed between two manifests.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangedFile {
+    pub path: String,
+    pub old_tokens: usize,
+    pub new_tokens: usize,
+}
+
+/// The differences between a saved manifest and the current one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ManifestDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<ChangedFile>,
+    /// The token count of the current files minus the one of the saved files.
+    pub token_delta: i64,
+}
+
+impl Manifest {
+    /// Builds the manifest of the files of a prompt.
+    ///
+    /// The token counts of the files are reused when they were counted exactly, and computed otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The JSON representations of the files.
+    /// * `root_path` - The path to the root directory.
+    /// * `relative_paths` - Whether the traversal produced relative paths.
+    /// * `encoding` - An optional string specifying the encoding to use for token counting.
+    ///
+    /// # Returns
+    ///
+    /// * `Manifest` - The manifest of the files.
+    pub fn from_files(
+        files: &[serde_json::Value],
+        root_path: &Path,
+        relative_paths: bool,
+        encoding: &Option<String>,
+    ) -> Self {
+        Self::from_files_with_tokenizer(files, root_path, relative_paths, get_dyn_tokenizer(encoding).as_ref())
+    }
+
+    /// Builds the manifest like [`Manifest::from_files`], counting the tokens with a tokenizer, e.g. the one of
+    /// [`crate::Code2PromptConfig::tokenizer`] so that `count_special_tokens` applies.
+    pub fn from_files_with_tokenizer(
+        files: &[serde_json::Value],
+        root_path: &Path,
+        relative_paths: bool,
+        tokenizer: &dyn Tokenizer,
+    ) -> Self {
+        let files = files
+            .iter()
+            .map(|file| {
+                let path = file["path"].as_str().unwrap_or_default();
+                let code = file["code"].as_str().unwrap_or_default();
+                let tokens = Some(&file["tokens"])
+                    .filter(|_| file["tokens_estimated"] != true)
+                    .and_then(|tokens| tokens.as_u64())
+                    .map(|tokens| tokens as usize)
+                    .unwrap_or_else(|| tokenizer.count(code));
+                let entry = ManifestEntry {
+                    hash: content_hash(code),
+                    tokens,
+                };
+                (root_relative_path(path, root_path, relative_paths), entry)
+            })
+            .collect();
+        Manifest { files }
+    }
+
+    /// Reads a manifest written by [`Manifest::write`].
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest: {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("Invalid manifest: {}", path.display()))
+    }
+
+    /// Writes the manifest as pretty JSON.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write manifest: {}", path.display()))
+    }
+
+    /// Returns the total token count of the files.
+    pub fn total_tokens(&self) -> usize {
+        self.files.values().map(|entry| entry.tokens).sum()
+    }
+
+    /// Compares the manifest of a previous run with the current one.
+    ///
+    /// # Arguments
+    ///
+    /// * `current` - The manifest of the current run.
+    ///
+    /// # Returns
+    ///
+    /// * `ManifestDiff` - The files added, removed and changed since this manifest, sorted by path.
+    pub fn compare(&self, current: &Manifest) -> ManifestDiff {
+        let added = current
+            .files
+            .keys()
+            .filter(|path| !self.files.contains_key(*path))
+            .cloned()
+            .collect();
+        let removed = self
+            .files
+            .keys()
+            .filter(|path| !current.files.contains_key(*path))
+            .cloned()
+            .collect();
+        let changed = current
+            .files
+            .iter()
+            .filter_map(|(path, entry)| {
+                let old = self.files.get(path)?;
+                (old.hash != entry.hash).then(|| ChangedFile {
+                    path: path.clone(),
+                    old_tokens: old.tokens,
+                    new_tokens: entry.tokens,
+                })
+            })
+            .collect();
+        ManifestDiff {
+            added,
+            removed,
+            changed,
+            token_delta: current.total_tokens() as i64 - self.total_tokens() as i64,
+        }
+    }
+}
+
+impl ManifestDiff {
+    /// Returns whether no file was added, removed or changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Prints the differences to stderr.
+    pub fn print(&self) {
+        eprintln!(
+            "{}{}{} {}",
+            "[".bold().white(),
+            "i".bold().blue(),
+            "]".bold().white(),
+            format!(
+                "Compared to the manifest: {} added, {} removed, {} changed ({:+} tokens).",
+                self.added.len(),
+                self.removed.len(),
+                self.changed.len(),
+                self.token_delta
+            )
+            .bold()
+        );
+        for path in &self.added {
+            eprintln!("    {} {}", "+".green(), path);
+        }
+        for path in &self.removed {
+            eprintln!("    {} {}", "-".red(), path);
+        }
+        for file in &self.changed {
+            eprintln!(
+                "    {} {} ({} -> {} tokens)",
+                "~".yellow(),
+                file.path,
+                file.old_tokens,
+                file.new_tokens
+            );
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! This module contains the map of a codebase: the size and estimated token count of each file, with
+//! subtotals per directory, for exploring a repository before deciding what to include.
+
+use crate::path::FileEntry;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The size and estimated token count of a file or a directory of the map.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MapEntry {
+    /// The path relative to the root directory, using `/` separators.
+    pub path: String,
+    pub size: u64,
+    pub tokens: usize,
+}
+
+/// The map of a codebase, with its files and directories sorted by size, largest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RepoMap {
+    pub files: Vec<MapEntry>,
+    /// The subtotals of every directory containing files, not including the root directory.
+    pub directories: Vec<MapEntry>,
+    pub total_size: u64,
+    pub total_tokens: usize,
+}
+
+impl RepoMap {
+    /// Builds the map of the included files.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - The included files, with their token count.
+    ///
+    /// # Returns
+    ///
+    /// * `RepoMap` - The map, sorted by size descending and then by path.
+    pub fn from_entries(entries: &[FileEntry]) -> Self {
+        let mut files: Vec<MapEntry> = entries
+            .iter()
+            .map(|entry| MapEntry {
+                path: entry.relative_path.clone(),
+                size: entry.size,
+                tokens: entry.tokens.unwrap_or_default(),
+            })
+            .collect();
+
+        let mut subtotals: BTreeMap<String, (u64, usize)> = BTreeMap::new();
+        for file in &files {
+            for directory in Path::new(&file.path).ancestors().skip(1) {
+                if directory.as_os_str().is_empty() {
+                    continue;
+                }
+                let subtotal = subtotals
+                    .entry(directory.to_string_lossy().replace('\\', "/"))
+                    .or_default();
+                subtotal.0 += file.size;
+                subtotal.1 += file.tokens;
+            }
+        }
+        let mut directories: Vec<MapEntry> = subtotals
+            .into_iter()
+            .map(|(path, (size, tokens))| MapEntry { path, size, tokens })
+            .collect();
+
+        let by_size = |a: &MapEntry, b: &MapEntry| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path));
+        files.sort_by(by_size);
+        directories.sort_by(by_size);
+        RepoMap {
+            total_size: files.iter().map(|file| file.size).sum(),
+            total_tokens: files.iter().map(|file| file.tokens).sum(),
+            files,
+            directories,
+        }
+    }
+
+    /// Formats the map as aligned tables of the files and of the directory subtotals.
+    pub fn to_table(&self) -> String {
+        let width = self
+            .files
+            .iter()
+            .chain(&self.directories)
+            .map(|entry| entry.size.to_string().len())
+            .chain([self.total_size.to_string().len(), "Size".len()])
+            .max()
+            .unwrap_or_default();
+        let row = |size: &dyn ToString, tokens: &dyn ToString, path: &str| {
+            format!("{:>width$}  {:>10}  {}\n", size.to_string(), tokens.to_string(), path, width = width)
+        };
+
+        let mut table = row(&"Size", &"Tokens (~)", "File");
+        for file in &self.files {
+            table.push_str(&row(&file.size, &file.tokens, &file.path));
+        }
+        if !self.directories.is_empty() {
+            table.push('\n');
+            table.push_str(&row(&"Size", &"Tokens (~)", "Directory"));
+            for directory in &self.directories {
+                table.push_str(&row(&directory.size, &directory.tokens, &format!("{}/", directory.path)));
+            }
+        }
+        table.push('\n');
+        table.push_str(&row(&self.total_size, &self.total_tokens, "Total"));
+        table
+    }
+}
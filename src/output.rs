@@ -0,0 +1,244 @@
+//! This module contains the structured output formats that bypass the Handlebars template.
+
+use crate::language::language_name;
+use crate::path::{content_line_count, root_relative_path};
+use crate::token::{get_dyn_tokenizer, Tokenizer};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The format of the generated output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// The prompt rendered through the Handlebars template.
+    #[default]
+    Prompt,
+    /// A JSON array with one object per file, for ingestion pipelines.
+    JsonFiles,
+}
+
+/// Returns the hex-encoded SHA-256 hash of a file content.
+pub fn content_hash(content: &str) -> String {
+    bytes_hash(content.as_bytes())
+}
+
+/// Returns the hex-encoded SHA-256 hash of the bytes of a file, like `sha256sum`.
+pub fn bytes_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Builds one JSON record per file with its path, content, language, token count and content hash.
+///
+/// Metadata attached to the files by other options, such as `url`, `diff`, `status`, `encoding` and `sha256`,
+/// is kept.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files.
+/// * `encoding` - An optional string specifying the encoding to use for token counting.
+///
+/// # Returns
+///
+/// * `Vec<serde_json::Value>` - The file records.
+pub fn json_file_records(files: &[serde_json::Value], encoding: &Option<String>) -> Vec<serde_json::Value> {
+    json_file_records_with_tokenizer(files, get_dyn_tokenizer(encoding).as_ref())
+}
+
+/// Builds the file records like [`json_file_records`], counting the tokens with a tokenizer, e.g. the one of
+/// [`crate::Code2PromptConfig::tokenizer`] so that `count_special_tokens` applies.
+pub fn json_file_records_with_tokenizer(files: &[serde_json::Value], tokenizer: &dyn Tokenizer) -> Vec<serde_json::Value> {
+    files
+        .iter()
+        .map(|file| {
+            let content = file["code"].as_str().unwrap_or_default();
+            let extension = file["extension"].as_str().unwrap_or_default();
+            // Estimated counts are only meant for previews, so records always get exact ones
+            let tokens = Some(&file["tokens"])
+                .filter(|_| file["tokens_estimated"] != true)
+                .and_then(|tokens| tokens.as_u64())
+                .unwrap_or_else(|| tokenizer.count(content) as u64);
+            let hash = content_hash(content);
+
+            let mut record = json!({
+                "path": file["path"],
+                "content": content,
+                "language": file["language"]
+                    .as_str()
+                    .or_else(|| language_name(extension))
+                    .unwrap_or(if extension.is_empty() { "text" } else { extension }),
+                "tokens": tokens,
+                "hash": hash,
+            });
+            for key in ["url", "diff", "status", "encoding", "sha256"] {
+                if let Some(value) = file.get(key) {
+                    record[key] = value.clone();
+                }
+            }
+            record
+        })
+        .collect()
+}
+
+/// Builds a CSV report of the files, with a header row and one row per file: its path relative to the root
+/// directory, its size in bytes, its line count and its token count.
+///
+/// Paths are always quoted, so that commas and quotes in file names don't break the columns. Exact token
+/// counts of the files are reused, and the others are counted with the tokenizer.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files.
+/// * `root_path` - The path to the root directory.
+/// * `relative_paths` - Whether the traversal produced relative paths.
+/// * `no_codeblock` - Whether the code of the files is wrapped in fences, which aren't counted as lines.
+/// * `tokenizer` - The tokenizer counting the tokens of the files without exact counts.
+///
+/// # Returns
+///
+/// * `String` - The CSV report, with `\n` line endings.
+pub fn csv_report(
+    files: &[serde_json::Value],
+    root_path: &Path,
+    relative_paths: bool,
+    no_codeblock: bool,
+    tokenizer: &dyn Tokenizer,
+) -> String {
+    let mut csv = String::from("path,size,lines,tokens\n");
+    for file in files {
+        let path = root_relative_path(file["path"].as_str().unwrap_or_default(), root_path, relative_paths);
+        let code = file["code"].as_str().unwrap_or_default();
+        let tokens = Some(&file["tokens"])
+            .filter(|_| file["tokens_estimated"] != true)
+            .and_then(|tokens| tokens.as_u64())
+            .unwrap_or_else(|| tokenizer.count(code) as u64);
+        csv.push_str(&format!(
+            "\"{}\",{},{},{}\n",
+            path.replace('"', "\"\""),
+            file["size"].as_u64().unwrap_or_default(),
+            content_line_count(code, no_codeblock),
+            tokens
+        ));
+    }
+    csv
+}
+
+/// Escapes the characters of a string that are special in HTML text and attribute values.
+pub fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A directory of the linked tree, holding its subdirectories and the indices of its files.
+#[derive(Default)]
+struct HtmlTreeNode {
+    directories: BTreeMap<String, HtmlTreeNode>,
+    files: BTreeMap<String, usize>,
+}
+
+impl HtmlTreeNode {
+    fn write(&self, html: &mut String) {
+        html.push_str("<ul>\n");
+        for (name, directory) in &self.directories {
+            html.push_str(&format!("<li>{}/\n", escape_html(name)));
+            directory.write(html);
+            html.push_str("</li>\n");
+        }
+        for (name, index) in &self.files {
+            html.push_str(&format!(
+                "<li><a href=\"#file-{}\">{}</a></li>\n",
+                index,
+                escape_html(name)
+            ));
+        }
+        html.push_str("</ul>\n");
+    }
+}
+
+/// Renders the files as a standalone HTML document, for viewing in a browser.
+///
+/// The tree of the files links to their blocks, and each block is a `<pre><code class="language-...">`
+/// element with escaped content, ready for a client-side highlighter such as highlight.js. Line numbers
+/// are a separate column before the code, left out of selections so that copied code comes without them.
+///
+/// # Arguments
+///
+/// * `title` - The title of the document, e.g. the name of the root directory.
+/// * `files` - The JSON representations of the files, without code blocks.
+/// * `root_path` - The path to the root directory.
+/// * `relative_paths` - Whether the traversal produced relative paths.
+/// * `line_numbers` - Whether to show a column of line numbers next to the code.
+///
+/// # Returns
+///
+/// * `String` - The HTML document.
+pub fn html_document(
+    title: &str,
+    files: &[serde_json::Value],
+    root_path: &Path,
+    relative_paths: bool,
+    line_numbers: bool,
+) -> String {
+    let mut tree = HtmlTreeNode::default();
+    for (index, file) in files.iter().enumerate() {
+        let path = file["path"].as_str().unwrap_or_default();
+        let relative = root_relative_path(path, root_path, relative_paths);
+        let mut components: Vec<&str> = relative.split('/').collect();
+        let name = components.pop().unwrap_or_default();
+        let directory = components.into_iter().fold(&mut tree, |node, component| {
+            node.directories.entry(component.to_string()).or_default()
+        });
+        directory.files.insert(name.to_string(), index);
+    }
+
+    let title = escape_html(title);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\" />\n<title>{}</title>\n</head>\n<body>\n<h1>{}</h1>\n<nav>\n",
+        title, title
+    );
+    tree.write(&mut html);
+    html.push_str("</nav>\n");
+    for (index, file) in files.iter().enumerate() {
+        let path = file["path"].as_str().unwrap_or_default();
+        let extension = file["extension"].as_str().unwrap_or_default();
+        let code = file["code"].as_str().unwrap_or_default();
+        let gutter = if line_numbers {
+            let numbers: Vec<String> = (1..=code.lines().count()).map(|number| number.to_string()).collect();
+            format!(
+                "<pre class=\"line-numbers\" aria-hidden=\"true\" style=\"user-select: none\">{}</pre>",
+                numbers.join("\n")
+            )
+        } else {
+            String::new()
+        };
+        html.push_str(&format!(
+            "<section id=\"file-{}\">\n<h2>{}</h2>\n{}<pre><code class=\"language-{}\">{}</code></pre>\n</section>\n",
+            index,
+            escape_html(path),
+            gutter,
+            file["language"]
+                .as_str()
+                .or_else(|| language_name(extension))
+                .unwrap_or("plaintext"),
+            escape_html(code)
+        ));
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
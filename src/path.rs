@@ -1,14 +1,37 @@
 //! This module contains the functions for traversing the directory and processing the files.
 
-use crate::filter::should_include_file;
-use anyhow::Result;
-use ignore::WalkBuilder;
-use log::debug;
+use crate::cache::{BlockCache, CachedBlock};
+use crate::error::Code2PromptError;
+use crate::anonymize::Anonymizer;
+use crate::filter::{is_explicitly_included, is_likely_secret, should_include_file_with_options};
+use crate::git::{get_changed_lines, get_tracked_files};
+use crate::language::{language_name, strip_leading_comment_block};
+use crate::output::bytes_hash;
+use crate::rust_api::public_api;
+use crate::source::{FileSource, FsSource, WalkOptions};
+use crate::token::{estimate_tokens, Tokenizer};
+use crate::Code2PromptConfig;
+use anyhow::{Context, Result};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use colored::*;
+use log::{debug, error};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::fs;
-use std::path::Path;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use termtree::Tree;
 
+/// The number of leading bytes of each file checked against the content deny patterns.
+pub const CONTENT_DENY_SCAN_BYTES: usize = 8 * 1024;
+
+/// The default size cap of the binary files included with `binary_as_base64`.
+pub const DEFAULT_MAX_BINARY_BYTES: usize = 64 * 1024;
+
 /// Traverses the directory and returns the string representation of the tree and the vector of JSON file representations.
 ///
 /// # Arguments
@@ -23,6 +46,7 @@ use termtree::Tree;
 /// # Returns
 ///
 /// A tuple containing the string representation of the directory tree and a vector of JSON representations of the files.
+#[allow(clippy::too_many_arguments)]
 pub fn traverse_directory(
     root_path: &Path,
     include: &[String],
@@ -33,25 +57,683 @@ pub fn traverse_directory(
     exclude_from_tree: bool,
     no_codeblock: bool,
 ) -> Result<(String, Vec<serde_json::Value>)> {
+    let config = Code2PromptConfig {
+        path: root_path.to_path_buf(),
+        include_priority,
+        line_number,
+        relative_paths,
+        exclude_from_tree,
+        no_codeblock,
+        ..Default::default()
+    };
+    traverse_with_config(&config, include, exclude)
+}
+
+/// Traverses the directory using the options of the configuration and returns the string representation
+/// of the tree and the vector of JSON file representations.
+///
+/// # Arguments
+///
+/// * `config` - The configuration, whose `path` is the root directory.
+/// * `include` - The patterns of files to include.
+/// * `exclude` - The patterns of files to exclude.
+///
+/// # Returns
+///
+/// A tuple containing the string representation of the directory tree and a vector of JSON representations of the files.
+pub fn traverse_with_config(
+    config: &Code2PromptConfig,
+    include: &[String],
+    exclude: &[String],
+) -> Result<(String, Vec<serde_json::Value>)> {
+    let traversal = traverse_source(&FsSource, config, include, exclude)?;
+    Ok((traversal.tree, traversal.files))
+}
+
+/// A file or directory that was left out of the prompt, along with the reason why.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedEntry {
+    pub path: String,
+    pub reason: String,
+}
+
+/// The result of a traversal: the directory tree, the included files and the manifest of skipped entries.
+#[derive(Debug, Clone, Default)]
+pub struct Traversal {
+    pub tree: String,
+    pub files: Vec<serde_json::Value>,
+    pub skipped: Vec<SkippedEntry>,
+    /// The original identifiers of the placeholders, when the files were anonymized.
+    pub anonymization_map: BTreeMap<String, String>,
+    /// The number of files whose block was reused from the cache, when `cache_dir` is set.
+    pub cache_hits: usize,
+    /// The number of files whose block was generated and stored in the cache, when `cache_dir` is set.
+    pub cache_misses: usize,
+}
+
+/// A file included in the prompt, with its formatted content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    /// The path shown in the prompt, absolute or prefixed by the root directory name with `relative_paths`.
+    pub path: String,
+    /// The path relative to the root directory, using `/` separators.
+    pub relative_path: String,
+    pub extension: String,
+    /// The language detected from the extension, e.g. `rust`, or its override from `language_override`.
+    pub language: Option<String>,
+    /// The size of the file in bytes, before formatting.
+    pub size: u64,
+    /// The token count of the formatted content, when `tokens` is set.
+    pub tokens: Option<usize>,
+    /// Whether the token count is an estimate, with `estimate_tokens`.
+    pub tokens_estimated: bool,
+    /// Whether the file is a symbolic link, detected with `follow_symlinks`.
+    pub is_symlink: bool,
+    /// The target of the symbolic link, as stored in the link.
+    pub link_target: Option<String>,
+    /// The Unix permission bits of the file, with `show_permissions` on sources that track them.
+    pub mode: Option<u32>,
+    /// Whether the file is executable, with `show_permissions`.
+    pub executable: Option<bool>,
+    /// The content, wrapped in a code block unless `no_codeblock` is set.
+    pub code: String,
+    /// How the content is encoded, `base64` for binary files included with `binary_as_base64`.
+    pub encoding: Option<&'static str>,
+    /// The content of the sidecar file of the file, with `sidecar_suffix`.
+    pub note: Option<String>,
+    /// The hex-encoded SHA-256 hash of the file as read, before formatting, with `include_hashes`.
+    pub sha256: Option<String>,
+}
+
+impl FileEntry {
+    /// Returns the JSON representation of the file used by the templates.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut file = json!({
+            "path": self.path,
+            "extension": self.extension,
+            "code": self.code,
+            "size": self.size,
+        });
+        if let Some(language) = &self.language {
+            file["language"] = json!(language);
+        }
+        if let Some(tokens) = self.tokens {
+            file["tokens"] = json!(tokens);
+            if self.tokens_estimated {
+                file["tokens_estimated"] = json!(true);
+            }
+        }
+        if self.is_symlink {
+            file["is_symlink"] = json!(true);
+            file["link_target"] = json!(self.link_target);
+        }
+        if let Some(executable) = self.executable {
+            file["executable"] = json!(executable);
+        }
+        if let Some(mode) = self.mode {
+            file["mode"] = json!(format!("{:o}", mode));
+        }
+        if let Some(encoding) = self.encoding {
+            file["encoding"] = json!(encoding);
+        }
+        if let Some(note) = &self.note {
+            file["note"] = json!(note);
+        }
+        if let Some(sha256) = &self.sha256 {
+            file["sha256"] = json!(sha256);
+        }
+        file
+    }
+}
+
+/// What became of a file encountered during a traversal.
+enum FileOutcome {
+    Included(FileEntry),
+    /// Left out of the prompt and listed in the manifest, along with the error when it couldn't be read.
+    Skipped(SkippedEntry, Option<anyhow::Error>),
+    /// Filtered out or without content to show.
+    Excluded,
+}
+
+/// The filter of the files of a traversal: the include and exclude patterns, and the options leaving files out.
+pub(crate) struct FileFilter<'a> {
+    source: &'a dyn FileSource,
+    config: &'a Code2PromptConfig,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    canonical_root_path: PathBuf,
+    /// The glob patterns of `language_override`, sorted by pattern, for the paths without an exact entry.
+    language_patterns: Vec<(glob::Pattern, String)>,
+}
+
+impl<'a> FileFilter<'a> {
+    pub(crate) fn new(
+        source: &'a dyn FileSource,
+        config: &'a Code2PromptConfig,
+        include: &[String],
+        exclude: &[String],
+    ) -> Result<Self> {
+        let mut language_patterns = config
+            .language_override
+            .iter()
+            .map(|(pattern, language)| {
+                let pattern = glob::Pattern::new(pattern.trim_start_matches("./"))
+                    .map_err(|e| invalid_pattern("language override", pattern, e))?;
+                Ok((pattern, language.clone()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        language_patterns.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+        Ok(Self {
+            source,
+            config,
+            include: include.to_vec(),
+            exclude: exclude.to_vec(),
+            canonical_root_path: source.canonicalize(&config.path)?,
+            language_patterns,
+        })
+    }
+
+    /// Returns the language of `language_override` for a root-relative path, preferring an exact entry.
+    fn language_override(&self, relative: &str) -> Option<&str> {
+        let overrides = &self.config.language_override;
+        let exact = overrides
+            .get(relative)
+            .or_else(|| overrides.get(&format!("./{}", relative)));
+        exact.map(String::as_str).or_else(|| {
+            self.language_patterns
+                .iter()
+                .find(|(pattern, _)| pattern.matches(relative))
+                .map(|(_, language)| language.as_str())
+        })
+    }
+
+    /// Returns whether a path passes the filters, see [`should_include_file_with_options`].
+    pub(crate) fn includes(&self, path: &Path) -> bool {
+        match self.source.canonicalize(path) {
+            Ok(canonical_path) => self.includes_canonical(&canonical_path),
+            Err(e) => {
+                error!("Failed to canonicalize path: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Returns whether a canonical path passes the filters, e.g. the former path of a removed file.
+    pub(crate) fn includes_canonical(&self, canonical_path: &Path) -> bool {
+        let path_str = canonical_path.to_string_lossy();
+        let relative = canonical_path
+            .strip_prefix(&self.canonical_root_path)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        // Sidecar files are attached to their files rather than included, unless there is no such file
+        if let Some(base) = self.config.sidecar_suffix.as_deref().and_then(|suffix| path_str.strip_suffix(suffix)) {
+            if self.source.metadata(Path::new(base)).is_ok_and(|metadata| metadata.is_file) {
+                return false;
+            }
+        }
+        let extension = canonical_path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+        let language = self.language_override(&relative).or_else(|| language_name(extension));
+        should_include_file_with_options(&path_str, &relative, language, &self.include, &self.exclude, self.config)
+    }
+}
+
+/// Filters and formats the files of a traversal, holding the state shared across files.
+struct FileProcessor<'a> {
+    source: &'a dyn FileSource,
+    config: &'a Code2PromptConfig,
+    filter: FileFilter<'a>,
+    canonical_root_path: PathBuf,
+    content_paths: HashSet<String>,
+    header_pattern: Option<Regex>,
+    content_deny: Vec<Regex>,
+    strip_headers: bool,
+    /// The lines changed since `mark_changes_since` in each file, by root-relative path.
+    changed_lines: HashMap<String, BTreeSet<usize>>,
+    anonymizer: Anonymizer,
+    /// The tokenizer counting the tokens of each file, when exact counts are requested.
+    tokenizer: Option<Arc<dyn Tokenizer>>,
+    cache: Option<BlockCache>,
+    cache_hits: usize,
+    cache_misses: usize,
+}
+
+impl<'a> FileProcessor<'a> {
+    fn new(
+        source: &'a dyn FileSource,
+        config: &'a Code2PromptConfig,
+        include: &[String],
+        exclude: &[String],
+    ) -> Result<Self> {
+        let canonical_root_path = source.canonicalize(&config.path)?;
+        let content_paths: HashSet<String> = config
+            .content_paths
+            .iter()
+            .map(|p| p.trim_start_matches("./").replace('\\', "/"))
+            .collect();
+
+        // Line numbers and change marks would no longer match the file, so headers are only stripped without them
+        let header_pattern = match &config.header_pattern {
+            Some(pattern) => Some(
+                Regex::new(&format!("^(?:{})", pattern)).map_err(|e| invalid_pattern("header", pattern, e))?,
+            ),
+            None => None,
+        };
+        let content_deny = config
+            .content_deny
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| invalid_pattern("content deny", pattern, e))
+            })
+            .collect::<Result<Vec<Regex>>>()?;
+        let wants_strip = config.strip_leading_comment_block || header_pattern.is_some();
+        let changed_lines = match &config.mark_changes_since {
+            Some(base_ref) => get_changed_lines(&config.path, base_ref)
+                .context("Marking the changed lines needs a git repository")?
+                .into_iter()
+                .map(|(path, lines)| (path.to_string_lossy().replace('\\', "/"), lines))
+                .collect(),
+            None => HashMap::new(),
+        };
+        if wants_strip && config.line_number && !config.quiet {
+            eprintln!(
+                "{}{}{} {}",
+                "[".bold().white(),
+                "!".bold().yellow(),
+                "]".bold().white(),
+                "Leading comment blocks are not stripped when line numbers are enabled.".yellow()
+            );
+        }
+
+        Ok(Self {
+            source,
+            config,
+            filter: FileFilter::new(source, config, include, exclude)?,
+            canonical_root_path,
+            content_paths,
+            header_pattern,
+            content_deny,
+            strip_headers: wants_strip && !config.line_number && config.mark_changes_since.is_none(),
+            changed_lines,
+            anonymizer: Anonymizer::new(),
+            tokenizer: (config.tokens && !config.estimate_tokens).then(|| config.tokenizer()),
+            cache: config.cache_dir.as_deref().map(|dir| BlockCache::new(dir, config)),
+            cache_hits: 0,
+            cache_misses: 0,
+        })
+    }
+
+    /// Filters, reads and formats an entry of the walk.
+    fn process(&mut self, path: &Path) -> FileOutcome {
+        let config = self.config;
+        let Ok(relative_path) = path.strip_prefix(&self.canonical_root_path) else {
+            return FileOutcome::Excluded;
+        };
+        let metadata = self.source.metadata(path).ok();
+        let is_file = metadata.is_some_and(|m| m.is_file);
+        if !is_file || !self.filter.includes(path) {
+            debug!("Excluded file: {:?}", path.display());
+            return FileOutcome::Excluded;
+        }
+
+        // The tree shows every file, but only the listed ones get a content block
+        let relative = relative_path.to_string_lossy().replace('\\', "/");
+        if !self.content_paths.is_empty() && !self.content_paths.contains(&relative) {
+            debug!("Excluded file content (not in content paths): {}", path.display());
+            return FileOutcome::Excluded;
+        }
+        if config.content_max_depth.is_some_and(|depth| relative_path.components().count() > depth) {
+            debug!("Excluded file content (deeper than the content max depth): {}", path.display());
+            return FileOutcome::Excluded;
+        }
+        let skipped = |reason: &str| SkippedEntry {
+            path: path.display().to_string(),
+            reason: reason.to_string(),
+        };
+        if is_likely_secret(path, &config.secret_patterns) {
+            warn_secret(path, config.block_secrets, config.quiet);
+            if config.block_secrets {
+                return FileOutcome::Skipped(skipped("secret"), None);
+            }
+        }
+
+        // The size floor, unless an include pattern asks for the file
+        let explicit = is_explicitly_included(&path.to_string_lossy(), &self.filter.include);
+        let size = metadata.map(|m| m.len).unwrap_or_default();
+        let mode = metadata.and_then(|m| m.mode).filter(|_| config.show_permissions);
+        if config.min_file_size.is_some_and(|min| size < min) && !explicit {
+            debug!("Skipped file (below the minimum size): {}", path.display());
+            return FileOutcome::Skipped(skipped("below-min-size"), None);
+        }
+
+        if self.matches_baseline(path, relative_path) {
+            debug!("Excluded file (unchanged from baseline): {}", path.display());
+            return FileOutcome::Skipped(skipped("unchanged-from-baseline"), None);
+        }
+
+        // Anonymized blocks depend on the placeholders assigned to the previous files, so they aren't cached
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        let language_override = self.filter.language_override(&relative).map(str::to_string);
+        // Marked blocks depend on the base reference too, so they aren't cached either
+        let changed_lines = self.changed_lines.get(&relative).cloned();
+        let cache_key = match (&self.cache, metadata) {
+            (Some(cache), Some(metadata)) if !config.anonymize && changed_lines.is_none() => {
+                cache.key(path, metadata.modified, metadata.len)
+            }
+            _ => None,
+        };
+        let cached = cache_key
+            .as_ref()
+            .and_then(|key| self.cache.as_ref()?.get(key));
+        let block = match cached {
+            Some(block) => {
+                self.cache_hits += 1;
+                block
+            }
+            None => {
+                let language = language_override.as_deref().unwrap_or(extension);
+                let mut fence = fence_info(config.fence_info_template.as_deref(), language, &relative);
+                if gutter_line_numbers(config) {
+                    fence.push_str(" showLineNumbers");
+                }
+                let block = match self.format_block(path, extension, &fence, changed_lines.as_ref()) {
+                    Ok(block) => block,
+                    Err(e) => return FileOutcome::Skipped(skipped("io-error"), Some(e.into())),
+                };
+                if let (Some(cache), Some(key)) = (&self.cache, &cache_key) {
+                    self.cache_misses += 1;
+                    cache.put(key, &block);
+                }
+                block
+            }
+        };
+        let (code, encoding, sha256) = match block {
+            CachedBlock::Included { code, sha256 } => (code, None, sha256),
+            CachedBlock::Base64 { content, sha256 } => (content, Some("base64"), sha256),
+            CachedBlock::BinaryTooLarge => {
+                debug!("Skipped file (binary larger than the cap): {}", path.display());
+                return FileOutcome::Skipped(skipped("binary-too-large"), None);
+            }
+            CachedBlock::DeniedContent => {
+                debug!("Excluded file (denied content): {}", path.display());
+                return FileOutcome::Skipped(skipped("denied-content"), None);
+            }
+            CachedBlock::Empty if config.skip_empty => {
+                debug!("Skipped file (empty): {}", path.display());
+                return FileOutcome::Skipped(skipped("empty"), None);
+            }
+            CachedBlock::Empty | CachedBlock::InvalidUtf8 => {
+                debug!("Excluded file (empty or invalid UTF-8): {}", path.display());
+                return FileOutcome::Excluded;
+            }
+        };
+
+        let file_path = if config.relative_paths {
+            format!("{}/{}", root_label(&self.canonical_root_path, config.stable), relative_path.display())
+        } else {
+            path.display().to_string()
+        };
+        let tokens = match &self.tokenizer {
+            Some(tokenizer) => Some(tokenizer.count(&code)),
+            None if config.tokens => Some(estimate_tokens(&code, &config.encoding)),
+            None => None,
+        };
+        if let Some(min) = config.min_file_tokens.filter(|_| !explicit) {
+            let count = tokens.unwrap_or_else(|| estimate_tokens(&code, &config.encoding));
+            if count < min {
+                debug!("Skipped file (below the minimum tokens): {}", path.display());
+                return FileOutcome::Skipped(skipped("below-min-tokens"), None);
+            }
+        }
+        let link_target = if config.follow_symlinks {
+            self.source.link_target(path)
+        } else {
+            None
+        };
+        let note = self.read_note(path);
+        // The note is rendered with the code, so it counts towards the tokens of the file
+        let tokens = tokens.map(|tokens| match (&note, &self.tokenizer) {
+            (Some(note), Some(tokenizer)) => tokens + tokenizer.count(note),
+            (Some(note), None) => tokens + estimate_tokens(note, &config.encoding),
+            (None, _) => tokens,
+        });
+        debug!(target: "included_files", "Included file: {}", file_path);
+        FileOutcome::Included(FileEntry {
+            path: file_path,
+            relative_path: relative,
+            extension: extension.to_string(),
+            language: language_override.or_else(|| language_name(extension).map(str::to_string)),
+            size,
+            tokens,
+            tokens_estimated: tokens.is_some() && config.estimate_tokens,
+            is_symlink: link_target.is_some(),
+            link_target: link_target.map(|target| target.display().to_string()),
+            mode,
+            executable: config.show_permissions.then(|| mode.is_some_and(|mode| mode & 0o111 != 0)),
+            code,
+            encoding,
+            note,
+            sha256,
+        })
+    }
+
+    /// Returns whether a file has the same content as the corresponding file of the baseline tree.
+    fn matches_baseline(&self, path: &Path, relative_path: &Path) -> bool {
+        let Some(baseline) = &self.config.baseline else {
+            return false;
+        };
+        let Ok(baseline_bytes) = std::fs::read(baseline.join(relative_path)) else {
+            return false;
+        };
+        self.source.read(path).is_ok_and(|bytes| bytes_hash(&bytes) == bytes_hash(&baseline_bytes))
+    }
+
+    /// Reads the sidecar file of a file, with the same content filters and transformations as the code.
+    fn read_note(&mut self, path: &Path) -> Option<String> {
+        let config = self.config;
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(config.sidecar_suffix.as_ref()?);
+        let sidecar = PathBuf::from(sidecar);
+        if is_likely_secret(&sidecar, &config.secret_patterns) {
+            warn_secret(&sidecar, config.block_secrets, config.quiet);
+            if config.block_secrets {
+                return None;
+            }
+        }
+        let note_bytes = self.source.read(&sidecar).ok()?;
+        if is_denied_content(&note_bytes, &self.content_deny) {
+            debug!("Excluded note (denied content): {}", sidecar.display());
+            return None;
+        }
+        let mut note = String::from_utf8_lossy(&note_bytes);
+        if config.line_ending != LineEnding::Preserve {
+            if let Cow::Owned(normalized) = normalize_line_endings(&note) {
+                note = normalized.into();
+            }
+        }
+        if config.strip_ansi {
+            if let Cow::Owned(stripped) = strip_ansi_escapes(&note) {
+                note = stripped.into();
+            }
+        }
+        if config.anonymize {
+            note = self.anonymizer.anonymize_text(&note).into();
+        }
+        let note = note.trim_end();
+        Some(match config.line_ending {
+            LineEnding::Crlf => note.replace('\n', "\r\n"),
+            _ => note.to_string(),
+        })
+    }
+
+    /// Reads a file and formats its content block.
+    fn format_block(
+        &mut self,
+        path: &Path,
+        extension: &str,
+        fence: &str,
+        changed_lines: Option<&BTreeSet<usize>>,
+    ) -> std::io::Result<CachedBlock> {
+        let config = self.config;
+        let code_bytes = self.source.read(path)?;
+        if is_denied_content(&code_bytes, &self.content_deny) {
+            return Ok(CachedBlock::DeniedContent);
+        }
+        let sha256 = config.include_hashes.then(|| bytes_hash(&code_bytes));
+
+        let mut code = String::from_utf8_lossy(&code_bytes);
+        if code.contains(char::REPLACEMENT_CHARACTER) {
+            if !config.binary_as_base64 {
+                return Ok(CachedBlock::InvalidUtf8);
+            }
+            // The cap is checked on the bytes read, so that files growing after the walk are caught too
+            if code_bytes.len() > config.max_binary_bytes() {
+                return Ok(CachedBlock::BinaryTooLarge);
+            }
+            return Ok(CachedBlock::Base64 {
+                content: BASE64_STANDARD.encode(&code_bytes),
+                sha256,
+            });
+        }
+        // Normalized first, so that the lines are split the same way whatever the endings of the file. When
+        // marking the changed lines, lone `\r` are kept, as git doesn't split the lines on them either
+        if config.line_ending != LineEnding::Preserve {
+            if changed_lines.is_some() {
+                if code.contains("\r\n") {
+                    code = code.replace("\r\n", "\n").into();
+                }
+            } else if let Cow::Owned(normalized) = normalize_line_endings(&code) {
+                code = normalized.into();
+            }
+        }
+        if config.strip_ansi {
+            if let Cow::Owned(stripped) = strip_ansi_escapes(&code) {
+                code = stripped.into();
+            }
+        }
+        if self.strip_headers {
+            if let Some(stripped) = strip_header(&code, extension, &self.header_pattern, config.strip_leading_comment_block) {
+                code = stripped.into();
+            }
+        }
+        if config.pub_only && extension == "rs" {
+            match public_api(&code) {
+                Ok(api) => code = api.into(),
+                Err(e) => warn_unparsed_rust(path, &e, config.quiet),
+            }
+        }
+        if config.anonymize {
+            code = self.anonymizer.anonymize(&code, extension).into();
+        }
+        if code.trim().is_empty() {
+            return Ok(CachedBlock::Empty);
+        }
+        if let Some(changed_lines) = changed_lines {
+            code = mark_changed_lines(&code, changed_lines).into();
+        }
+        let mut block = wrap_code_block(
+            &code,
+            fence,
+            config.line_number && !gutter_line_numbers(config),
+            config.no_codeblock,
+        );
+        if config.line_ending == LineEnding::Crlf {
+            block = block.replace('\n', "\r\n");
+        }
+        Ok(CachedBlock::Included { code: block, sha256 })
+    }
+}
+
+/// Traverses a file source using the options of the configuration.
+///
+/// Entries that can't be read are skipped with a warning rather than failing the traversal;
+/// only an inaccessible root path is an error.
+///
+/// # Arguments
+///
+/// * `source` - The source of the files, e.g. the real filesystem or an in-memory tree.
+/// * `config` - The configuration, whose `path` is the root directory within the source.
+/// * `include` - The patterns of files to include.
+/// * `exclude` - The patterns of files to exclude.
+///
+/// # Returns
+///
+/// * `Result<Traversal>` - The directory tree, the JSON representations of the files and the skipped entries.
+pub fn traverse_source(
+    source: &dyn FileSource,
+    config: &Code2PromptConfig,
+    include: &[String],
+    exclude: &[String],
+) -> Result<Traversal> {
+    traverse_source_with_callback(source, config, include, exclude, None)
+}
+
+/// Traverses a file source like [`traverse_source`], invoking a callback as each file is included.
+///
+/// The callback receives the metadata of the file, such as its size, language and token count, so
+/// embedders can collect telemetry without parsing the output.
+///
+/// # Arguments
+///
+/// * `source` - The source of the files, e.g. the real filesystem or an in-memory tree.
+/// * `config` - The configuration, whose `path` is the root directory within the source.
+/// * `include` - The patterns of files to include.
+/// * `exclude` - The patterns of files to exclude.
+/// * `on_file` - The callback invoked with each included file, in traversal order.
+///
+/// # Returns
+///
+/// * `Result<Traversal>` - The directory tree, the JSON representations of the files and the skipped entries.
+pub fn traverse_source_with_callback(
+    source: &dyn FileSource,
+    config: &Code2PromptConfig,
+    include: &[String],
+    exclude: &[String],
+    on_file: Option<&dyn Fn(&FileEntry)>,
+) -> Result<Traversal> {
     // ~~~ Initialization ~~~
     let mut files = Vec::new();
-    let canonical_root_path = root_path.canonicalize()?;
-    let parent_directory = label(&canonical_root_path);
+    let mut skipped = Vec::new();
+    let mut processor = FileProcessor::new(source, config, include, exclude)?;
+    let canonical_root_path = processor.canonical_root_path.clone();
+    let parent_directory = root_label(&canonical_root_path, config.stable);
 
     // ~~~ Build the Tree ~~~
-    let tree = WalkBuilder::new(&canonical_root_path)
-        .git_ignore(true)
-        .build()
-        .filter_map(|e| e.ok())
+    let mut annotations = TreeAnnotations::default();
+    let entries: Vec<PathBuf> = walk_source(source, config, &canonical_root_path)
+        .filter_map(|entry| match entry {
+            Ok(path) => Some(path),
+            Err(e) => {
+                let path = error_path(&e);
+                warn_io_error(&path, &e, config.quiet);
+                skipped.push(SkippedEntry {
+                    path,
+                    reason: "io-error".to_string(),
+                });
+                None
+            }
+        })
+        .collect();
+    let mut tree = entries
+        .iter()
         .fold(Tree::new(parent_directory.to_owned()), |mut root, entry| {
-            let path = entry.path();
+            let path = entry.as_path();
             if let Ok(relative_path) = path.strip_prefix(&canonical_root_path) {
+                if config.follow_symlinks {
+                    if let Some(target) = source.link_target(path) {
+                        annotations.links.push((relative_path.to_path_buf(), target));
+                    }
+                }
                 let mut current_tree = &mut root;
-                for component in relative_path.components() {
+                let tree_depth = if config.no_tree {
+                    0
+                } else {
+                    config.tree_max_depth.unwrap_or(usize::MAX)
+                };
+                for component in relative_path.components().take(tree_depth) {
                     let component_str = component.as_os_str().to_string_lossy().to_string();
 
                     // Check if the current component should be excluded from the tree
-                    if exclude_from_tree && !should_include_file(path, include, exclude, include_priority) {
+                    if config.exclude_from_tree && !processor.filter.includes(path) {
                         break;
                     }
 
@@ -69,40 +751,475 @@ pub fn traverse_directory(
                 }
 
                 // ~~~ Process the file ~~~
-                if path.is_file() && should_include_file(path, include, exclude, include_priority) {
-                    if let Ok(code_bytes) = fs::read(path) {
-                        let code = String::from_utf8_lossy(&code_bytes);
-
-                        let code_block = wrap_code_block(&code, path.extension().and_then(|ext| ext.to_str()).unwrap_or(""), line_number, no_codeblock);
-
-                        if !code.trim().is_empty() && !code.contains(char::REPLACEMENT_CHARACTER) {
-                            let file_path = if relative_paths {
-                                format!("{}/{}", parent_directory, relative_path.display())
-                            } else {
-                                path.display().to_string()
-                            };
-
-                            files.push(json!({
-                                "path": file_path,
-                                "extension": path.extension().and_then(|ext| ext.to_str()).unwrap_or(""),
-                                "code": code_block,
-                            }));
-                            debug!(target: "included_files", "Included file: {}", file_path);
-                        } else {
-                            debug!("Excluded file (empty or invalid UTF-8): {}", path.display());
+                match processor.process(path) {
+                    FileOutcome::Included(file) => {
+                        if let Some(on_file) = on_file {
+                            on_file(&file);
                         }
-                    } else {
-                        debug!("Failed to read file: {}", path.display());
+                        if config.tree_line_stats {
+                            annotations
+                                .line_counts
+                                .insert(relative_path.to_path_buf(), content_line_count(&file.code, config.no_codeblock));
+                        }
+                        if file.executable == Some(true) {
+                            annotations.executables.push(relative_path.to_path_buf());
+                        }
+                        files.push(file.to_json());
                     }
-                } else {
-                    debug!("Excluded file: {:?}", path.display());
+                    FileOutcome::Skipped(entry, error) => {
+                        if let Some(e) = error {
+                            warn_io_error(&entry.path, &e, config.quiet);
+                        }
+                        skipped.push(entry);
+                    }
+                    FileOutcome::Excluded => {}
                 }
             }
 
             root
         });
 
-    Ok((tree.to_string(), files))
+    // Names renamed before the annotations, which then find the entries by their placeholders
+    if config.anonymize && !config.no_tree {
+        let anonymizer = &mut processor.anonymizer;
+        anonymize_tree(&mut tree, anonymizer);
+        let mut anonymize = |path: &Path| PathBuf::from(anonymizer.anonymize_path(&path.to_string_lossy()));
+        annotations = TreeAnnotations {
+            line_counts: annotations.line_counts.into_iter().map(|(path, lines)| (anonymize(&path), lines)).collect(),
+            links: annotations.links.into_iter().map(|(path, target)| (anonymize(&path), anonymize(&target))).collect(),
+            executables: annotations.executables.iter().map(|path| anonymize(path)).collect(),
+        };
+    }
+
+    let tree = if config.no_tree { String::new() } else { render_tree(tree, &annotations, config) };
+
+    Ok(Traversal {
+        tree,
+        files,
+        skipped,
+        anonymization_map: processor.anonymizer.mapping().clone(),
+        cache_hits: processor.cache_hits,
+        cache_misses: processor.cache_misses,
+    })
+}
+
+/// Lazily yields the filtered and formatted files of a source, one at a time.
+///
+/// Created by [`iter_source_files`]. Entries that can't be read are yielded as errors, so the caller
+/// decides whether to skip them; files left out for other reasons, such as secrets, are not yielded.
+pub struct FileIter<'a> {
+    entries: Box<dyn Iterator<Item = Result<PathBuf>> + 'a>,
+    processor: FileProcessor<'a>,
+}
+
+impl FileIter<'_> {
+    /// Returns the original identifiers of the placeholders assigned so far, when the files are anonymized.
+    pub fn anonymization_map(&self) -> &BTreeMap<String, String> {
+        self.processor.anonymizer.mapping()
+    }
+}
+
+impl Iterator for FileIter<'_> {
+    type Item = Result<FileEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let path = match self.entries.next()? {
+                Ok(path) => path,
+                Err(e) => return Some(Err(e)),
+            };
+            match self.processor.process(&path) {
+                FileOutcome::Included(file) => return Some(Ok(file)),
+                FileOutcome::Skipped(entry, Some(e)) => {
+                    return Some(Err(e.context(format!("Failed to read file: {}", entry.path))))
+                }
+                FileOutcome::Skipped(_, None) | FileOutcome::Excluded => {}
+            }
+        }
+    }
+}
+
+/// Returns an iterator over the files of a source, filtered and formatted like in [`traverse_source`].
+///
+/// Files are read one at a time as the iterator advances, so memory stays bounded on large trees.
+///
+/// # Arguments
+///
+/// * `source` - The source of the files, e.g. the real filesystem or an in-memory tree.
+/// * `config` - The configuration, whose `path` is the root directory within the source.
+/// * `include` - The patterns of files to include.
+/// * `exclude` - The patterns of files to exclude.
+///
+/// # Returns
+///
+/// * `Result<FileIter>` - The iterator over the files, or an error for an inaccessible root or invalid options.
+pub fn iter_source_files<'a>(
+    source: &'a dyn FileSource,
+    config: &'a Code2PromptConfig,
+    include: &[String],
+    exclude: &[String],
+) -> Result<FileIter<'a>> {
+    let processor = FileProcessor::new(source, config, include, exclude)?;
+    let entries = walk_source(source, config, &processor.canonical_root_path);
+    Ok(FileIter { entries, processor })
+}
+
+/// Walks a source with the walk options of the configuration.
+fn walk_source<'a>(
+    source: &'a dyn FileSource,
+    config: &Code2PromptConfig,
+    root: &Path,
+) -> Box<dyn Iterator<Item = Result<PathBuf>> + 'a> {
+    let options = WalkOptions {
+        follow_symlinks: config.follow_symlinks,
+        extra_ignore_files: config.extra_ignore_files.clone(),
+        sorted: config.seed.is_some() || config.stable,
+    };
+    let mut entries = source.walk_with_options(root, &options);
+
+    // The output of a previous run and the template aren't part of the codebase
+    if config.self_exclude() {
+        let generated: Vec<PathBuf> = [config.output.as_ref().map(PathBuf::from), config.template_path()]
+            .into_iter()
+            .flatten()
+            .filter_map(|path| path.canonicalize().ok())
+            .collect();
+        if !generated.is_empty() {
+            entries = Box::new(entries.filter(move |entry| match entry {
+                Ok(path) => !generated.contains(path),
+                Err(_) => true,
+            }));
+        }
+    }
+    if !config.git_tracked_only {
+        return entries;
+    }
+    let tracked = match get_tracked_files(&config.path) {
+        Ok(tracked) => tracked,
+        Err(e) => {
+            if !config.quiet {
+                eprintln!(
+                    "{}{}{} {}",
+                    "[".bold().white(),
+                    "!".bold().yellow(),
+                    "]".bold().white(),
+                    format!("Not limiting the files to the ones tracked by git: {}", e).yellow()
+                );
+            }
+            return entries;
+        }
+    };
+
+    // Directories are kept when they contain a tracked file, so that the tree still shows it
+    let mut kept: HashSet<PathBuf> = HashSet::new();
+    for file in tracked {
+        kept.extend(file.ancestors().skip(1).map(Path::to_path_buf));
+        kept.insert(file);
+    }
+    let root = root.to_path_buf();
+    Box::new(entries.filter(move |entry| match entry {
+        Ok(path) => path.strip_prefix(&root).is_ok_and(|relative| kept.contains(relative)),
+        Err(_) => true,
+    }))
+}
+
+/// Counts the lines of the content of a formatted file block, without the fences and the trailing blank lines.
+pub(crate) fn content_line_count(code: &str, no_codeblock: bool) -> usize {
+    let mut lines: Vec<&str> = code.lines().collect();
+    if !no_codeblock && lines.len() >= 2 {
+        lines = lines[1..lines.len() - 1].to_vec();
+    }
+    lines.iter().rposition(|line| !line.trim().is_empty()).map_or(0, |last| last + 1)
+}
+
+/// Appends the line counts to the entries of a subtree: `(120 lines, 4%)` to the files, where the percentage
+/// is of the `total` lines of the included files, and the sum of the lines of their files to the directories.
+///
+/// Entries without included files are left as-is. The sums are built bottom-up, returning the lines of the
+/// included files of the subtree, if any.
+fn annotate_line_stats(
+    node: &mut Tree<String>,
+    parent: &Path,
+    line_counts: &HashMap<PathBuf, usize>,
+    total: usize,
+) -> Option<usize> {
+    let path = parent.join(&node.root);
+    let plural = |lines: usize| if lines == 1 { "line" } else { "lines" };
+    if let Some(&lines) = line_counts.get(&path) {
+        let percent = if total == 0 { 0.0 } else { lines as f64 * 100.0 / total as f64 };
+        node.root = format!("{} ({} {}, {:.0}%)", node.root, lines, plural(lines), percent);
+        return Some(lines);
+    }
+    let lines = node
+        .leaves
+        .iter_mut()
+        .filter_map(|child| annotate_line_stats(child, &path, line_counts, total))
+        .reduce(|sum, lines| sum + lines)?;
+    node.root = format!("{} ({} {})", node.root, lines, plural(lines));
+    Some(lines)
+}
+
+/// Renames the identifiers of the names of the entries of a subtree, see [`Anonymizer::anonymize_path`].
+fn anonymize_tree(node: &mut Tree<String>, anonymizer: &mut Anonymizer) {
+    node.root = anonymizer.anonymize_path(&node.root);
+    for child in node.leaves.iter_mut() {
+        anonymize_tree(child, anonymizer);
+    }
+}
+
+/// The annotations of the entries of a tree, by root-relative path.
+#[derive(Default)]
+struct TreeAnnotations {
+    /// The line counts of the included files, with `tree_line_stats`.
+    line_counts: HashMap<PathBuf, usize>,
+    /// The symbolic links and their targets.
+    links: Vec<(PathBuf, PathBuf)>,
+    /// The executable files.
+    executables: Vec<PathBuf>,
+}
+
+/// Annotates the entries of a tree, caps its directories by `max_tree_entries` and renders it, preceded by the
+/// legend of the annotations it shows with `tree_legend`.
+fn render_tree(mut tree: Tree<String>, annotations: &TreeAnnotations, config: &Code2PromptConfig) -> String {
+    // Capped directories keep their first entries by name, so every directory is sorted by its bare names
+    if config.max_tree_entries.is_some() {
+        sort_tree_entries(&mut tree);
+    }
+
+    // Line counts first; the other annotations find the entries by the start of their names
+    if config.tree_line_stats {
+        let total = annotations.line_counts.values().sum();
+        for child in tree.leaves.iter_mut() {
+            annotate_line_stats(child, Path::new(""), &annotations.line_counts, total);
+        }
+    }
+
+    // Links are annotated once the tree is built, so that their entries are all there
+    let links: Vec<&Path> = annotations
+        .links
+        .iter()
+        .filter(|(relative_path, target)| annotate_link(&mut tree, relative_path, target))
+        .map(|(relative_path, _)| relative_path.as_path())
+        .collect();
+
+    // Executables last, marking the names of entries that may already be annotated
+    let executables: Vec<&Path> = annotations
+        .executables
+        .iter()
+        .filter(|relative_path| annotate_executable(&mut tree, relative_path))
+        .map(PathBuf::as_path)
+        .collect();
+
+    // Capped once annotated, so that the annotations still find the entries by name
+    let collapsed = config
+        .max_tree_entries
+        .is_some_and(|max_entries| collapse_tree_entries(&mut tree, max_entries, config.truncation_marker()));
+
+    if !config.tree_legend {
+        return tree.to_string();
+    }
+    // The legend lists the annotations of the entries left once capped, e.g. of the directories of deeper files
+    let mut shown = |relative_path: &Path| find_entry(&mut tree, relative_path).is_some();
+    let line_stats = annotations.line_counts.keys().any(|relative_path| {
+        relative_path.ancestors().any(|path| !path.as_os_str().is_empty() && shown(path))
+    });
+    let links = links.iter().any(|relative_path| shown(relative_path));
+    let executables = executables.iter().any(|relative_path| shown(relative_path));
+    let legend = tree_legend(line_stats, links, executables, collapsed.then(|| config.truncation_marker()));
+    match legend {
+        Some(legend) => format!("{}\n{}", legend, tree),
+        None => tree.to_string(),
+    }
+}
+
+/// Appends the target of a symbolic link to its entry of the tree, e.g. `name -> target`.
+///
+/// Returns whether the entry was found, e.g. not beyond the depth of the tree.
+fn annotate_link(root: &mut Tree<String>, relative_path: &Path, target: &Path) -> bool {
+    match find_entry(root, relative_path) {
+        Some(entry) => {
+            entry.root = format!("{} -> {}", entry.root, target.display());
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns the entry of the tree at a path, matching the names of entries that may already be annotated.
+fn find_entry<'a>(root: &'a mut Tree<String>, relative_path: &Path) -> Option<&'a mut Tree<String>> {
+    let mut current_tree = root;
+    for component in relative_path.components() {
+        let name = component.as_os_str().to_string_lossy();
+        let is_entry = |entry: &str| {
+            entry
+                .strip_prefix(name.as_ref())
+                .map(|rest| rest.strip_prefix('*').unwrap_or(rest))
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+        };
+        current_tree = current_tree.leaves.iter_mut().find(|child| is_entry(&child.root))?;
+    }
+    Some(current_tree)
+}
+
+/// Sorts the entries of each directory of the tree by name.
+fn sort_tree_entries(node: &mut Tree<String>) {
+    node.leaves.sort_by(|a, b| a.root.cmp(&b.root));
+    for child in node.leaves.iter_mut() {
+        sort_tree_entries(child);
+    }
+}
+
+/// Keeps the first entries of each directory of the tree, collapsing the others into an entry of the
+/// truncation marker, e.g. `... [truncated] ... (N more)`.
+///
+/// Returns whether any entry was collapsed.
+fn collapse_tree_entries(node: &mut Tree<String>, max_entries: usize, marker: &str) -> bool {
+    let mut collapsed = false;
+    if node.leaves.len() > max_entries {
+        let more = node.leaves.len() - max_entries;
+        node.leaves.truncate(max_entries);
+        node.leaves.push(Tree::new(format!("{} ({} more)", marker, more)));
+        collapsed = true;
+    }
+    for child in node.leaves.iter_mut() {
+        collapsed |= collapse_tree_entries(child, max_entries, marker);
+    }
+    collapsed
+}
+
+/// Builds the legend of the annotations of the tree, listing only those that appear in it.
+///
+/// # Arguments
+///
+/// * `line_stats` - Whether entries are annotated with their line counts.
+/// * `links` - Whether symbolic links are annotated with their targets.
+/// * `executables` - Whether executable files are marked.
+/// * `collapsed` - The truncation marker of the collapsed entries, if any.
+///
+/// # Returns
+///
+/// * `Option<String>` - The legend, one line per annotation, or `None` without annotations.
+fn tree_legend(line_stats: bool, links: bool, executables: bool, collapsed: Option<&str>) -> Option<String> {
+    let mut lines = Vec::new();
+    if line_stats {
+        lines.push("  (N lines, P%)  the lines of a file and its share of all the lines; the lines of a directory".to_string());
+    }
+    if links {
+        lines.push("  name -> target  a symbolic link and its target".to_string());
+    }
+    if executables {
+        lines.push("  name*           an executable file".to_string());
+    }
+    if let Some(marker) = collapsed {
+        lines.push(format!("  {} (N more)  the other N entries of the directory, left out of the tree", marker));
+    }
+    (!lines.is_empty()).then(|| format!("Legend:\n{}\n", lines.join("\n")))
+}
+
+/// Appends a `*` to the name of an executable file in the tree, e.g. `build.sh*`, like `ls -F`.
+///
+/// The entries are found by the start of their names, which may be followed by other annotations. Returns
+/// whether the entry was found.
+fn annotate_executable(root: &mut Tree<String>, relative_path: &Path) -> bool {
+    match find_entry(root, relative_path) {
+        Some(entry) => {
+            let name_len = relative_path.file_name().map_or(0, |name| name.to_string_lossy().len());
+            entry.root.insert(name_len, '*');
+            true
+        }
+        None => false,
+    }
+}
+
+/// Removes the header of a file, matched by the header pattern or else detected by comment syntax.
+fn strip_header(code: &str, extension: &str, header_pattern: &Option<Regex>, by_syntax: bool) -> Option<String> {
+    if let Some(pattern) = header_pattern {
+        if let Some(found) = pattern.find(code) {
+            return Some(code[found.end()..].trim_start_matches(['\r', '\n']).to_string());
+        }
+    }
+    if by_syntax {
+        return strip_leading_comment_block(code, extension);
+    }
+    None
+}
+
+/// Returns whether the beginning of a file matches one of the content deny patterns.
+fn is_denied_content(code_bytes: &[u8], content_deny: &[Regex]) -> bool {
+    if content_deny.is_empty() {
+        return false;
+    }
+    let head = String::from_utf8_lossy(&code_bytes[..code_bytes.len().min(CONTENT_DENY_SCAN_BYTES)]);
+    content_deny.iter().any(|pattern| pattern.is_match(&head))
+}
+
+/// Returns the path an I/O error of the walk relates to, or an empty string when it is unknown.
+fn error_path(error: &anyhow::Error) -> String {
+    fn walk_error_path(error: &ignore::Error) -> Option<&Path> {
+        match error {
+            ignore::Error::WithPath { path, .. } => Some(path),
+            ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+                walk_error_path(err)
+            }
+            ignore::Error::Partial(errors) => errors.iter().find_map(walk_error_path),
+            _ => None,
+        }
+    }
+    error
+        .downcast_ref::<ignore::Error>()
+        .and_then(walk_error_path)
+        .map(|path| path.display().to_string())
+        .unwrap_or_default()
+}
+
+/// Emits a warning for an entry that is skipped because it couldn't be read.
+fn warn_io_error(path: &str, error: &anyhow::Error, quiet: bool) {
+    if quiet {
+        return;
+    }
+    eprintln!(
+        "{}{}{} {}",
+        "[".bold().white(),
+        "!".bold().yellow(),
+        "]".bold().white(),
+        format!("Skipped unreadable entry {}: {}", path, error).yellow()
+    );
+}
+
+/// Warns that a Rust file couldn't be reduced to its public API, so it is included in full.
+fn warn_unparsed_rust(path: &Path, error: &syn::Error, quiet: bool) {
+    if quiet {
+        return;
+    }
+    eprintln!(
+        "{}{}{} {}",
+        "[".bold().white(),
+        "!".bold().yellow(),
+        "]".bold().white(),
+        format!("Included the full content of {}, which failed to parse: {}", path.display(), error).yellow()
+    );
+}
+
+/// Emits a prominent warning for a file that likely contains secrets.
+fn warn_secret(path: &Path, blocked: bool, quiet: bool) {
+    if quiet {
+        return;
+    }
+    let message = if blocked {
+        format!("Skipped likely secret file: {}", path.display())
+    } else {
+        format!(
+            "Likely secret file included in the prompt: {} (set block_secrets to skip it)",
+            path.display()
+        )
+    };
+    eprintln!(
+        "{}{}{} {}",
+        "[".bold().white(),
+        "!".bold().red(),
+        "]".bold().white(),
+        message.red()
+    );
 }
 
 /// Returns the file name or the string representation of the path.
@@ -131,20 +1248,580 @@ pub fn label<P: AsRef<Path>>(p: P) -> String {
     }
 }
 
+/// The label of the root directory in stable output, whatever the directory is named.
+pub const STABLE_ROOT_LABEL: &str = ".";
+
+/// Returns the label of the root directory: its name, or the fixed label of stable output.
+///
+/// # Arguments
+///
+/// * `root_path` - The path to the root directory.
+/// * `stable` - Whether the output is stable.
+///
+/// # Returns
+///
+/// * `String` - The label of the root directory.
+pub fn root_label<P: AsRef<Path>>(root_path: P, stable: bool) -> String {
+    if stable {
+        STABLE_ROOT_LABEL.to_string()
+    } else {
+        label(root_path)
+    }
+}
+
+/// Returns the path of a file entry relative to the root directory, using `/` separators.
+///
+/// # Arguments
+///
+/// * `file_path` - The `path` of a file entry, as produced by the traversal.
+/// * `root_path` - The path to the root directory.
+/// * `relative_paths` - Whether the traversal produced relative paths.
+///
+/// # Returns
+///
+/// * `String` - The path relative to the root directory, e.g. `src/main.rs`.
+pub fn root_relative_path(file_path: &str, root_path: &Path, relative_paths: bool) -> String {
+    let canonical_root_path = root_path
+        .canonicalize()
+        .unwrap_or_else(|_| root_path.to_path_buf());
+    let relative = if relative_paths {
+        let prefix = format!("{}/", label(&canonical_root_path));
+        let stable_prefix = format!("{}/", STABLE_ROOT_LABEL);
+        file_path
+            .strip_prefix(&prefix)
+            .or_else(|| file_path.strip_prefix(&stable_prefix))
+            .unwrap_or(file_path)
+            .to_string()
+    } else {
+        Path::new(file_path)
+            .strip_prefix(&canonical_root_path)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|_| file_path.to_string())
+    };
+    if cfg!(windows) {
+        relative.replace('\\', "/")
+    } else {
+        relative
+    }
+}
+
+/// Builds the error of an invalid pattern of the configuration.
+fn invalid_pattern(kind: &'static str, pattern: &str, error: impl std::fmt::Display) -> anyhow::Error {
+    Code2PromptError::InvalidPattern {
+        kind,
+        pattern: pattern.to_string(),
+        error: error.to_string(),
+    }
+    .into()
+}
+
+/// How the file entries are sorted, see [`Code2PromptConfig::sort_files`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSort {
+    /// The most recently committed files first, or the most recently modified ones for files without commits.
+    ByRecency,
+}
+
+/// Sorts the file entries by recency, the most recent first.
+///
+/// A file is as recent as the last commit touching it, or as its modification time when it has no commit,
+/// e.g. outside of a git repository or when it is untracked. Files equally recent keep their order.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files.
+/// * `commit_times` - The Unix timestamp of the last commit of each file, by root-relative path.
+/// * `root_path` - The path to the root directory.
+/// * `relative_paths` - Whether the traversal produced relative paths.
+pub fn sort_by_recency(
+    files: &mut [serde_json::Value],
+    commit_times: &HashMap<PathBuf, i64>,
+    root_path: &Path,
+    relative_paths: bool,
+) {
+    sort_by_recency_with_fallback(files, commit_times, root_path, relative_paths, true)
+}
+
+/// Sorts the file entries by recency like [`sort_by_recency`], choosing whether the files without commits are
+/// as recent as their modification time, or the oldest, e.g. for stable output, which can't depend on it.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files.
+/// * `commit_times` - The Unix timestamp of the last commit of each file, by root-relative path.
+/// * `root_path` - The path to the root directory.
+/// * `relative_paths` - Whether the traversal produced relative paths.
+/// * `modified_fallback` - Whether the files without commits are ranked by their modification time.
+pub fn sort_by_recency_with_fallback(
+    files: &mut [serde_json::Value],
+    commit_times: &HashMap<PathBuf, i64>,
+    root_path: &Path,
+    relative_paths: bool,
+    modified_fallback: bool,
+) {
+    files.sort_by_cached_key(|file| {
+        let path = file["path"].as_str().unwrap_or_default();
+        let relative = PathBuf::from(root_relative_path(path, root_path, relative_paths));
+        let time = commit_times.get(&relative).copied().unwrap_or_else(|| {
+            if !modified_fallback {
+                return 0;
+            }
+            std::fs::metadata(root_path.join(&relative))
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |elapsed| elapsed.as_secs() as i64)
+        });
+        std::cmp::Reverse(time)
+    });
+}
+
+/// Shuffles the file entries into an order derived from a seed, by a hash of the seed and their root-relative path.
+///
+/// This runs before the rankings by churn or recency, whose stable sorts then break ties in the seeded order: the
+/// same seed always gives the same order, and different seeds give different ones.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files.
+/// * `seed` - The seed of the order.
+/// * `root_path` - The path to the root directory.
+/// * `relative_paths` - Whether the traversal produced relative paths.
+pub fn shuffle_with_seed(files: &mut [serde_json::Value], seed: u64, root_path: &Path, relative_paths: bool) {
+    files.sort_by_cached_key(|file| {
+        let path = file["path"].as_str().unwrap_or_default();
+        let relative = root_relative_path(path, root_path, relative_paths);
+        // FNV-1a, which unlike the standard hasher is stable across Rust releases
+        seed.to_le_bytes()
+            .iter()
+            .chain(relative.as_bytes())
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3))
+    });
+}
+
+/// Sorts the file entries to follow an explicit order of paths or glob patterns.
+///
+/// Files are ordered by the first entry of `order` matching their root-relative path. Files matching the
+/// same glob keep their discovery order, and unlisted files are appended in their current order.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files.
+/// * `order` - The paths or glob patterns, relative to the root directory.
+/// * `root_path` - The path to the root directory.
+/// * `relative_paths` - Whether the traversal produced relative paths.
+///
+/// # Returns
+///
+/// * `Result<()>` - An empty result, or an error for an invalid glob pattern.
+pub fn sort_by_order(
+    files: &mut [serde_json::Value],
+    order: &[String],
+    root_path: &Path,
+    relative_paths: bool,
+) -> Result<()> {
+    let patterns = order
+        .iter()
+        .map(|entry| {
+            glob::Pattern::new(entry.trim_start_matches("./")).map_err(|e| invalid_pattern("order", entry, e))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    files.sort_by_cached_key(|file| {
+        let path = file["path"].as_str().unwrap_or_default();
+        let relative = root_relative_path(path, root_path, relative_paths);
+        patterns
+            .iter()
+            .position(|pattern| pattern.matches(&relative))
+            .unwrap_or(patterns.len())
+    });
+    Ok(())
+}
+
+/// Moves the documentation files of each directory, such as `README*` and `*.md` files, before its other files.
+///
+/// Each documentation file is placed just before the first file of its directory, so the order of the
+/// directories and of the remaining files is left unchanged.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files.
+/// * `root_path` - The path to the root directory.
+/// * `relative_paths` - Whether the traversal produced relative paths.
+pub fn sort_readme_first(files: &mut [serde_json::Value], root_path: &Path, relative_paths: bool) {
+    let mut first_in_directory: HashMap<PathBuf, usize> = HashMap::new();
+    let mut keys = Vec::with_capacity(files.len());
+    for (index, file) in files.iter().enumerate() {
+        let path = file["path"].as_str().unwrap_or_default();
+        let relative = PathBuf::from(root_relative_path(path, root_path, relative_paths));
+        let directory = relative.parent().map(Path::to_path_buf).unwrap_or_default();
+        let first = *first_in_directory.entry(directory).or_insert(index);
+        keys.push(if is_doc_file(&relative) { (first, false) } else { (index, true) });
+    }
+    let mut keyed: Vec<_> = keys.into_iter().zip(files.iter().cloned()).collect();
+    keyed.sort_by_key(|(key, _)| *key);
+    for (slot, (_, file)) in files.iter_mut().zip(keyed) {
+        *slot = file;
+    }
+}
+
+/// Groups the file entries by top-level directory, keeping their order within each group.
+///
+/// Groups are ordered by their first file, and files at the root form the `.` group. Each entry gets
+/// the `group` it belongs to, and the first one of each group is marked with `group_start`, so that
+/// templates can render a header per group over the flat files list.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files, in their current order.
+/// * `root_path` - The path to the root directory.
+/// * `relative_paths` - Whether the traversal produced relative paths.
+pub fn group_by_top_level_dir(files: &mut [serde_json::Value], root_path: &Path, relative_paths: bool) {
+    let mut first_in_group: HashMap<String, usize> = HashMap::new();
+    let mut keyed: Vec<((usize, usize), String, serde_json::Value)> = files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| {
+            let path = file["path"].as_str().unwrap_or_default();
+            let relative = root_relative_path(path, root_path, relative_paths);
+            let group = match relative.split_once('/') {
+                Some((directory, _)) => directory.to_string(),
+                None => ".".to_string(),
+            };
+            let first = *first_in_group.entry(group.clone()).or_insert(index);
+            ((first, index), group, file.clone())
+        })
+        .collect();
+    keyed.sort_by_key(|(key, _, _)| *key);
+    let mut previous = None;
+    for (slot, (_, group, mut file)) in files.iter_mut().zip(keyed) {
+        file["group_start"] = json!(previous.as_ref() != Some(&group));
+        file["group"] = json!(group);
+        previous = file["group"].as_str().map(str::to_string);
+        *slot = file;
+    }
+}
+
+/// Tags the file entries with the first include group matching them, and keeps the groups together.
+///
+/// Groups are ordered like in `groups`, keeping the order of the files within each group, and the files
+/// matching no group come last, without a `group`. Each entry of a group gets its label as `group`, and
+/// the first one of each group is marked with `group_start`, like with [`group_by_top_level_dir`].
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files, in their current order.
+/// * `groups` - The labels of the groups with their include patterns, matched like `include` patterns.
+/// * `root_path` - The path to the root directory.
+/// * `relative_paths` - Whether the traversal produced relative paths.
+///
+/// # Returns
+///
+/// * `Result<()>` - An empty result, or the error of an invalid pattern.
+pub fn group_by_include_groups(
+    files: &mut [serde_json::Value],
+    groups: &[(String, Vec<String>)],
+    root_path: &Path,
+    relative_paths: bool,
+) -> Result<()> {
+    let groups = groups
+        .iter()
+        .map(|(label, patterns)| {
+            let patterns = patterns
+                .iter()
+                .map(|pattern| glob::Pattern::new(pattern).map_err(|e| invalid_pattern("include group", pattern, e)))
+                .collect::<Result<Vec<_>>>()?;
+            Ok((label, patterns))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Patterns match absolute paths, like in the traversal
+    let canonical_root_path = root_path
+        .canonicalize()
+        .unwrap_or_else(|_| root_path.to_path_buf());
+    let mut keyed: Vec<(usize, serde_json::Value)> = files
+        .iter()
+        .map(|file| {
+            let path = file["path"].as_str().unwrap_or_default();
+            let relative = root_relative_path(path, root_path, relative_paths);
+            let absolute = canonical_root_path.join(relative).to_string_lossy().to_string();
+            let group = groups
+                .iter()
+                .position(|(_, patterns)| patterns.iter().any(|pattern| pattern.matches(&absolute)))
+                .unwrap_or(groups.len());
+            (group, file.clone())
+        })
+        .collect();
+    keyed.sort_by_key(|(group, _)| *group);
+    let mut previous = None;
+    for (slot, (group, mut file)) in files.iter_mut().zip(keyed) {
+        if let Some((label, _)) = groups.get(group) {
+            file["group_start"] = json!(previous != Some(group));
+            file["group"] = json!(label);
+        }
+        previous = Some(group);
+        *slot = file;
+    }
+    Ok(())
+}
+
+/// Returns whether a file is a documentation file: a `README*` or a Markdown file.
+fn is_doc_file(path: &Path) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    name.starts_with("readme") || name.ends_with(".md")
+}
+
+/// Builds the directory tree of the given file entries only, sorted by path.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files.
+/// * `root_path` - The path to the root directory.
+/// * `relative_paths` - Whether the paths of the files are relative to the root directory.
+///
+/// # Returns
+///
+/// * `String` - The string representation of the directory tree.
+pub fn files_tree(files: &[serde_json::Value], root_path: &Path, relative_paths: bool) -> String {
+    let config = Code2PromptConfig {
+        path: root_path.to_path_buf(),
+        relative_paths,
+        ..Default::default()
+    };
+    files_tree_with_config(files, &config)
+}
+
+/// Builds the directory tree of the given file entries only, sorted by path.
+///
+/// The root is labeled like the tree of the traversal, with the same annotations and legend, taken from the
+/// files, and the entries of each directory are capped by `max_tree_entries`.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files.
+/// * `config` - The configuration, whose `path` is the root directory.
+///
+/// # Returns
+///
+/// * `String` - The string representation of the directory tree.
+pub fn files_tree_with_config(files: &[serde_json::Value], config: &Code2PromptConfig) -> String {
+    let root_path = &config.path;
+    let canonical_root_path = root_path
+        .canonicalize()
+        .unwrap_or_else(|_| root_path.to_path_buf());
+    let mut annotations = TreeAnnotations::default();
+    let mut paths: Vec<PathBuf> = files
+        .iter()
+        .map(|file| {
+            let path = file["path"].as_str().unwrap_or_default();
+            let relative_path = PathBuf::from(root_relative_path(path, root_path, config.relative_paths));
+            if config.tree_line_stats {
+                let code = file["code"].as_str().unwrap_or_default();
+                annotations
+                    .line_counts
+                    .insert(relative_path.clone(), content_line_count(code, config.no_codeblock));
+            }
+            if let Some(target) = file["link_target"].as_str() {
+                annotations.links.push((relative_path.clone(), PathBuf::from(target)));
+            }
+            if file["executable"] == true {
+                annotations.executables.push(relative_path.clone());
+            }
+            relative_path
+        })
+        .collect();
+    paths.sort();
+
+    let mut root = Tree::new(root_label(&canonical_root_path, config.stable));
+    for path in &paths {
+        let mut current_tree = &mut root;
+        for component in path.components() {
+            let component_str = component.as_os_str().to_string_lossy().to_string();
+            current_tree = if let Some(pos) = current_tree
+                .leaves
+                .iter()
+                .position(|child| child.root == component_str)
+            {
+                &mut current_tree.leaves[pos]
+            } else {
+                current_tree.leaves.push(Tree::new(component_str));
+                current_tree.leaves.last_mut().unwrap()
+            };
+        }
+    }
+    render_tree(root, &annotations, config)
+}
+
+/// Builds the URL of a file from a template containing a `{path}` placeholder.
+///
+/// # Arguments
+///
+/// * `url_template` - The URL template, e.g. `https://github.com/org/repo/blob/main/{path}`.
+/// * `relative_path` - The path of the file relative to the root directory.
+///
+/// # Returns
+///
+/// * `String` - The URL with the percent-encoded path substituted.
+pub fn file_url(url_template: &str, relative_path: &str) -> String {
+    let encoded: String = relative_path
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    url_template.replace("{path}", &encoded)
+}
+
+/// Terminal escape sequences: CSI sequences such as colors, OSC sequences such as titles and links, and
+/// two-character escapes.
+static ANSI_ESCAPE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)|\x1b[@-Z\\-_]").unwrap()
+});
+
+/// Removes the ANSI terminal escape sequences, e.g. color codes, from a text.
+///
+/// # Arguments
+///
+/// * `text` - The text, e.g. a log file or the captured output of a command.
+///
+/// # Returns
+///
+/// * `Cow<str>` - The text without escape sequences, borrowed when it had none.
+pub fn strip_ansi_escapes(text: &str) -> Cow<'_, str> {
+    ANSI_ESCAPE.replace_all(text, "")
+}
+
+/// The line endings of the file contents in the output, see [`Code2PromptConfig::line_ending`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEnding {
+    /// The line endings of the files, as they are.
+    Preserve,
+    /// Unix line endings, `\n`.
+    #[default]
+    Lf,
+    /// Windows line endings, `\r\n`.
+    Crlf,
+}
+
+/// Converts the `\r\n` line endings of a text, and the lone `\r` of classic Mac OS files, to `\n`.
+///
+/// # Arguments
+///
+/// * `text` - The text, with any line endings.
+///
+/// # Returns
+///
+/// * `Cow<str>` - The text with `\n` line endings, borrowed when it had no `\r`.
+pub fn normalize_line_endings(text: &str) -> Cow<'_, str> {
+    if text.contains('\r') {
+        text.replace("\r\n", "\n").replace('\r', "\n").into()
+    } else {
+        text.into()
+    }
+}
+
+/// Builds the info string following the opening fence of a code block, the language unless a template is set.
+///
+/// # Arguments
+///
+/// * `template` - The template of the info string, with `{{language}}` and `{{path}}` placeholders.
+/// * `language` - The language of the block, the file extension unless its language is overridden.
+/// * `relative_path` - The path of the file relative to the root directory.
+///
+/// # Returns
+///
+/// * `String` - The info string, e.g. `rs title="src/lib.rs"`.
+pub fn fence_info(template: Option<&str>, language: &str, relative_path: &str) -> String {
+    match template {
+        Some(template) => template
+            .replace("{{language}}", language)
+            .replace("{{path}}", relative_path),
+        None => language.to_string(),
+    }
+}
+
+/// Prefixes the changed lines of a file with `> `, keeping the other lines as-is.
+///
+/// # Arguments
+///
+/// * `code` - The content of the file.
+/// * `changed_lines` - The numbers of the changed lines, starting at 1.
+///
+/// # Returns
+///
+/// * `String` - The content with its changed lines marked.
+pub fn mark_changed_lines(code: &str, changed_lines: &BTreeSet<usize>) -> String {
+    code.split_inclusive('\n')
+        .enumerate()
+        .map(|(index, line)| {
+            if changed_lines.contains(&(index + 1)) {
+                format!("> {}", line)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Returns the content of a file block formatted by a traversal, without its fences and line numbers.
+///
+/// # Arguments
+///
+/// * `code` - The `code` of the file, as formatted by the traversal.
+/// * `config` - The configuration of the traversal.
+///
+/// # Returns
+///
+/// * `Cow<str>` - The content of the file, with `\n` line endings when it was unwrapped.
+pub(crate) fn block_content<'a>(code: &'a str, config: &Code2PromptConfig) -> Cow<'a, str> {
+    let line_numbers = config.line_number && !gutter_line_numbers(config);
+    if config.no_codeblock && !line_numbers {
+        return Cow::Borrowed(code);
+    }
+    let mut lines: Vec<&str> = code.lines().collect();
+    if !config.no_codeblock && lines.len() >= 2 {
+        lines = lines[1..lines.len() - 1].to_vec();
+    }
+    if line_numbers {
+        lines = lines.iter().map(|line| line.split_once(" | ").map_or(*line, |(_, line)| line)).collect();
+    }
+    Cow::Owned(lines.join("\n"))
+}
+
+/// Returns whether the line numbers are left to a gutter outside of the code blocks, with `line_numbers_outside`.
+///
+/// The code of a block is then left as-is, and the info string asks markdown renderers to show the numbers
+/// with `showLineNumbers`. Without code blocks, the numbers still prefix the lines.
+fn gutter_line_numbers(config: &Code2PromptConfig) -> bool {
+    config.line_number && config.line_numbers_outside && !config.no_codeblock
+}
+
 /// Wraps the code block with a delimiter and adds line numbers if required.
 ///
+/// The delimiter is a run of backticks longer than any run in the code, e.g. ```` when the code has a
+/// fenced block of its own, so that the code can't close the block early.
+///
 /// # Arguments
 ///
 /// * `code` - The code block to wrap.
-/// * `extension` - The file extension of the code block.
+/// * `fence` - The info string of the code block, see [`fence_info`].
 /// * `line_numbers` - Whether to add line numbers to the code.
 /// * `no_codeblock` - Whether to not wrap the code block with a delimiter.
 ///
 /// # Returns
 ///
 /// * `String` - The wrapped code block.
-fn wrap_code_block(code: &str, extension: &str, line_numbers: bool, no_codeblock: bool) -> String {
-    let delimiter = "`".repeat(3);
+fn wrap_code_block(code: &str, fence: &str, line_numbers: bool, no_codeblock: bool) -> String {
+    let longest_run = code
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let delimiter = "`".repeat(longest_run.max(2) + 1);
     let mut code_with_line_numbers = String::new();
 
     if line_numbers {
@@ -160,7 +1837,7 @@ fn wrap_code_block(code: &str, extension: &str, line_numbers: bool, no_codeblock
     } else {
         format!(
             "{}{}\n{}\n{}",
-            delimiter, extension, code_with_line_numbers, delimiter
+            delimiter, fence, code_with_line_numbers, delimiter
         )
     }
 }
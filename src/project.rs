@@ -0,0 +1,339 @@
+//! This module reads the manifest of the project, e.g. `Cargo.toml`, for an overview of the project.
+//!
+//! The supported manifests are parsed into a normalized [`ProjectMetadata`], so templates render the
+//! name, version, description and dependencies the same way whatever the ecosystem.
+
+use crate::source::{FileSource, WalkOptions};
+use anyhow::{anyhow, Context, Result};
+use colored::*;
+use glob::{MatchOptions, Pattern};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// The manifest files detected in the root directory, in order of precedence.
+pub const MANIFEST_FILES: &[&str] = &["Cargo.toml", "package.json", "pyproject.toml"];
+
+/// The files of a workspace root shared by its packages, e.g. the manifest of the workspace and its lockfile.
+pub const WORKSPACE_FILES: &[&str] = &[
+    "Cargo.toml",
+    "Cargo.lock",
+    "package.json",
+    "package-lock.json",
+    "pnpm-workspace.yaml",
+    "pnpm-lock.yaml",
+    "yarn.lock",
+];
+
+/// A dependency of the project, with its version requirement when the manifest has one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProjectDependency {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+/// The overview of a project, normalized from its manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ProjectMetadata {
+    /// The file name of the manifest, e.g. `Cargo.toml`.
+    pub manifest: String,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    /// The runtime dependencies, without the development and build ones.
+    pub dependencies: Vec<ProjectDependency>,
+}
+
+/// Detects the manifest of the project in the root directory and parses it
+///
+/// The manifests of [`MANIFEST_FILES`] are tried in order. A manifest that can't be parsed is reported
+/// with a warning, and the next one is tried.
+///
+/// # Arguments
+///
+/// * `source` - The source of the files.
+/// * `root_path` - The path to the root directory.
+///
+/// # Returns
+///
+/// * `Option<ProjectMetadata>` - The overview of the project, or `None` without a parseable manifest.
+pub fn detect_project(source: &dyn FileSource, root_path: &Path) -> Option<ProjectMetadata> {
+    detect_project_with_options(source, root_path, false)
+}
+
+/// Detects the manifest of the project in the root directory and parses it
+///
+/// # Arguments
+///
+/// * `source` - The source of the files.
+/// * `root_path` - The path to the root directory.
+/// * `quiet` - Whether to skip the warning for a manifest that can't be parsed.
+///
+/// # Returns
+///
+/// * `Option<ProjectMetadata>` - The overview of the project, or `None` without a parseable manifest.
+pub fn detect_project_with_options(source: &dyn FileSource, root_path: &Path, quiet: bool) -> Option<ProjectMetadata> {
+    MANIFEST_FILES.iter().find_map(|file_name| {
+        let content = source.read(&root_path.join(file_name)).ok()?;
+        let parsed = String::from_utf8(content)
+            .context("Invalid UTF-8")
+            .and_then(|content| parse_manifest(file_name, &content));
+        match parsed {
+            Ok(project) => Some(project),
+            Err(e) => {
+                if !quiet {
+                    eprintln!(
+                        "{}{}{} {}",
+                        "[".bold().white(),
+                        "!".bold().yellow(),
+                        "]".bold().white(),
+                        format!("Failed to parse {}: {:#}", file_name, e).yellow()
+                    );
+                }
+                None
+            }
+        }
+    })
+}
+
+/// Parses a manifest into the overview of the project
+///
+/// # Arguments
+///
+/// * `file_name` - The file name of the manifest, one of [`MANIFEST_FILES`].
+/// * `content` - The content of the manifest.
+///
+/// # Returns
+///
+/// * `Result<ProjectMetadata>` - The overview of the project, or an error for an invalid or unsupported manifest.
+pub fn parse_manifest(file_name: &str, content: &str) -> Result<ProjectMetadata> {
+    let mut project = match file_name {
+        "Cargo.toml" => parse_cargo_toml(content)?,
+        "package.json" => parse_package_json(content)?,
+        "pyproject.toml" => parse_pyproject_toml(content)?,
+        _ => return Err(anyhow::anyhow!("Unsupported manifest: {}", file_name)),
+    };
+    project.manifest = file_name.to_string();
+    Ok(project)
+}
+
+/// Resolves the directory of a member package of a Cargo or npm workspace, by its name
+///
+/// The members are listed by the `[workspace]` table of the root `Cargo.toml`, or by the `workspaces` of the
+/// root `package.json`, as directories or glob patterns like `crates/*`. Each member is named by its own manifest,
+/// and the root manifest of a workspace that is also a package, e.g. with a `[package]` table, names the root.
+///
+/// # Arguments
+///
+/// * `source` - The source of the files.
+/// * `root_path` - The path to the root directory of the workspace.
+/// * `package` - The name of the package.
+///
+/// # Returns
+///
+/// * `Result<PathBuf>` - The directory of the package, or an error outside of a workspace or for an unknown package.
+pub fn workspace_package_path(source: &dyn FileSource, root_path: &Path, package: &str) -> Result<PathBuf> {
+    let mut workspaces = Vec::new();
+    for file_name in ["Cargo.toml", "package.json"] {
+        let Ok(content) = source.read(&root_path.join(file_name)) else {
+            continue;
+        };
+        let content = String::from_utf8(content).context("Invalid UTF-8")?;
+        let (members, exclude) = workspace_members(file_name, &content)
+            .with_context(|| format!("Failed to parse the workspace of {}", file_name))?;
+        let pattern = |member: &String| {
+            Pattern::new(member.trim_start_matches("./").trim_end_matches('/'))
+                .with_context(|| format!("Invalid workspace member: {}", member))
+        };
+        let members = members.iter().map(pattern).collect::<Result<Vec<_>>>()?;
+        let exclude = exclude.iter().map(pattern).collect::<Result<Vec<_>>>()?;
+        if !members.is_empty() {
+            workspaces.push((file_name, members, exclude));
+        }
+    }
+    if workspaces.is_empty() {
+        return Err(anyhow!("{} isn't the root of a Cargo or npm workspace", root_path.display()));
+    }
+
+    // A `*` of the members matches a single directory
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+    let walk_options = WalkOptions {
+        sorted: true,
+        ..Default::default()
+    };
+    for path in source.walk_with_options(root_path, &walk_options).filter_map(Result::ok) {
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some((_, members, exclude)) = workspaces.iter().find(|(manifest, _, _)| *manifest == file_name) else {
+            continue;
+        };
+        let Some(directory) = path.parent().and_then(|parent| parent.strip_prefix(root_path).ok()) else {
+            continue;
+        };
+        let is_member = directory.as_os_str().is_empty()
+            || (members.iter().any(|member| member.matches_path_with(directory, options))
+                && !exclude.iter().any(|excluded| excluded.matches_path_with(directory, options)));
+        if !is_member {
+            continue;
+        }
+        let name = source
+            .read(&path)
+            .ok()
+            .and_then(|content| String::from_utf8(content).ok())
+            .and_then(|content| parse_manifest(file_name, &content).ok())
+            .and_then(|project| project.name);
+        if name.as_deref() == Some(package) {
+            return Ok(root_path.join(directory));
+        }
+    }
+    Err(anyhow!("No package named `{}` in the workspace of {}", package, root_path.display()))
+}
+
+/// Returns the member and the excluded patterns of the workspace of a root manifest.
+fn workspace_members(file_name: &str, content: &str) -> Result<(Vec<String>, Vec<String>)> {
+    if file_name == "Cargo.toml" {
+        let manifest: toml::Table = toml::from_str(content).context("Invalid TOML")?;
+        let list = |key: &str| -> Vec<String> {
+            manifest
+                .get("workspace")
+                .and_then(|workspace| workspace.get(key)?.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|member| member.as_str().map(str::to_string))
+                .collect()
+        };
+        return Ok((list("members"), list("exclude")));
+    }
+    let manifest: serde_json::Value = serde_json::from_str(content).context("Invalid JSON")?;
+    // Either an array, or an object with the array as `packages`
+    let members = manifest
+        .get("workspaces")
+        .and_then(|workspaces| workspaces.as_array().or_else(|| workspaces.get("packages")?.as_array()))
+        .into_iter()
+        .flatten()
+        .filter_map(|member| member.as_str().map(str::to_string))
+        .collect();
+    Ok((members, Vec::new()))
+}
+
+/// Parses a `Cargo.toml`, whose dependencies are either a version or a table with a `version`.
+fn parse_cargo_toml(content: &str) -> Result<ProjectMetadata> {
+    let manifest: toml::Table = toml::from_str(content).context("Invalid TOML")?;
+    let package = manifest.get("package");
+    let field = |key: &str| package?.get(key)?.as_str().map(str::to_string);
+    let dependencies = manifest
+        .get("dependencies")
+        .and_then(toml::Value::as_table)
+        .map(|dependencies| {
+            dependencies
+                .iter()
+                .map(|(name, spec)| ProjectDependency {
+                    name: name.clone(),
+                    version: spec
+                        .as_str()
+                        .or_else(|| spec.get("version")?.as_str())
+                        .map(str::to_string),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(ProjectMetadata {
+        name: field("name"),
+        version: field("version"),
+        description: field("description"),
+        dependencies,
+        ..Default::default()
+    })
+}
+
+/// Parses a `package.json`, whose dependencies map names to version ranges.
+fn parse_package_json(content: &str) -> Result<ProjectMetadata> {
+    let manifest: serde_json::Value = serde_json::from_str(content).context("Invalid JSON")?;
+    let field = |key: &str| manifest.get(key)?.as_str().map(str::to_string);
+    let dependencies = manifest
+        .get("dependencies")
+        .and_then(serde_json::Value::as_object)
+        .map(|dependencies| {
+            dependencies
+                .iter()
+                .map(|(name, version)| ProjectDependency {
+                    name: name.clone(),
+                    version: version.as_str().map(str::to_string),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(ProjectMetadata {
+        name: field("name"),
+        version: field("version"),
+        description: field("description"),
+        dependencies,
+        ..Default::default()
+    })
+}
+
+/// Parses a `pyproject.toml`, from its standard `[project]` table or else its `[tool.poetry]` one.
+fn parse_pyproject_toml(content: &str) -> Result<ProjectMetadata> {
+    let manifest: toml::Table = toml::from_str(content).context("Invalid TOML")?;
+    if let Some(project) = manifest.get("project") {
+        let field = |key: &str| project.get(key)?.as_str().map(str::to_string);
+        // Requirements like `requests>=2.31; python_version >= "3.8"`
+        let dependencies = project
+            .get("dependencies")
+            .and_then(toml::Value::as_array)
+            .map(|dependencies| {
+                dependencies
+                    .iter()
+                    .filter_map(toml::Value::as_str)
+                    .map(|requirement| {
+                        let requirement = requirement.split(';').next().unwrap_or_default().trim();
+                        let end = requirement
+                            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | '.')))
+                            .unwrap_or(requirement.len());
+                        let version = requirement[end..].trim();
+                        ProjectDependency {
+                            name: requirement[..end].to_string(),
+                            version: (!version.is_empty()).then(|| version.to_string()),
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        return Ok(ProjectMetadata {
+            name: field("name"),
+            version: field("version"),
+            description: field("description"),
+            dependencies,
+            ..Default::default()
+        });
+    }
+
+    let poetry = manifest.get("tool").and_then(|tool| tool.get("poetry"));
+    let field = |key: &str| poetry?.get(key)?.as_str().map(str::to_string);
+    let dependencies = poetry
+        .and_then(|poetry| poetry.get("dependencies")?.as_table())
+        .map(|dependencies| {
+            dependencies
+                .iter()
+                .filter(|(name, _)| name.as_str() != "python")
+                .map(|(name, spec)| ProjectDependency {
+                    name: name.clone(),
+                    version: spec
+                        .as_str()
+                        .or_else(|| spec.get("version")?.as_str())
+                        .map(str::to_string),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(ProjectMetadata {
+        name: field("name"),
+        version: field("version"),
+        description: field("description"),
+        dependencies,
+        ..Default::default()
+    })
+}
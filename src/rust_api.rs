@@ -0,0 +1,98 @@
+//! This module reduces Rust source files to their public API surface, for compact overviews of crates.
+
+use quote::quote;
+use syn::{Fields, ImplItem, Item, TraitItem, Visibility};
+
+/// Returns the public API surface of a Rust source file
+///
+/// Only the `pub` items are kept, with their doc comments and other attributes: function signatures without
+/// their bodies, structs without their private fields, enums, traits without default method bodies, type
+/// aliases, constants, statics, re-exports, `#[macro_export]` macros and inline modules, reduced recursively.
+/// `impl` blocks keep the signatures of their `pub` methods, or of all their methods for trait impls.
+///
+/// # Arguments
+///
+/// * `code` - The source code of the file.
+///
+/// # Returns
+///
+/// * `syn::Result<String>` - The formatted public items, or the error of a file that doesn't parse.
+pub fn public_api(code: &str) -> syn::Result<String> {
+    let mut file = syn::parse_file(code)?;
+    file.shebang = None;
+    file.attrs.retain(|attr| attr.path().is_ident("doc"));
+    file.items = public_items(file.items);
+    Ok(prettyplease::unparse(&file))
+}
+
+/// Keeps the public items, reduced to their signatures.
+fn public_items(items: Vec<Item>) -> Vec<Item> {
+    items.into_iter().filter_map(public_item).collect()
+}
+
+/// Reduces an item to its public surface, or returns `None` for a private item.
+fn public_item(item: Item) -> Option<Item> {
+    match item {
+        Item::Fn(item) if is_public(&item.vis) => {
+            let (attrs, vis, sig) = (&item.attrs, &item.vis, &item.sig);
+            Some(Item::Verbatim(quote!(#(#attrs)* #vis #sig;)))
+        }
+        Item::Struct(mut item) if is_public(&item.vis) => {
+            if let Fields::Named(fields) = &mut item.fields {
+                fields.named = std::mem::take(&mut fields.named)
+                    .into_iter()
+                    .filter(|field| is_public(&field.vis))
+                    .collect();
+            }
+            Some(Item::Struct(item))
+        }
+        Item::Trait(mut item) if is_public(&item.vis) => {
+            for trait_item in item.items.iter_mut() {
+                if let TraitItem::Fn(method) = trait_item {
+                    method.default = None;
+                    method.semi_token = Some(Default::default());
+                }
+            }
+            Some(Item::Trait(item))
+        }
+        Item::Mod(mut item) if is_public(&item.vis) => {
+            if let Some((brace, items)) = item.content.take() {
+                item.content = Some((brace, public_items(items)));
+            }
+            Some(Item::Mod(item))
+        }
+        Item::Impl(mut item) => {
+            let is_trait_impl = item.trait_.is_some();
+            item.items = std::mem::take(&mut item.items)
+                .into_iter()
+                .filter_map(|impl_item| match impl_item {
+                    ImplItem::Fn(method) if is_trait_impl || is_public(&method.vis) => {
+                        let (attrs, vis, sig) = (&method.attrs, &method.vis, &method.sig);
+                        Some(ImplItem::Verbatim(quote!(#(#attrs)* #vis #sig;)))
+                    }
+                    ImplItem::Const(constant) if is_trait_impl || is_public(&constant.vis) => {
+                        Some(ImplItem::Const(constant))
+                    }
+                    ImplItem::Type(alias) if is_trait_impl || is_public(&alias.vis) => Some(ImplItem::Type(alias)),
+                    _ => None,
+                })
+                .collect();
+            (is_trait_impl || !item.items.is_empty()).then_some(Item::Impl(item))
+        }
+        Item::Enum(item) if is_public(&item.vis) => Some(Item::Enum(item)),
+        Item::Type(item) if is_public(&item.vis) => Some(Item::Type(item)),
+        Item::Const(item) if is_public(&item.vis) => Some(Item::Const(item)),
+        Item::Static(item) if is_public(&item.vis) => Some(Item::Static(item)),
+        Item::Use(item) if is_public(&item.vis) => Some(Item::Use(item)),
+        Item::Union(item) if is_public(&item.vis) => Some(Item::Union(item)),
+        Item::Macro(item) if item.attrs.iter().any(|attr| attr.path().is_ident("macro_export")) => {
+            Some(Item::Macro(item))
+        }
+        _ => None,
+    }
+}
+
+/// Returns whether a visibility is public outside of the crate, i.e. plain `pub`.
+fn is_public(vis: &Visibility) -> bool {
+    matches!(vis, Visibility::Public(_))
+}
@@ -0,0 +1,168 @@
+//! This module collapses groups of near-identical files, e.g. migrations, fixtures or generated clients,
+//! into one representative, as a heuristic token-saver.
+
+use crate::path::{block_content, root_relative_path};
+use crate::Code2PromptConfig;
+use serde_json::json;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+/// The similarity from which files are collapsed, unless another threshold is set.
+pub const DEFAULT_SIMILARITY_THRESHOLD: f64 = 0.8;
+
+/// The number of consecutive words of the shingles compared between files.
+const SHINGLE_WORDS: usize = 3;
+
+/// Returns the set of shingles of a content: its runs of consecutive words, hashed.
+fn shingles(content: &str) -> HashSet<u64> {
+    use std::hash::{Hash, Hasher};
+
+    let words: Vec<&str> = content.split_whitespace().collect();
+    words
+        .windows(SHINGLE_WORDS.min(words.len()).max(1))
+        .map(|window| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            window.hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect()
+}
+
+/// Returns the Jaccard similarity of two contents, from 0 for unrelated contents to 1 for identical ones.
+///
+/// The contents are compared by their shingles, so that the similarity only drops by a few shingles for
+/// each word that differs.
+///
+/// # Arguments
+///
+/// * `a` - The first content.
+/// * `b` - The second content.
+///
+/// # Returns
+///
+/// * `f64` - The similarity of the contents.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    jaccard(&shingles(a), &shingles(b))
+}
+
+/// Returns the Jaccard index of two sets of shingles, 1 for two empty sets.
+fn jaccard(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// Collapses the groups of similar files into their first file, which lists the others
+///
+/// Files are compared to the representative of each group with the same extension, in order, and join the
+/// first one they are similar enough to; the others start a group of their own. The representative of a group
+/// with other files keeps its content and gets the root-relative paths of the others as `similar_files`, along
+/// with a `similar_summary` like `2 similar files: b.rs, c.rs`. Files without content, such as deleted
+/// ones, and base64 files are kept as-is.
+///
+/// The `code` of the files is compared as-is, e.g. the bare content of a traversal with `no_codeblock`; see
+/// [`collapse_similar_files_with_options`] for the code blocks of a traversal.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files.
+/// * `threshold` - The similarity from which files are collapsed, between 0 and 1.
+/// * `root_path` - The path to the root directory.
+/// * `relative_paths` - Whether the traversal produced relative paths.
+///
+/// # Returns
+///
+/// * `Vec<serde_json::Value>` - The files, without the collapsed ones.
+pub fn collapse_similar_files(
+    files: Vec<serde_json::Value>,
+    threshold: f64,
+    root_path: &Path,
+    relative_paths: bool,
+) -> Vec<serde_json::Value> {
+    collapse(files, threshold, root_path, relative_paths, &|code| Cow::Borrowed(code))
+}
+
+/// Collapses the groups of similar files like [`collapse_similar_files`], comparing the contents of the files
+/// without the fences and line numbers the traversal formatted their code blocks with.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files.
+/// * `threshold` - The similarity from which files are collapsed, between 0 and 1.
+/// * `config` - The configuration of the traversal of the files.
+///
+/// # Returns
+///
+/// * `Vec<serde_json::Value>` - The files, without the collapsed ones.
+pub fn collapse_similar_files_with_options(
+    files: Vec<serde_json::Value>,
+    threshold: f64,
+    config: &Code2PromptConfig,
+) -> Vec<serde_json::Value> {
+    collapse(files, threshold, &config.path, config.relative_paths, &|code| block_content(code, config))
+}
+
+/// Collapses the groups of similar files, comparing the contents extracted from their code.
+///
+/// Two sets of shingles are at most as similar as the ratio of their sizes, so each file is only compared to
+/// the representatives whose number of shingles is within the threshold of its own.
+fn collapse(
+    files: Vec<serde_json::Value>,
+    threshold: f64,
+    root_path: &Path,
+    relative_paths: bool,
+    content: &dyn Fn(&str) -> Cow<'_, str>,
+) -> Vec<serde_json::Value> {
+    // The index of the representative of each group in the kept files, with its shingles and the similar files
+    let mut groups: Vec<(usize, HashSet<u64>, Vec<String>)> = Vec::new();
+    // The groups of each extension, by the number of shingles of their representative
+    let mut by_size: HashMap<String, BTreeMap<usize, Vec<usize>>> = HashMap::new();
+    let mut kept = Vec::new();
+    for file in files {
+        let code = file["code"].as_str().unwrap_or_default();
+        if code.is_empty() || file.get("encoding").is_some() {
+            kept.push(file);
+            continue;
+        }
+        let extension = file["extension"].as_str().unwrap_or_default().to_string();
+        let file_shingles = shingles(&content(code));
+        let size = file_shingles.len();
+        let sizes = by_size.entry(extension).or_default();
+        let min_size = (size as f64 * threshold).floor() as usize;
+        let max_size = if threshold > 0.0 { (size as f64 / threshold).ceil() as usize } else { usize::MAX };
+        let mut candidates: Vec<usize> = sizes.range(min_size..=max_size).flat_map(|(_, groups)| groups).copied().collect();
+        candidates.sort_unstable();
+        let group = candidates
+            .into_iter()
+            .find(|&group| jaccard(&groups[group].1, &file_shingles) >= threshold);
+        match group {
+            Some(group) => {
+                let path = file["path"].as_str().unwrap_or_default();
+                groups[group].2.push(root_relative_path(path, root_path, relative_paths));
+            }
+            None => {
+                sizes.entry(size).or_default().push(groups.len());
+                groups.push((kept.len(), file_shingles, Vec::new()));
+                kept.push(file);
+            }
+        }
+    }
+
+    for (index, _, similar) in groups {
+        if similar.is_empty() {
+            continue;
+        }
+        kept[index]["similar_summary"] = json!(similar_summary(&similar));
+        kept[index]["similar_files"] = json!(similar);
+    }
+    kept
+}
+
+/// Returns the summary of the files collapsed into a representative, e.g. `2 similar files: b.rs, c.rs`.
+pub fn similar_summary(similar: &[String]) -> String {
+    let plural = if similar.len() == 1 { "file" } else { "files" };
+    format!("{} similar {}: {}", similar.len(), plural, similar.join(", "))
+}
@@ -0,0 +1,468 @@
+//! This module abstracts the filesystem access used by the traversal, so prompts can be generated
+//! from the real filesystem as well as from in-memory or other virtual trees.
+
+use anyhow::{Context, Result};
+use git2::{ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::WalkBuilder;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The name of the ignore files whose gitignore-style rules apply to the subtree of their directory.
+///
+/// Like `.gitignore` files, nested ones are merged with the ones of their ancestors and take precedence,
+/// so a subdirectory can re-include files excluded higher up with `!pattern`.
+pub const IGNORE_FILE_NAME: &str = ".code2promptignore";
+
+/// The metadata of an entry of a file source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileMetadata {
+    pub is_file: bool,
+    pub is_dir: bool,
+    pub len: u64,
+    /// The last modification time, when the source tracks it.
+    pub modified: Option<SystemTime>,
+    /// The Unix permission bits, e.g. `0o755`, when the source tracks them.
+    pub mode: Option<u32>,
+}
+
+/// A source of files the traversal can operate on.
+pub trait FileSource {
+    /// Lists the root and every entry below it, in traversal order.
+    ///
+    /// The yielded paths are prefixed with `root`, so that they can be stripped to obtain relative paths.
+    fn walk<'a>(&'a self, root: &Path) -> Box<dyn Iterator<Item = Result<PathBuf>> + 'a>;
+
+    /// Reads the content of a file.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Returns the metadata of an entry.
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata>;
+
+    /// Returns the canonical form of a path, against which the include and exclude patterns are matched.
+    ///
+    /// Virtual sources have no links to resolve, so the path is returned as-is by default.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+
+    /// Lists the entries like [`FileSource::walk`], with options such as following symbolic links.
+    ///
+    /// Sources that don't support the options list the same entries as `walk` by default.
+    fn walk_with_options<'a>(
+        &'a self,
+        root: &Path,
+        _options: &WalkOptions,
+    ) -> Box<dyn Iterator<Item = Result<PathBuf>> + 'a> {
+        self.walk(root)
+    }
+
+    /// Returns the target of an entry that is a symbolic link, or `None` for other entries.
+    fn link_target(&self, _path: &Path) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// The options of a walk beyond the default `.gitignore` and [`IGNORE_FILE_NAME`] support.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WalkOptions {
+    /// Whether to descend into the directories behind symbolic links.
+    pub follow_symlinks: bool,
+    /// The names of additional gitignore-style files, such as `.dockerignore`, each applying from its directory.
+    pub extra_ignore_files: Vec<String>,
+    /// Whether to list the entries of each directory by name, rather than in the order the filesystem returns them.
+    pub sorted: bool,
+}
+
+/// Returns whether a path below `root` is left out by the ignore files of a walk with these options, e.g. a
+/// file that changed since the walk.
+///
+/// The ignore files of the directories from the path up to `root` are matched like in
+/// [`FsSource::walk_with_options`]: deeper ignore files take precedence, and within a directory
+/// [`IGNORE_FILE_NAME`] takes precedence over the additional ignore files, which take precedence over
+/// `.gitignore`, only applied inside a git repository.
+pub fn is_ignored(root: &Path, path: &Path, options: &WalkOptions) -> bool {
+    let in_git_repository = root.ancestors().any(|dir| dir.join(".git").exists());
+    let mut names = vec![IGNORE_FILE_NAME];
+    names.extend(options.extra_ignore_files.iter().rev().map(String::as_str));
+    if in_git_repository {
+        names.push(".gitignore");
+    }
+    let is_dir = path.is_dir();
+    for dir in path.ancestors().skip(1).take_while(|dir| dir.starts_with(root)) {
+        for name in &names {
+            let ignore_file = dir.join(name);
+            if !ignore_file.is_file() {
+                continue;
+            }
+            let (gitignore, _) = Gitignore::new(&ignore_file);
+            let matched = gitignore.matched_path_or_any_parents(path, is_dir);
+            if matched.is_ignore() {
+                return true;
+            }
+            if matched.is_whitelist() {
+                return false;
+            }
+        }
+    }
+    false
+}
+
+/// The real filesystem, walked with `.gitignore` and [`IGNORE_FILE_NAME`] support.
+#[derive(Debug, Clone, Default)]
+pub struct FsSource;
+
+impl FileSource for FsSource {
+    fn walk<'a>(&'a self, root: &Path) -> Box<dyn Iterator<Item = Result<PathBuf>> + 'a> {
+        self.walk_with_options(root, &WalkOptions::default())
+    }
+
+    fn walk_with_options<'a>(
+        &'a self,
+        root: &Path,
+        options: &WalkOptions,
+    ) -> Box<dyn Iterator<Item = Result<PathBuf>> + 'a> {
+        let mut builder = WalkBuilder::new(root);
+        builder.git_ignore(true).follow_links(options.follow_symlinks);
+        // Custom ignore files added last take precedence, so code2prompt's own rules win
+        for name in &options.extra_ignore_files {
+            builder.add_custom_ignore_filename(name);
+        }
+        builder.add_custom_ignore_filename(IGNORE_FILE_NAME);
+        if options.sorted {
+            builder.sort_by_file_name(|a, b| a.cmp(b));
+        }
+        Box::new(builder.build().map(|entry| Ok(entry?.into_path())))
+    }
+
+    fn link_target(&self, path: &Path) -> Option<PathBuf> {
+        std::fs::read_link(path).ok()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(FileMetadata {
+            is_file: metadata.is_file(),
+            is_dir: metadata.is_dir(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            mode: file_mode(&metadata),
+        })
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        std::fs::canonicalize(path)
+    }
+}
+
+/// Returns the Unix permission bits of a file, or `None` on platforms without them.
+#[cfg(unix)]
+fn file_mode(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+/// Returns the Unix permission bits of a file, or `None` on platforms without them.
+#[cfg(not(unix))]
+fn file_mode(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// An in-memory tree of files, useful for tests and for embedding.
+///
+/// # Examples
+///
+/// ```
+/// use code2prompt::source::{FileSource, MemorySource};
+/// use std::path::Path;
+///
+/// let source = MemorySource::new("/project").with_file("src/main.rs", "fn main() {}");
+/// assert_eq!(source.read(Path::new("/project/src/main.rs")).unwrap(), b"fn main() {}");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+    root: PathBuf,
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl MemorySource {
+    /// Creates an empty tree whose entries live below `root`.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        MemorySource {
+            root: root.into(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a file at a path relative to the root.
+    pub fn with_file<P: AsRef<Path>, C: Into<Vec<u8>>>(mut self, path: P, content: C) -> Self {
+        self.files.insert(path.as_ref().to_path_buf(), content.into());
+        self
+    }
+
+    /// Returns the path relative to the root, if it lies below it.
+    fn relative<'p>(&self, path: &'p Path) -> Option<&'p Path> {
+        path.strip_prefix(&self.root).ok()
+    }
+
+}
+
+impl FileSource for MemorySource {
+    fn walk<'a>(&'a self, root: &Path) -> Box<dyn Iterator<Item = Result<PathBuf>> + 'a> {
+        walk_files(&self.root, self.files.keys(), root)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.relative(path)
+            .and_then(|relative| self.files.get(relative))
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.display().to_string()))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let relative = self
+            .relative(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.display().to_string()))?;
+        if let Some(content) = self.files.get(relative) {
+            return Ok(FileMetadata {
+                is_file: true,
+                is_dir: false,
+                len: content.len() as u64,
+                modified: None,
+                mode: None,
+            });
+        }
+        if relative.as_os_str().is_empty() || directories(self.files.keys()).contains(relative) {
+            return Ok(FileMetadata {
+                is_file: false,
+                is_dir: true,
+                len: 0,
+                modified: None,
+                mode: None,
+            });
+        }
+        Err(io::Error::new(io::ErrorKind::NotFound, path.display().to_string()))
+    }
+}
+
+/// Returns every directory containing the given files, relative to the root.
+fn directories<'a>(files: impl Iterator<Item = &'a PathBuf>) -> BTreeSet<PathBuf> {
+    files
+        .flat_map(|file| file.ancestors().skip(1))
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+/// Walks the files of an in-memory tree: `root` first, then every directory and file below it, sorted.
+fn walk_files<'a>(
+    source_root: &'a Path,
+    files: impl Iterator<Item = &'a PathBuf> + Clone,
+    root: &Path,
+) -> Box<dyn Iterator<Item = Result<PathBuf>> + 'a> {
+    let prefix = match root.strip_prefix(source_root) {
+        Ok(prefix) => prefix.to_path_buf(),
+        Err(_) => return Box::new(std::iter::empty()),
+    };
+    let mut entries: BTreeSet<PathBuf> = directories(files.clone());
+    entries.extend(files.cloned());
+
+    let below = entries
+        .into_iter()
+        .filter(move |entry| entry.starts_with(&prefix) && *entry != prefix)
+        .map(move |entry| Ok(source_root.join(entry)));
+    Box::new(std::iter::once(Ok(root.to_path_buf())).chain(below))
+}
+
+/// The tree of a git reference, read from the object store without a working copy.
+///
+/// This works on bare clones too. Like the filesystem walk, hidden entries and the paths ignored by the
+/// `.gitignore` and [`IGNORE_FILE_NAME`] files of the tree are skipped.
+pub struct GitTreeSource {
+    repo: Repository,
+    root: PathBuf,
+    files: BTreeMap<PathBuf, Oid>,
+}
+
+impl GitTreeSource {
+    /// Reads the tree of a reference of the repository containing `path`.
+    ///
+    /// Only the part of the tree below `path` is exposed, so a subdirectory of a working copy selects
+    /// the same subdirectory of the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path inside the repository, which becomes the root of the source.
+    /// * `git_ref` - The tree-ish to read, e.g. a branch, a tag or a commit SHA.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<GitTreeSource>` - The source, or an error if the reference can't be resolved.
+    pub fn new(path: &Path, git_ref: &str) -> Result<Self> {
+        Self::with_ignore_files(path, git_ref, &[])
+    }
+
+    /// Reads the tree of a reference like [`GitTreeSource::new`], also skipping the paths ignored by
+    /// additional gitignore-style files of the tree, such as `.dockerignore`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path inside the repository, which becomes the root of the source.
+    /// * `git_ref` - The tree-ish to read, e.g. a branch, a tag or a commit SHA.
+    /// * `extra_ignore_files` - The names of the additional ignore files, each applying from its directory.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<GitTreeSource>` - The source, or an error if the reference can't be resolved.
+    pub fn with_ignore_files(path: &Path, git_ref: &str, extra_ignore_files: &[String]) -> Result<Self> {
+        let root = std::fs::canonicalize(path)?;
+        let repo = Repository::discover(&root).context("Failed to discover repository")?;
+        let scope = match repo.workdir() {
+            Some(workdir) => root
+                .strip_prefix(workdir.canonicalize()?)
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            None => PathBuf::new(),
+        };
+
+        let mut files = BTreeMap::new();
+        {
+            let tree = repo
+                .revparse_single(git_ref)
+                .with_context(|| format!("Failed to resolve reference: {}", git_ref))?
+                .peel_to_tree()
+                .context("Failed to peel to tree")?;
+            let tree = if scope.as_os_str().is_empty() {
+                tree
+            } else {
+                repo.find_tree(tree.get_path(&scope)?.id())?
+            };
+            tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+                if entry.kind() == Some(ObjectType::Blob) {
+                    if let Some(name) = entry.name() {
+                        files.insert(Path::new(dir).join(name), entry.id());
+                    }
+                }
+                TreeWalkResult::Ok
+            })?;
+        }
+
+        let mut source = GitTreeSource { repo, root, files };
+        source.remove_ignored(extra_ignore_files)?;
+        Ok(source)
+    }
+
+    /// Removes the hidden entries and the entries ignored by the `.gitignore`, [`IGNORE_FILE_NAME`] and additional ignore files of the tree.
+    fn remove_ignored(&mut self, extra_ignore_files: &[String]) -> Result<()> {
+        let mut gitignores: Vec<(PathBuf, Gitignore)> = Vec::new();
+        let is_ignore_file = |name: &std::ffi::OsStr| {
+            name == ".gitignore" || name == IGNORE_FILE_NAME || extra_ignore_files.iter().any(|extra| name == extra.as_str())
+        };
+        for (path, oid) in &self.files {
+            if path.file_name().is_some_and(is_ignore_file) {
+                let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+                let blob = self.repo.find_blob(*oid)?;
+                let mut builder = GitignoreBuilder::new(self.root.join(&dir));
+                for line in String::from_utf8_lossy(blob.content()).lines() {
+                    builder.add_line(None, line)?;
+                }
+                gitignores.push((dir, builder.build()?));
+            }
+        }
+        // Deeper ignore files take precedence over the ones of their parents. The sort is stable, so within
+        // a directory the rules of code2prompt, listed before `.gitignore`, take precedence over the ones of git
+        gitignores.sort_by_key(|(dir, _)| std::cmp::Reverse(dir.components().count()));
+
+        self.files.retain(|path, _| {
+            let hidden = path
+                .components()
+                .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+            if hidden {
+                return false;
+            }
+            for (dir, gitignore) in &gitignores {
+                if !path.starts_with(dir) {
+                    continue;
+                }
+                let matched = gitignore.matched_path_or_any_parents(self.root.join(path), false);
+                if matched.is_ignore() {
+                    return false;
+                }
+                if matched.is_whitelist() {
+                    return true;
+                }
+            }
+            true
+        });
+        Ok(())
+    }
+
+    /// Returns the path relative to the root, if it lies below it.
+    fn relative<'p>(&self, path: &'p Path) -> Option<&'p Path> {
+        path.strip_prefix(&self.root).ok()
+    }
+
+}
+
+impl FileSource for GitTreeSource {
+    fn walk<'a>(&'a self, root: &Path) -> Box<dyn Iterator<Item = Result<PathBuf>> + 'a> {
+        walk_files(&self.root, self.files.keys(), root)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let oid = self
+            .relative(path)
+            .and_then(|relative| self.files.get(relative))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.display().to_string()))?;
+        let blob = self
+            .repo
+            .find_blob(*oid)
+            .map_err(io::Error::other)?;
+        Ok(blob.content().to_vec())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let relative = self
+            .relative(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.display().to_string()))?;
+        if let Some(oid) = self.files.get(relative) {
+            let len = self
+                .repo
+                .find_blob(*oid)
+                .map(|blob| blob.size() as u64)
+                .unwrap_or_default();
+            return Ok(FileMetadata {
+                is_file: true,
+                is_dir: false,
+                len,
+                modified: None,
+                mode: None,
+            });
+        }
+        if relative.as_os_str().is_empty() || directories(self.files.keys()).contains(relative) {
+            return Ok(FileMetadata {
+                is_file: false,
+                is_dir: true,
+                len: 0,
+                modified: None,
+                mode: None,
+            });
+        }
+        Err(io::Error::new(io::ErrorKind::NotFound, path.display().to_string()))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        // Paths of the tree don't exist on disk, only the root does
+        if path.starts_with(&self.root) {
+            Ok(path.to_path_buf())
+        } else {
+            std::fs::canonicalize(path)
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! This module loads prompt specs: portable files bundling the options of a prompt, e.g. its template,
+//! patterns, encoding and variables, so that a team can version and share them.
+
+use crate::{Code2PromptConfig, Code2PromptError};
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Loads a prompt spec, a TOML, YAML or JSON file with the options of [`Code2PromptConfig`]
+///
+/// The format is detected from the extension: `.toml`, `.yaml` or `.yml`, and `.json`. The template is either
+/// inlined as `template_content` or referenced as `template`, relative to the directory of the spec.
+///
+/// # Arguments
+///
+/// * `path` - The path of the spec.
+///
+/// # Returns
+///
+/// * `Result<Code2PromptConfig>` - The options of the spec, or an error for an unreadable or invalid spec.
+pub fn load_spec(path: &Path) -> Result<Code2PromptConfig> {
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read spec: {}", path.display()))?;
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    let mut config: Code2PromptConfig = match extension.to_ascii_lowercase().as_str() {
+        "toml" => toml::from_str(&content).map_err(anyhow::Error::from),
+        "yaml" | "yml" => serde_yaml::from_str(&content).map_err(anyhow::Error::from),
+        "json" => serde_json::from_str(&content).map_err(anyhow::Error::from),
+        _ => Err(anyhow::anyhow!("Unsupported spec format, expected .toml, .yaml or .json")),
+    }
+    .with_context(|| format!("Invalid spec: {}", path.display()))?;
+
+    if config.allow_commands {
+        return Err(Code2PromptError::InvalidOptions(format!(
+            "A spec can't allow commands, pass --allow-commands instead: {}",
+            path.display()
+        ))
+        .into());
+    }
+
+    if let (Some(template), Some(directory)) = (&config.template, path.parent()) {
+        config.template = Some(directory.join(template));
+    }
+    Ok(config)
+}
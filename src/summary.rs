@@ -0,0 +1,77 @@
+//! This module summarizes the directories of a prompt from the header comments of their files, as an
+//! orientation aid for large codebases.
+
+use crate::language::header_comment;
+use crate::path::root_relative_path;
+use crate::source::FileSource;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// A file of a directory summary, with the first line of its header comment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileSummary {
+    /// The file name, without its directory.
+    pub name: String,
+    pub summary: String,
+}
+
+/// The files of a directory that have a header comment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DirectorySummary {
+    /// The path of the directory relative to the root directory, `.` for the root itself.
+    pub directory: String,
+    pub files: Vec<FileSummary>,
+}
+
+/// Summarizes the directories of the files by listing the first header comment line of each file
+///
+/// The header comments are read from the original content of the files, see [`header_comment`]. Files
+/// without one are left out, and so are directories without any such file. Directories are sorted by path
+/// and their files by name.
+///
+/// # Arguments
+///
+/// * `source` - The source of the files.
+/// * `files` - The JSON representations of the files.
+/// * `root_path` - The path to the root directory.
+/// * `relative_paths` - Whether the traversal produced relative paths.
+///
+/// # Returns
+///
+/// * `Vec<DirectorySummary>` - The summaries of the directories.
+pub fn directory_summaries(
+    source: &dyn FileSource,
+    files: &[serde_json::Value],
+    root_path: &Path,
+    relative_paths: bool,
+) -> Vec<DirectorySummary> {
+    let mut directories: BTreeMap<String, Vec<FileSummary>> = BTreeMap::new();
+    for file in files {
+        let path = file["path"].as_str().unwrap_or_default();
+        let relative = root_relative_path(path, root_path, relative_paths);
+        let relative = Path::new(&relative);
+        let extension = relative.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+        let Ok(bytes) = source.read(&root_path.join(relative)) else {
+            continue;
+        };
+        let Some(summary) = header_comment(&String::from_utf8_lossy(&bytes), extension) else {
+            continue;
+        };
+        let directory = match relative.parent().map(|parent| parent.to_string_lossy().replace('\\', "/")) {
+            Some(parent) if !parent.is_empty() => parent,
+            _ => ".".to_string(),
+        };
+        directories.entry(directory).or_default().push(FileSummary {
+            name: relative.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            summary,
+        });
+    }
+    directories
+        .into_iter()
+        .map(|(directory, mut files)| {
+            files.sort_by(|a, b| a.name.cmp(&b.name));
+            DirectorySummary { directory, files }
+        })
+        .collect()
+}
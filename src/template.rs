@@ -1,15 +1,63 @@
 //! This module contains the functions to set up the Handlebars template engine and render the template with the provided data.
 //! It also includes functions for handling user-defined variables, copying the rendered output to the clipboard, and writing it to a file.
 
+use crate::error::Code2PromptError;
 use anyhow::{Context, Result};
 use arboard::Clipboard;
 use colored::*;
-use handlebars::{no_escape, Handlebars};
+use handlebars::{
+    no_escape, Context as HandlebarsContext, Handlebars, Helper, HelperDef, RenderContext, RenderError, ScopedJson,
+    TemplateError,
+};
 use inquire::Text;
 use regex::Regex;
+use serde_json::json;
 use std::io::Write;
+use std::path::Path;
+
+/// The name of the partial rendering a single file block.
+pub const FILE_BLOCK_PARTIAL: &str = "file_block";
+
+/// The name of the partial rendering the header of a file block, such as its path.
+pub const FILE_HEADER_PARTIAL: &str = "file_header";
+
+/// Returns the name of the partial rendering file blocks for the given extension.
+///
+/// # Arguments
+///
+/// * `extension` - The file extension, with or without a leading dot.
+///
+/// # Returns
+///
+/// * `String` - The partial name, e.g. `file_block.sql`.
+pub fn file_block_partial_name(extension: &str) -> String {
+    format!("{}.{}", FILE_BLOCK_PARTIAL, extension.trim_start_matches('.'))
+}
+
+/// Helper resolving the file block partial for an extension, for use as `{{> (file_block_partial extension)}}`.
+/// Falls back to the default file block when no template is registered for the extension.
+struct FileBlockPartialHelper;
+
+impl HelperDef for FileBlockPartialHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        r: &'reg Handlebars<'reg>,
+        _: &'rc HandlebarsContext,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        let extension = h.param(0).and_then(|p| p.value().as_str()).unwrap_or("");
+        let name = file_block_partial_name(extension);
+        if !extension.is_empty() && r.has_template(&name) {
+            Ok(ScopedJson::Derived(json!(name)))
+        } else {
+            Ok(ScopedJson::Derived(json!(FILE_BLOCK_PARTIAL)))
+        }
+    }
+}
 
 /// Set up the Handlebars template engine with a template string and a template name.
+/// The default file block partial and the `file_block_partial` helper are registered as well.
 ///
 /// # Arguments
 ///
@@ -22,16 +70,125 @@ use std::io::Write;
 pub fn handlebars_setup(template_str: &str, template_name: &str) -> Result<Handlebars<'static>> {
     let mut handlebars = Handlebars::new();
     handlebars.register_escape_fn(no_escape);
+    handlebars.register_helper("file_block_partial", Box::new(FileBlockPartialHelper));
+
+    handlebars
+        .register_partial(FILE_BLOCK_PARTIAL, include_str!("default_file_block.hbs"))
+        .map_err(|e| anyhow::anyhow!("Failed to register file block template: {}", e))?;
+    handlebars
+        .register_partial(FILE_HEADER_PARTIAL, include_str!("default_file_header.hbs"))
+        .map_err(|e| anyhow::anyhow!("Failed to register file header template: {}", e))?;
 
     handlebars
         .register_template_string(template_name, template_str)
-        .map_err(|e| anyhow::anyhow!("Failed to register template: {}", e))?;
+        .map_err(|e| {
+            Code2PromptError::Template(format!(
+                "Failed to register template: {}",
+                describe_template_error(&e, template_str)
+            ))
+        })?;
 
     Ok(handlebars)
 }
 
+/// Describes a template syntax error with its location and the offending region of the template.
+fn describe_template_error(error: &TemplateError, template_str: &str) -> String {
+    match (error.line_no, error.column_no) {
+        (Some(line), Some(column)) => format!(
+            "{} at line {}, column {}\n{}",
+            error.reason().to_string().trim_end_matches('.'),
+            line,
+            column,
+            template_snippet(template_str, line, column)
+        ),
+        _ => error.reason().to_string(),
+    }
+}
+
+/// Returns the lines around a template location, with a caret under the given column.
+///
+/// # Arguments
+///
+/// * `template_str` - The Handlebars template string.
+/// * `line` - The 1-based line number.
+/// * `column` - The 1-based column number.
+///
+/// # Returns
+///
+/// * `String` - The numbered lines surrounding the location.
+fn template_snippet(template_str: &str, line: usize, column: usize) -> String {
+    let first = line.saturating_sub(2).max(1);
+    let mut snippet = String::new();
+    for (number, content) in template_str.lines().enumerate().map(|(i, l)| (i + 1, l)) {
+        if number < first || number > line + 1 {
+            continue;
+        }
+        snippet.push_str(&format!("{:>4} | {}\n", number, content));
+        if number == line {
+            snippet.push_str(&format!("     | {}^\n", " ".repeat(column.saturating_sub(1))));
+        }
+    }
+    snippet.trim_end().to_string()
+}
+
+/// Registers a file block template used for the files with the given extension.
+///
+/// # Arguments
+///
+/// * `handlebars` - The configured Handlebars instance.
+/// * `extension` - The file extension the template applies to, with or without a leading dot.
+/// * `template_str` - The Handlebars template string rendering a single file entry.
+///
+/// # Returns
+///
+/// * `Result<()>` - An empty result indicating success or an error.
+pub fn register_file_block_template(
+    handlebars: &mut Handlebars,
+    extension: &str,
+    template_str: &str,
+) -> Result<()> {
+    handlebars
+        .register_partial(&file_block_partial_name(extension), template_str)
+        .map_err(|e| {
+            Code2PromptError::Template(format!(
+                "Failed to register file block template for '{}': {}",
+                extension, e
+            ))
+            .into()
+        })
+}
+
+/// Registers the template rendering the header of each file block, in place of the default `` `{{path}}`: ``.
+///
+/// The header has access to the fields of the file, such as `path`, `language` and `token_count`.
+///
+/// # Arguments
+///
+/// * `handlebars` - The configured Handlebars instance.
+/// * `template_str` - The Handlebars template string rendering the header of a file entry.
+///
+/// # Returns
+///
+/// * `Result<()>` - An empty result indicating success or an error.
+pub fn register_file_header_template(handlebars: &mut Handlebars, template_str: &str) -> Result<()> {
+    // The header is followed by a line break, like the default one
+    let template_str = format!("{}\n", template_str.trim_end_matches(['\r', '\n']));
+    handlebars
+        .register_partial(FILE_HEADER_PARTIAL, &template_str)
+        .map_err(|e| {
+            Code2PromptError::Template(format!(
+                "Failed to register file header template: {}",
+                describe_template_error(&e, &template_str)
+            ))
+            .into()
+        })
+}
+
 /// Extracts the undefined variables from the template string.
 ///
+/// Variables inside `{{#each}}` and `{{#with}}` blocks refer to the block's own context, such as the
+/// fields of a file or a diff, so only the variables outside of them are considered.
+///
 /// # Arguments
 ///
 /// * `template` - The Handlebars template string.
@@ -41,11 +198,22 @@ pub fn handlebars_setup(template_str: &str, template_name: &str) -> Result<Handl
 /// * `Vec<String>` - A vector of undefined variable names.
 pub fn extract_undefined_variables(template: &str) -> Vec<String> {
     let registered_identifiers = ["path", "code", "git_diff"];
-    let re = Regex::new(r"\{\{\s*(?P<var>[a-zA-Z_][a-zA-Z_0-9]*)\s*\}\}").unwrap();
-    re.captures_iter(template)
-        .map(|cap| cap["var"].to_string())
-        .filter(|var| !registered_identifiers.contains(&var.as_str()))
-        .collect()
+    let re = Regex::new(
+        r"\{\{\s*(?:(?P<open>#(?:each|with))\b[^}]*|(?P<close>/(?:each|with))\s*|(?P<var>[a-zA-Z_][a-zA-Z_0-9]*)\s*)\}\}",
+    )
+    .unwrap();
+    let mut depth = 0usize;
+    let mut variables = Vec::new();
+    for cap in re.captures_iter(template) {
+        if cap.name("open").is_some() {
+            depth += 1;
+        } else if cap.name("close").is_some() {
+            depth = depth.saturating_sub(1);
+        } else if depth == 0 && !registered_identifiers.contains(&&cap["var"]) {
+            variables.push(cap["var"].to_string());
+        }
+    }
+    variables
 }
 
 /// Renders the template with the provided data.
@@ -66,16 +234,85 @@ pub fn render_template(
 ) -> Result<String> {
     let rendered = handlebars
         .render(template_name, data)
-        .map_err(|e| anyhow::anyhow!("Failed to render template: {}", e))?;
+        .map_err(|e| Code2PromptError::Template(describe_render_error(&e)))?;
     Ok(rendered.trim().to_string())
 }
 
+/// Renders a template with arbitrary data in one call, using the same Handlebars setup as the prompt.
+///
+/// The helpers and partials of the prompt, such as `file_block_partial`, are available to the template.
+///
+/// # Arguments
+///
+/// * `template` - The Handlebars template string.
+/// * `data` - The JSON data object.
+///
+/// # Returns
+///
+/// * `Result<String>` - The rendered template as a string.
+///
+/// # Examples
+///
+/// ```
+/// use code2prompt::template::render;
+/// use serde_json::json;
+///
+/// let rendered = render("Review {{#each files}}`{{path}}` {{/each}}", &json!({
+///     "files": [{ "path": "src/main.rs" }, { "path": "src/lib.rs" }]
+/// }))
+/// .unwrap();
+/// assert_eq!(rendered, "Review `src/main.rs` `src/lib.rs`");
+/// ```
+pub fn render(template: &str, data: &serde_json::Value) -> Result<String> {
+    let handlebars = handlebars_setup(template, "template")?;
+    render_template(&handlebars, "template", data)
+}
+
+/// Describes a render error with its location, telling undefined helpers apart from other failures.
+fn describe_render_error(error: &RenderError) -> String {
+    let location = match (error.line_no, error.column_no) {
+        (Some(line), Some(column)) => format!(" at line {}, column {}", line, column),
+        _ => String::new(),
+    };
+    match error.desc.strip_prefix("Helper not defined: ") {
+        Some(helper) => format!("Undefined helper {}{}", helper, location),
+        None => format!("Failed to render template{}: {}", location, error.desc),
+    }
+}
+
+/// Returns the undefined variables of the template that the user is asked for.
+///
+/// The variables defined in the data, and the optional ones, are left out.
+///
+/// # Arguments
+///
+/// * `data` - The JSON data object.
+/// * `template_content` - The template content string.
+/// * `optional_variables` - The variables that default to an empty string rather than being asked for.
+///
+/// # Returns
+///
+/// * `Vec<String>` - The names of the variables to ask for, in order of appearance.
+pub fn required_variables(
+    data: &serde_json::Value,
+    template_content: &str,
+    optional_variables: &[String],
+) -> Vec<String> {
+    extract_undefined_variables(template_content)
+        .into_iter()
+        .filter(|var| !data.as_object().unwrap().contains_key(var) && !optional_variables.contains(var))
+        .collect()
+}
+
 /// Handles user-defined variables in the template and adds them to the data.
 ///
+/// The required variables are asked for interactively, while the optional ones default to an empty string.
+///
 /// # Arguments
 ///
 /// * `data` - The JSON data object.
 /// * `template_content` - The template content string.
+/// * `optional_variables` - The variables that default to an empty string rather than being asked for.
 ///
 /// # Returns
 ///
@@ -83,18 +320,21 @@ pub fn render_template(
 pub fn handle_undefined_variables(
     data: &mut serde_json::Value,
     template_content: &str,
+    optional_variables: &[String],
 ) -> Result<()> {
-    let undefined_variables = extract_undefined_variables(template_content);
     let mut user_defined_vars = serde_json::Map::new();
 
-    for var in undefined_variables.iter() {
-        if !data.as_object().unwrap().contains_key(var) {
-            let prompt = format!("Enter value for '{}': ", var);
-            let answer = Text::new(&prompt)
-                .with_help_message("Fill user defined variable in template")
-                .prompt()
-                .unwrap_or_default();
-            user_defined_vars.insert(var.clone(), serde_json::Value::String(answer));
+    for var in required_variables(data, template_content, optional_variables) {
+        let prompt = format!("Enter value for '{}': ", var);
+        let answer = Text::new(&prompt)
+            .with_help_message("Fill user defined variable in template")
+            .prompt()
+            .unwrap_or_default();
+        user_defined_vars.insert(var, serde_json::Value::String(answer));
+    }
+    for var in extract_undefined_variables(template_content) {
+        if optional_variables.contains(&var) && !data.as_object().unwrap().contains_key(&var) {
+            user_defined_vars.insert(var, serde_json::Value::String(String::new()));
         }
     }
 
@@ -127,7 +367,7 @@ pub fn copy_to_clipboard(rendered: &str) -> Result<()> {
     }
 }
 
-/// Writes the rendered template to a specified output file.
+/// Writes the rendered template to a specified output file, creating its parent directories as needed.
 ///
 /// # Arguments
 ///
@@ -138,15 +378,60 @@ pub fn copy_to_clipboard(rendered: &str) -> Result<()> {
 ///
 /// * `Result<()>` - An empty result indicating success or an error.
 pub fn write_to_file(output_path: &str, rendered: &str) -> Result<()> {
-    let file = std::fs::File::create(output_path)?;
+    write_to_file_with_options(output_path, rendered, true)
+}
+
+/// Writes the rendered template to a specified output file.
+///
+/// The file is written in place rather than renamed from a temporary file, so the output may live
+/// on a different filesystem than the working directory.
+///
+/// # Arguments
+///
+/// * `output_path` - The path to the output file.
+/// * `rendered` - The rendered template string.
+/// * `create_dirs` - Whether to create the missing parent directories of the output file.
+///
+/// # Returns
+///
+/// * `Result<()>` - An empty result indicating success or an error.
+pub fn write_to_file_with_options(output_path: &str, rendered: &str, create_dirs: bool) -> Result<()> {
+    write_to_file_quietly(output_path, rendered, create_dirs, false)
+}
+
+/// Writes the rendered template to a specified output file, optionally without the status line.
+///
+/// # Arguments
+///
+/// * `output_path` - The path to the output file.
+/// * `rendered` - The rendered template string.
+/// * `create_dirs` - Whether to create the missing parent directories of the output file.
+/// * `quiet` - Whether to skip the status line on stderr.
+///
+/// # Returns
+///
+/// * `Result<()>` - An empty result indicating success or an error.
+pub fn write_to_file_quietly(output_path: &str, rendered: &str, create_dirs: bool, quiet: bool) -> Result<()> {
+    if create_dirs {
+        if let Some(parent) = Path::new(output_path).parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!("Failed to create output directory: {}", parent.display())
+            })?;
+        }
+    }
+    let file = std::fs::File::create(output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path))?;
     let mut writer = std::io::BufWriter::new(file);
     write!(writer, "{}", rendered)?;
-    println!(
-        "{}{}{} {}",
-        "[".bold().white(),
-        "✓".bold().green(),
-        "]".bold().white(),
-        format!("Prompt written to file: {}", output_path).green()
-    );
+    writer.flush()?;
+    if !quiet {
+        eprintln!(
+            "{}{}{} {}",
+            "[".bold().white(),
+            "✓".bold().green(),
+            "]".bold().white(),
+            format!("Prompt written to file: {}", output_path).green()
+        );
+    }
     Ok(())
 }
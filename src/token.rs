@@ -1,21 +1,281 @@
 //! This module encapsulates the logic for counting the tokens in the rendered text.
 
 use colored::*;
-use tiktoken_rs::{cl100k_base, p50k_base, p50k_edit, r50k_base, CoreBPE};
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, RwLock};
+use tiktoken_rs::tokenizer::Tokenizer as TiktokenTokenizer;
+use tiktoken_rs::{cl100k_base, o200k_base, p50k_base, p50k_edit, r50k_base, CoreBPE};
 
-/// Returns the appropriate tokenizer based on the provided encoding.
+/// Environment variable naming the encoding to use when none is configured explicitly.
+pub const ENCODING_ENV_VAR: &str = "CODE2PROMPT_ENCODING";
+
+/// Environment variable naming an OpenAI model whose encoding is used as a fallback.
+pub const MODEL_ENV_VAR: &str = "OPENAI_MODEL";
+
+/// The encoding used when neither the configuration nor the environment specifies one.
+pub const DEFAULT_ENCODING: &str = "cl100k";
+
+/// A tokenizer counting the tokens of a text, such as a tiktoken encoding or a custom one.
+pub trait Tokenizer: Send + Sync {
+    /// Counts the tokens of a text.
+    fn count(&self, text: &str) -> usize;
+
+    /// Counts the tokens of a text before any rounding, for a tokenizer whose counts are approximations.
+    ///
+    /// The counts of the chunks of a text are added up unrounded and rounded once, so that the total is the
+    /// count of the whole text.
+    fn count_unrounded(&self, text: &str) -> f64 {
+        self.count(text) as f64
+    }
+
+    /// Encodes a text into its tokens, or returns `None` for a tokenizer that only counts them.
+    fn encode(&self, _text: &str) -> Option<Vec<usize>> {
+        None
+    }
+
+    /// Decodes tokens back into the bytes of their text, or returns `None` for a tokenizer that only counts them.
+    ///
+    /// A token may hold part of a multi-byte character, so the bytes of a range of tokens aren't always valid UTF-8.
+    fn decode(&self, _tokens: &[usize]) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+impl Tokenizer for CoreBPE {
+    fn count(&self, text: &str) -> usize {
+        self.encode_with_special_tokens(text).len()
+    }
+
+    fn encode(&self, text: &str) -> Option<Vec<usize>> {
+        Some(self.encode_with_special_tokens(text))
+    }
+
+    fn decode(&self, tokens: &[usize]) -> Option<Vec<u8>> {
+        Some(self._decode_native(tokens))
+    }
+}
+
+/// A tiktoken encoding that counts special tokens such as `<|endoftext|>` as plain text, like `encode`
+/// with no allowed special tokens.
+struct OrdinaryTokenizer(CoreBPE);
+
+impl Tokenizer for OrdinaryTokenizer {
+    fn count(&self, text: &str) -> usize {
+        self.0.encode(text, HashSet::new()).len()
+    }
+
+    fn encode(&self, text: &str) -> Option<Vec<usize>> {
+        Some(self.0.encode(text, HashSet::new()))
+    }
+
+    fn decode(&self, tokens: &[usize]) -> Option<Vec<u8>> {
+        Tokenizer::decode(&self.0, tokens)
+    }
+}
+
+/// The assumed ratio of Claude tokens to `cl100k` tokens.
+///
+/// This is a rough estimate, not a measurement: it hasn't been checked against Anthropic's token counting
+/// API, so the error of the counts scaled by it is unknown.
+pub const CLAUDE_TOKEN_RATIO: f64 = 1.15;
+
+/// An approximation of the tokenizer of Anthropic's Claude models, for the `claude` encoding.
+///
+/// Claude's tokenizer isn't public, so the tokens are counted with `cl100k` and scaled by
+/// [`CLAUDE_TOKEN_RATIO`]. How far the counts are from the ones reported by Anthropic's token counting
+/// API isn't known; use that API when the count must be exact. Special token markers are plain text for
+/// Claude, so they are always encoded as such.
+struct ClaudeTokenizer(OrdinaryTokenizer);
+
+impl Tokenizer for ClaudeTokenizer {
+    fn count(&self, text: &str) -> usize {
+        self.count_unrounded(text).round() as usize
+    }
+
+    fn count_unrounded(&self, text: &str) -> f64 {
+        self.0.count(text) as f64 * CLAUDE_TOKEN_RATIO
+    }
+}
+
+/// The tokenizers registered with [`register_tokenizer`], by encoding name.
+static CUSTOM_TOKENIZERS: Lazy<RwLock<HashMap<String, Arc<dyn Tokenizer>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Registers a tokenizer under an encoding name, so that `encoding` resolves to it in [`get_tokenizer`].
+///
+/// A registered tokenizer takes precedence over a built-in encoding of the same name.
+///
+/// # Arguments
+///
+/// * `name` - The name of the encoding, e.g. `mycorp`.
+/// * `tokenizer` - The tokenizer counting the tokens of this encoding.
+pub fn register_tokenizer(name: impl Into<String>, tokenizer: impl Tokenizer + 'static) {
+    CUSTOM_TOKENIZERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.into(), Arc::new(tokenizer));
+}
+
+/// Returns the tokenizer registered under an encoding name, if any.
+fn custom_tokenizer(encoding: &str) -> Option<Arc<dyn Tokenizer>> {
+    CUSTOM_TOKENIZERS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(encoding)
+        .cloned()
+}
+
+/// Resolves the encoding to use for tokenization.
+///
+/// The lookup order is:
+///
+/// 1. The explicitly configured `encoding`.
+/// 2. The `CODE2PROMPT_ENCODING` environment variable.
+/// 3. The encoding of the model named by the `OPENAI_MODEL` environment variable (e.g. `gpt-4` -> `cl100k`).
+/// 4. The default `cl100k` encoding.
 ///
 /// # Arguments
 ///
 /// * `encoding` - An optional string specifying the encoding to use for tokenization.
-///                Supported encodings: "cl100k" (default), "p50k", "p50k_edit", "r50k", "gpt2".
+///
+/// # Returns
+///
+/// * `String` - The name of the encoding to use.
+pub fn resolve_encoding(encoding: &Option<String>) -> String {
+    resolve_encoding_with_env(encoding, &env_var)
+}
+
+/// Resolves the encoding to use for tokenization like [`resolve_encoding`], looking up the environment
+/// variables with `env`.
+///
+/// # Arguments
+///
+/// * `encoding` - An optional string specifying the encoding to use for tokenization.
+/// * `env` - Returns the value of an environment variable, if set.
+///
+/// # Returns
+///
+/// * `String` - The name of the encoding to use.
+pub fn resolve_encoding_with_env(encoding: &Option<String>, env: &dyn Fn(&str) -> Option<String>) -> String {
+    if let Some(encoding) = encoding {
+        return encoding.clone();
+    }
+    if let Some(encoding) = env(ENCODING_ENV_VAR) {
+        if !encoding.trim().is_empty() {
+            return encoding.trim().to_string();
+        }
+    }
+    if let Some(model) = env(MODEL_ENV_VAR) {
+        if let Some(encoding) = model_encoding(&model) {
+            return encoding.to_string();
+        }
+    }
+    DEFAULT_ENCODING.to_string()
+}
+
+/// Returns the value of an environment variable of the process, if set.
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+/// Returns the encoding of a model name known to tiktoken, e.g. `gpt-4` -> `cl100k`.
+fn model_encoding(model: &str) -> Option<&'static str> {
+    match tiktoken_rs::tokenizer::get_tokenizer(model.trim())? {
+        TiktokenTokenizer::O200kBase => Some("o200k"),
+        TiktokenTokenizer::Cl100kBase => Some("cl100k"),
+        TiktokenTokenizer::P50kBase => Some("p50k"),
+        TiktokenTokenizer::P50kEdit => Some("p50k_edit"),
+        TiktokenTokenizer::R50kBase => Some("r50k"),
+        TiktokenTokenizer::Gpt2 => Some("gpt2"),
+    }
+}
+
+/// Resolves the name of the model whose tokenizer counts the tokens.
+///
+/// The model named by the `OPENAI_MODEL` environment variable is used when it uses the resolved encoding;
+/// otherwise a representative model of the encoding is given, e.g. `gpt-4o` for `o200k`. A registered
+/// tokenizer is named after its encoding.
+///
+/// # Arguments
+///
+/// * `encoding` - An optional string specifying the encoding, resolved like [`resolve_encoding`].
+///
+/// # Returns
+///
+/// * `String` - The name of the model.
+pub fn resolve_model(encoding: &Option<String>) -> String {
+    resolve_model_with_env(encoding, &env_var)
+}
+
+/// Resolves the name of the model like [`resolve_model`], looking up the environment variables with `env`.
+///
+/// # Arguments
+///
+/// * `encoding` - An optional string specifying the encoding, resolved like [`resolve_encoding_with_env`].
+/// * `env` - Returns the value of an environment variable, if set.
+///
+/// # Returns
+///
+/// * `String` - The name of the model.
+pub fn resolve_model_with_env(encoding: &Option<String>, env: &dyn Fn(&str) -> Option<String>) -> String {
+    let encoding = resolve_encoding_with_env(encoding, env);
+    if let Some(model) = env(MODEL_ENV_VAR) {
+        if model_encoding(&model) == Some(encoding.as_str()) {
+            return model.trim().to_string();
+        }
+    }
+    let model = match encoding.as_str() {
+        "cl100k" => "gpt-4",
+        "o200k" => "gpt-4o",
+        "p50k" => "text-davinci-003",
+        "p50k_edit" => "text-davinci-edit-001",
+        "r50k" | "gpt2" => "davinci",
+        "claude" => "claude",
+        _ => return encoding,
+    };
+    model.to_string()
+}
+
+/// Returns the appropriate tokenizer based on the provided encoding, as a [`Tokenizer`] trait object.
+///
+/// # Arguments
+///
+/// * `encoding` - An optional string specifying the encoding to use for tokenization.
+///   Supported encodings: "cl100k" (default), "o200k", "p50k", "p50k_edit", "r50k", "gpt2", "claude" (an
+///   approximation, see [`CLAUDE_TOKEN_RATIO`]), and the names registered with [`register_tokenizer`].
+///   When `None`, the encoding is resolved from the environment (see [`resolve_encoding`]).
+///
+/// # Returns
+///
+/// * `Arc<dyn Tokenizer>` - The tokenizer corresponding to the specified encoding.
+pub fn get_dyn_tokenizer(encoding: &Option<String>) -> Arc<dyn Tokenizer> {
+    get_tokenizer_with_special_tokens(encoding, true)
+}
+
+/// Returns the tiktoken encoding of an encoding name.
+///
+/// The approximate `claude` encoding and the registered tokenizers aren't tiktoken encodings, so they give
+/// the `cl100k` encoding they are based on, or the default one; use [`get_dyn_tokenizer`] to count with them.
+///
+/// # Arguments
+///
+/// * `encoding` - An optional string specifying the encoding to use for tokenization.
+///   Supported encodings: "cl100k" (default), "o200k", "p50k", "p50k_edit", "r50k", "gpt2".
+///   When `None`, the encoding is resolved from the environment (see [`resolve_encoding`]).
 ///
 /// # Returns
 ///
 /// * `CoreBPE` - The tokenizer corresponding to the specified encoding.
 pub fn get_tokenizer(encoding: &Option<String>) -> CoreBPE {
-    match encoding.as_deref().unwrap_or("cl100k") {
-        "cl100k" => cl100k_base().unwrap(),
+    bpe(&resolve_encoding(encoding))
+}
+
+/// Returns the tiktoken encoding of a resolved encoding name, `cl100k` for the unknown ones.
+fn bpe(encoding: &str) -> CoreBPE {
+    match encoding {
+        "o200k" => o200k_base().unwrap(),
         "p50k" => p50k_base().unwrap(),
         "p50k_edit" => p50k_edit().unwrap(),
         "r50k" | "gpt2" => r50k_base().unwrap(),
@@ -23,56 +283,368 @@ pub fn get_tokenizer(encoding: &Option<String>) -> CoreBPE {
     }
 }
 
+/// Returns the tokenizer of an encoding like [`get_dyn_tokenizer`], choosing how special tokens are counted.
+///
+/// Texts may contain the markers of special tokens, e.g. `<|endoftext|>` in a file about tokenizers. Counted
+/// as special tokens, with `encode_with_special_tokens`, each marker is a single token; otherwise it is
+/// encoded with `encode` as plain text, like most APIs do for user content, and takes several tokens.
+/// Registered tokenizers count tokens their own way either way.
+///
+/// # Arguments
+///
+/// * `encoding` - An optional string specifying the encoding to use for tokenization.
+/// * `count_special_tokens` - Whether the markers of special tokens count as single special tokens.
+///
+/// # Returns
+///
+/// * `Arc<dyn Tokenizer>` - The tokenizer corresponding to the specified encoding.
+pub fn get_tokenizer_with_special_tokens(encoding: &Option<String>, count_special_tokens: bool) -> Arc<dyn Tokenizer> {
+    let encoding = resolve_encoding(encoding);
+    if let Some(tokenizer) = custom_tokenizer(&encoding) {
+        return tokenizer;
+    }
+    if encoding == "claude" {
+        return Arc::new(ClaudeTokenizer(OrdinaryTokenizer(cl100k_base().unwrap())));
+    }
+    let bpe = bpe(&encoding);
+    if count_special_tokens {
+        Arc::new(bpe)
+    } else {
+        Arc::new(OrdinaryTokenizer(bpe))
+    }
+}
+
 /// Returns the model information based on the provided encoding.
 ///
 /// # Arguments
 ///
 /// * `encoding` - An optional string specifying the encoding to use for retrieving model information.
-///                Supported encodings: "cl100k" (default), "p50k", "p50k_edit", "r50k", "gpt2".
+///   Supported encodings: "cl100k" (default), "o200k", "p50k", "p50k_edit", "r50k", "gpt2", "claude".
+///   When `None`, the encoding is resolved from the environment (see [`resolve_encoding`]).
 ///
 /// # Returns
 ///
 /// * `&'static str` - A string describing the models associated with the specified encoding.
 pub fn get_model_info(encoding: &Option<String>) -> &'static str {
-    match encoding.as_deref().unwrap_or("cl100k") {
+    let encoding = resolve_encoding(encoding);
+    if custom_tokenizer(&encoding).is_some() {
+        return "Custom tokenizer";
+    }
+    match encoding.as_str() {
         "cl100k" => "ChatGPT models, text-embedding-ada-002",
+        "o200k" => "GPT-4o models",
         "p50k" => "Code models, text-davinci-002, text-davinci-003",
         "p50k_edit" => "Edit models like text-davinci-edit-001, code-davinci-edit-001",
         "r50k" | "gpt2" => "GPT-3 models like davinci",
+        "claude" => "Claude models, 200K token context window (approximate count)",
         _ => "ChatGPT models, text-embedding-ada-002",
     }
 }
 
-/// Counts the tokens in the rendered text using the specified encoding and prints the result.
+/// Counts the tokens in the rendered text using the specified encoding and prints the result to stderr.
 ///
 /// # Arguments
 ///
 /// * `rendered` - The rendered template string.
 /// * `encoding` - An optional string specifying the encoding to use for token counting.
-///                Supported encodings: "cl100k" (default), "p50k", "p50k_edit", "r50k", "gpt2".
+///   Supported encodings: "cl100k" (default), "o200k", "p50k", "p50k_edit", "r50k", "gpt2".
+///   When `None`, the encoding is resolved from the environment (see [`resolve_encoding`]).
 pub fn count_tokens(rendered: &str, encoding: &Option<String>) {
-    let (bpe, model_info) = match encoding.as_deref().unwrap_or("cl100k") {
-        "cl100k" => (cl100k_base(), "ChatGPT models, text-embedding-ada-002"),
-        "p50k" => (
-            p50k_base(),
-            "Code models, text-davinci-002, text-davinci-003",
-        ),
-        "p50k_edit" => (
-            p50k_edit(),
-            "Edit models like text-davinci-edit-001, code-davinci-edit-001",
-        ),
-        "r50k" | "gpt2" => (r50k_base(), "GPT-3 models like davinci"),
-        _ => (cl100k_base(), "ChatGPT models, text-embedding-ada-002"),
+    let tokens = get_tokenizer(encoding).count(rendered);
+    TokenStats::new(rendered, tokens, encoding, 0).print();
+}
+
+/// The statistics of a rendered prompt, printed after it is generated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenStats {
+    pub tokens: usize,
+    pub encoding: String,
+    /// The model whose tokenizer counts the tokens, see [`resolve_model`].
+    pub model: String,
+    pub model_info: &'static str,
+    pub chars: usize,
+    pub files: usize,
+}
+
+impl TokenStats {
+    /// Collects the statistics of a rendered prompt whose tokens were counted.
+    ///
+    /// # Arguments
+    ///
+    /// * `rendered` - The rendered prompt.
+    /// * `tokens` - The token count of the prompt.
+    /// * `encoding` - The configured encoding, resolved from the environment when `None`.
+    /// * `files` - The number of files in the prompt.
+    pub fn new(rendered: &str, tokens: usize, encoding: &Option<String>, files: usize) -> Self {
+        Self {
+            tokens,
+            encoding: resolve_encoding(encoding),
+            model: resolve_model(encoding),
+            model_info: get_model_info(encoding),
+            chars: rendered.chars().count(),
+            files,
+        }
+    }
+
+    /// Returns the statistics as a single line of `key=value` pairs, e.g. `tokens=12000 model=gpt-4 chars=48000 files=12`.
+    pub fn machine_line(&self) -> String {
+        format!(
+            "tokens={} model={} chars={} files={}",
+            self.tokens, self.model, self.chars, self.files
+        )
+    }
+
+    /// Prints the statistics to stderr as an aligned table.
+    ///
+    /// Colors are left out when the `NO_COLOR` environment variable is set.
+    pub fn print(&self) {
+        eprintln!(
+            "{}{}{} {}",
+            "[".bold().white(),
+            "i".bold().blue(),
+            "]".bold().white(),
+            "Prompt statistics:".bold()
+        );
+        let rows = [
+            ("Tokens", self.tokens.to_string().bold().yellow().to_string()),
+            ("Characters", self.chars.to_string()),
+            ("Files", self.files.to_string()),
+            ("Encoding", self.encoding.clone()),
+            ("Model info", self.model_info.to_string()),
+        ];
+        for (name, value) in rows {
+            eprintln!("    {:<12} {}", format!("{}:", name), value);
+        }
+    }
+}
+
+/// The size of the chunks encoded between two progress reports, in bytes.
+pub const TOKEN_CHUNK_BYTES: usize = 256 * 1024;
+
+/// Counts the tokens of a text chunk by chunk, reporting the progress after each chunk.
+///
+/// Chunks end after a single line break between a non-whitespace character and a letter or a digit.
+/// The encodings never merge text across such a boundary, so the total is the same as when encoding the whole text at once.
+/// A chunk grows beyond `chunk_bytes` when it has no such boundary.
+///
+/// # Arguments
+///
+/// * `text` - The text to count the tokens of.
+/// * `tokenizer` - The tokenizer.
+/// * `chunk_bytes` - The approximate size of the chunks, in bytes.
+/// * `progress` - Called with the number of bytes encoded so far and the total number of bytes.
+///
+/// # Returns
+///
+/// * `usize` - The exact token count.
+pub fn count_tokens_with_progress(
+    text: &str,
+    tokenizer: &dyn Tokenizer,
+    chunk_bytes: usize,
+    progress: &mut dyn FnMut(usize, usize),
+) -> usize {
+    let mut tokens = 0.0;
+    let mut start = 0;
+    while start < text.len() {
+        let end = chunk_end(text, start, chunk_bytes);
+        tokens += tokenizer.count_unrounded(&text[start..end]);
+        start = end;
+        progress(start, text.len());
+    }
+    tokens.round() as usize
+}
+
+/// Counts the tokens of a text like [`count_tokens_with_progress`], encoding the chunks on several threads.
+///
+/// The chunks are the same as with a single thread, so the count is identical; a single thread counts them
+/// in order on the calling thread. The progress is reported as chunks complete, in any order.
+///
+/// # Arguments
+///
+/// * `text` - The text to count the tokens of.
+/// * `tokenizer` - The tokenizer.
+/// * `chunk_bytes` - The approximate size of the chunks, in bytes.
+/// * `threads` - The number of threads encoding the chunks.
+/// * `progress` - Called with the number of bytes encoded so far and the total number of bytes.
+///
+/// # Returns
+///
+/// * `usize` - The exact token count.
+pub fn count_tokens_parallel(
+    text: &str,
+    tokenizer: &dyn Tokenizer,
+    chunk_bytes: usize,
+    threads: usize,
+    progress: &mut dyn FnMut(usize, usize),
+) -> usize {
+    if threads <= 1 {
+        return count_tokens_with_progress(text, tokenizer, chunk_bytes, progress);
+    }
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < text.len() {
+        let end = chunk_end(text, start, chunk_bytes);
+        chunks.push(&text[start..end]);
+        start = end;
+    }
+
+    let next_chunk = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    std::thread::scope(|scope| {
+        for _ in 0..threads.min(chunks.len()) {
+            let sender = sender.clone();
+            let (chunks, next_chunk) = (&chunks, &next_chunk);
+            scope.spawn(move || {
+                loop {
+                    let index = next_chunk.fetch_add(1, Ordering::Relaxed);
+                    let Some(chunk) = chunks.get(index) else {
+                        break;
+                    };
+                    let _ = sender.send((index, chunk.len(), tokenizer.count_unrounded(chunk)));
+                }
+            });
+        }
+        drop(sender);
+
+        // The counts are added up in the order of the chunks, like with a single thread
+        let mut counts = vec![0.0; chunks.len()];
+        let mut encoded = 0;
+        for (index, bytes, count) in receiver {
+            counts[index] = count;
+            encoded += bytes;
+            progress(encoded, text.len());
+        }
+        counts.iter().sum::<f64>().round() as usize
+    })
+}
+
+/// Returns the end of the chunk starting at `start`, just after a line break that is safe to split at.
+fn chunk_end(text: &str, start: usize, chunk_bytes: usize) -> usize {
+    let bytes = text.as_bytes();
+    let mut index = start + chunk_bytes.max(2);
+    while index < text.len() {
+        if bytes[index - 1] == b'\n'
+            && !bytes[index - 2].is_ascii_whitespace()
+            && bytes[index].is_ascii_alphanumeric()
+        {
+            return index;
+        }
+        index += 1;
+    }
+    text.len()
+}
+
+/// Adds a `tokens` field to each file entry with the token count of its formatted code.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files.
+/// * `encoding` - An optional string specifying the encoding to use for token counting.
+pub fn count_file_tokens(files: &mut [serde_json::Value], encoding: &Option<String>) {
+    let tokenizer = get_tokenizer(encoding);
+    for file in files.iter_mut() {
+        let code = file["code"].as_str().unwrap_or_default();
+        file["tokens"] = serde_json::json!(tokenizer.count(code));
+    }
+}
+
+/// Estimates the token count of a text without encoding it.
+///
+/// Counts about four characters per token, with indentation counted separately since older encodings
+/// spend a token on each space of it. This is much faster than encoding and usually within 10% of the
+/// exact count on source code, so it suits previews where precision isn't needed.
+///
+/// # Arguments
+///
+/// * `text` - The text to estimate.
+/// * `encoding` - An optional string specifying the encoding to estimate for.
+///   When `None`, the encoding is resolved from the environment (see [`resolve_encoding`]).
+///
+/// # Returns
+///
+/// * `usize` - The estimated token count.
+pub fn estimate_tokens(text: &str, encoding: &Option<String>) -> usize {
+    let indentation: usize = text
+        .lines()
+        .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+        .sum();
+    let rest = text.chars().count() - indentation;
+
+    // Older encodings have no tokens for runs of spaces, so indentation costs more
+    let (chars_per_token, indentation_per_token) = match resolve_encoding(encoding).as_str() {
+        "p50k" | "p50k_edit" => (3.2, 4.0),
+        "r50k" | "gpt2" => (3.3, 1.0),
+        "claude" => (4.0 / CLAUDE_TOKEN_RATIO, 8.0 / CLAUDE_TOKEN_RATIO),
+        _ => (4.0, 8.0),
     };
+    (rest as f64 / chars_per_token + indentation as f64 / indentation_per_token).round() as usize
+}
+
+/// Adds a `tokens` field to each file entry with the estimated token count of its formatted code.
+///
+/// The entries are also marked with `tokens_estimated`, so that exact counts can be told apart.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files.
+/// * `encoding` - An optional string specifying the encoding to estimate for.
+pub fn estimate_file_tokens(files: &mut [serde_json::Value], encoding: &Option<String>) {
+    for file in files.iter_mut() {
+        let code = file["code"].as_str().unwrap_or_default();
+        file["tokens"] = serde_json::json!(estimate_tokens(code, encoding));
+        file["tokens_estimated"] = serde_json::json!(true);
+    }
+}
+
+/// Aggregates the per-file token counts by file extension.
+///
+/// Files without an extension are grouped under `(none)`.
+///
+/// # Arguments
+///
+/// * `files` - The JSON representations of the files, with their `tokens` field.
+///
+/// # Returns
+///
+/// * `BTreeMap<String, usize>` - The token count of each extension.
+pub fn tokens_by_language(files: &[serde_json::Value]) -> BTreeMap<String, usize> {
+    let mut totals = BTreeMap::new();
+    for file in files {
+        let extension = match file["extension"].as_str().unwrap_or_default() {
+            "" => "(none)",
+            extension => extension,
+        };
+        let tokens = file["tokens"].as_u64().unwrap_or_default() as usize;
+        *totals.entry(extension.to_string()).or_insert(0) += tokens;
+    }
+    totals
+}
 
-    let token_count = bpe.unwrap().encode_with_special_tokens(rendered).len();
+/// Prints the token counts by extension as a table to stderr, largest first.
+///
+/// # Arguments
+///
+/// * `totals` - The token count of each extension.
+/// * `estimated` - Whether the counts are estimates, which labels the table as such.
+pub fn print_tokens_by_language(totals: &BTreeMap<String, usize>, estimated: bool) {
+    let mut rows: Vec<(&String, &usize)> = totals.iter().collect();
+    rows.sort_by_key(|(_, tokens)| std::cmp::Reverse(**tokens));
+    let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max(5);
 
-    println!(
-        "{}{}{} Token count: {}, Model info: {}",
+    eprintln!(
+        "{}{}{} {}:",
         "[".bold().white(),
         "i".bold().blue(),
         "]".bold().white(),
-        token_count.to_string().bold().yellow(),
-        model_info
+        if estimated { "Estimated tokens by language (approximate)" } else { "Tokens by language" }
+    );
+    for (name, tokens) in rows {
+        eprintln!("    {:<width$} {:>10}", name, tokens, width = width);
+    }
+    eprintln!(
+        "    {:<width$} {:>10}",
+        "total",
+        totals.values().sum::<usize>(),
+        width = width
     );
 }
@@ -0,0 +1,101 @@
+//! This module regenerates the prompt when the files of the codebase change.
+
+use crate::path::FileFilter;
+use crate::source::{is_ignored, FsSource, WalkOptions};
+use crate::{generate_prompt, resolve_patterns, Code2PromptConfig, Code2PromptError};
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::ops::ControlFlow;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// How long the filesystem has to be quiet after a change before the prompt is regenerated.
+pub const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches the codebase and regenerates the prompt with [`generate_prompt`] whenever its files change
+///
+/// Bursts of events, e.g. from a save or a checkout, are debounced into a single regeneration. Only the
+/// changes to the files the traversal would include trigger it: the files left out by the ignore files, the
+/// patterns or the options such as `no_tests` and `exclude_languages` are ignored, like hidden paths and the
+/// output file. The prompt is not generated before the first change.
+///
+/// # Arguments
+///
+/// * `config` - The configuration, whose `path` is the directory to watch.
+/// * `on_change` - Called with the result of each regeneration; returning `ControlFlow::Break` stops watching.
+///
+/// # Returns
+///
+/// * `Result<()>` - An error if the directory can't be watched.
+pub fn watch<F>(config: &Code2PromptConfig, mut on_change: F) -> Result<()>
+where
+    F: FnMut(Result<String, Code2PromptError>) -> ControlFlow<()>,
+{
+    let (include, exclude) = resolve_patterns(config)?;
+    let root = config
+        .path
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve path: {}", config.path.display()))?;
+    let output = config.output.as_ref().map(|output| absolute_path(Path::new(output)));
+    let filter = FileFilter::new(&FsSource, config, &include, &exclude)?;
+    let walk_options = WalkOptions {
+        follow_symlinks: config.follow_symlinks,
+        extra_ignore_files: config.extra_ignore_files.clone(),
+        sorted: false,
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        let _ = sender.send(event);
+    })
+    .context("Failed to create the file watcher")?;
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch directory: {}", root.display()))?;
+
+    let is_relevant = |event: &Event| {
+        matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_))
+            && event.paths.iter().any(|path| {
+                let Ok(relative) = path.strip_prefix(&root) else {
+                    return false;
+                };
+                // A removed file can't be resolved anymore, so its former path is filtered as is
+                !is_hidden(relative)
+                    && output.as_deref() != Some(path.as_path())
+                    && !path.is_dir()
+                    && !is_ignored(&root, path, &walk_options)
+                    && filter.includes_canonical(&path.canonicalize().unwrap_or_else(|_| path.clone()))
+            })
+    };
+
+    // The sender lives in the watcher, so the channel only closes when the watcher fails
+    while let Ok(event) = receiver.recv() {
+        let mut changed = event.as_ref().is_ok_and(&is_relevant);
+        while let Ok(event) = receiver.recv_timeout(WATCH_DEBOUNCE) {
+            changed |= event.as_ref().is_ok_and(&is_relevant);
+        }
+        if changed && on_change(generate_prompt(config)).is_break() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether a component of a relative path is hidden.
+fn is_hidden(path: &Path) -> bool {
+    path.components()
+        .any(|component| matches!(component, Component::Normal(name) if name.to_string_lossy().starts_with('.')))
+}
+
+/// Returns the absolute path of a file that may not exist yet, resolving its parent directory.
+fn absolute_path(path: &Path) -> PathBuf {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    match (parent.canonicalize(), path.file_name()) {
+        (Ok(parent), Some(name)) => parent.join(name),
+        _ => path.to_path_buf(),
+    }
+}
@@ -0,0 +1,123 @@
+use code2prompt::anonymize::Anonymizer;
+use code2prompt::source::MemorySource;
+use code2prompt::{render_prompt, traverse_source, Code2PromptConfig};
+use std::path::PathBuf;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renames_consistently_within_a_file() {
+        let mut anonymizer = Anonymizer::new();
+        let code = "fn compute_total(price: u32) -> u32 {\n    let total = price * 2; // double the price\n    total\n}\n";
+        let anonymized = anonymizer.anonymize(code, "rs");
+
+        assert_eq!(
+            anonymized,
+            "fn id_1(id_2: u32) -> u32 {\n    let id_3 = id_2 * 2; // double the id_2\n    id_3\n}\n"
+        );
+        assert_eq!(anonymizer.mapping()["id_1"], "compute_total");
+        assert_eq!(anonymizer.mapping()["id_2"], "price");
+    }
+
+    #[test]
+    fn test_keeps_literals_and_string_prefixes() {
+        let mut anonymizer = Anonymizer::new();
+        let anonymized = anonymizer.anonymize("name = r\"raw name\"\nlabel = 'name'\n", "py");
+        assert_eq!(anonymized, "id_1 = r\"raw name\"\nid_2 = 'name'\n");
+    }
+
+    #[test]
+    fn test_renames_consistently_across_files() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("a.py", "def load_user(user_id):\n    return fetch(user_id)\n")
+            .with_file("b.py", "from a import load_user\n\nload_user(42)\n")
+            .with_file("notes.md", "load_user is documented here");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            anonymize: true,
+            no_codeblock: true,
+            ..Default::default()
+        };
+        let traversal = traverse_source(&source, &config, &[], &[]).unwrap();
+
+        assert_eq!(
+            traversal.files[0]["code"],
+            "def id_1(id_2):\n    return id_3(id_2)\n"
+        );
+        assert_eq!(traversal.files[1]["code"], "from id_4 import id_1\n\nid_1(42)\n");
+        // Unsupported languages are left untouched
+        assert_eq!(traversal.files[2]["code"], "load_user is documented here");
+
+        // The mapping restores the original names
+        let restored = traversal
+            .anonymization_map
+            .iter()
+            .rev()
+            .fold(traversal.files[1]["code"].as_str().unwrap().to_string(), |code, (placeholder, original)| {
+                code.replace(placeholder, original)
+            });
+        assert_eq!(restored, "from a import load_user\n\nload_user(42)\n");
+    }
+
+    #[test]
+    fn test_keeps_rust_char_literals_apart_from_lifetimes() {
+        let mut anonymizer = Anonymizer::new();
+        let code = "fn first<'a>(text: &'a str) -> char {\n    'outer: for c in text.chars() {\n        if c == 'a' || c == '\\n' || c == '\\'' || c == '\\u{1F600}' || c == b'x' as char {\n            break 'outer;\n        }\n    }\n    'z'\n}\n";
+        let anonymized = anonymizer.anonymize(code, "rs");
+
+        assert_eq!(
+            anonymized,
+            "fn id_1<'id_2>(id_3: &'id_2 str) -> char {\n    'id_4: for id_5 in id_3.id_6() {\n        if id_5 == 'a' || id_5 == '\\n' || id_5 == '\\'' || id_5 == '\\u{1F600}' || id_5 == b'x' as char {\n            break 'id_4;\n        }\n    }\n    'z'\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_keeps_rust_raw_strings() {
+        let mut anonymizer = Anonymizer::new();
+        let code = "let pattern = r#\"a \"quoted\" name\"#;\nlet path = r\"C:\\dir\\\";\nlet bytes = br\"name\";\n";
+        assert_eq!(
+            anonymizer.anonymize(code, "rs"),
+            "let id_1 = r#\"a \"quoted\" name\"#;\nlet id_2 = r\"C:\\dir\\\";\nlet id_3 = br\"name\";\n"
+        );
+    }
+
+    #[test]
+    fn test_placeholders_skip_existing_names() {
+        let mut anonymizer = Anonymizer::new();
+        let anonymized = anonymizer.anonymize("let id_2 = count + id_1;\n", "rs");
+
+        // The existing names are renamed too, to placeholders that don't take their names
+        assert_eq!(anonymized, "let id_3 = id_4 + id_5;\n");
+        assert_eq!(anonymizer.deanonymize(&anonymized), "let id_2 = count + id_1;\n");
+    }
+
+    #[test]
+    fn test_anonymizes_the_tree_and_paths() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("billing/invoice.rs", "pub fn total() {}\n")
+            .with_file("main.rs", "mod billing;\n");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            anonymize: true,
+            ..Default::default()
+        };
+        let rendered = render_prompt(&source, &config).unwrap();
+
+        for name in ["project", "billing", "invoice", "total"] {
+            assert!(!rendered.prompt.contains(name), "{} in {}", name, rendered.prompt);
+        }
+        let placeholder = |name: &str| {
+            let found = rendered.anonymization_map.iter().find(|(_, original)| *original == name);
+            found.unwrap().0.clone()
+        };
+        // The module and its directory share a placeholder
+        let (root, billing, invoice) = (placeholder("project"), placeholder("billing"), placeholder("invoice"));
+        assert_eq!(rendered.files[0]["path"], format!("{}/{}/{}.rs", root, billing, invoice));
+        assert!(rendered.prompt.contains(&format!("mod {};", billing)));
+        assert!(rendered.prompt.contains(&format!("{}.rs", invoice)));
+        // Keywords such as `main` are kept
+        assert!(rendered.prompt.contains("main.rs"));
+    }
+}
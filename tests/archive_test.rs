@@ -0,0 +1,74 @@
+use code2prompt::{generate_prompt, read_zip, read_zip_with_limit, traverse_source, Code2PromptConfig, Traversal};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zip::write::SimpleFileOptions;
+
+    /// Builds a zip archive in memory with a directory entry, nested files and a hidden file.
+    fn zip_bytes() -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.add_directory("src/", options).unwrap();
+        writer.start_file("src/main.rs", options).unwrap();
+        writer.write_all(b"fn main() {}").unwrap();
+        writer.start_file("src/lib/util.rs", options).unwrap();
+        writer.write_all(b"pub fn util() {}").unwrap();
+        writer.start_file("notes.log", options).unwrap();
+        writer.write_all(b"noise").unwrap();
+        writer.start_file(".env", options).unwrap();
+        writer.write_all(b"SECRET=1").unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_traverse_zip_entries() {
+        let source = read_zip(Path::new("/virtual/code.zip"), &zip_bytes()).unwrap();
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/code.zip"),
+            ..Default::default()
+        };
+        let exclude = vec!["*.log".to_string()];
+        let Traversal { tree, files, .. } = traverse_source(&source, &config, &[], &exclude).unwrap();
+
+        assert!(tree.starts_with("code.zip"));
+        assert!(tree.contains("lib"));
+        assert!(!tree.contains(".env"));
+        let paths: Vec<&str> = files.iter().map(|f| f["path"].as_str().unwrap()).collect();
+        assert_eq!(
+            paths,
+            vec!["/virtual/code.zip/src/lib/util.rs", "/virtual/code.zip/src/main.rs"]
+        );
+        assert_eq!(files[1]["code"], "```rs\nfn main() {}\n```");
+    }
+
+    #[test]
+    fn test_zip_entries_are_capped_in_total() {
+        let root = Path::new("/virtual/code.zip");
+        // The visible entries hold 33 bytes, the hidden one is skipped unread
+        assert!(read_zip_with_limit(root, &zip_bytes(), 33).is_ok());
+        let error = read_zip_with_limit(root, &zip_bytes(), 32).err().unwrap();
+        assert!(error.to_string().contains("exceeds 32 bytes"));
+    }
+
+    #[test]
+    fn test_generate_prompt_from_zip_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("code.zip");
+        std::fs::write(&archive, zip_bytes()).unwrap();
+
+        let config = Code2PromptConfig {
+            path: archive,
+            relative_paths: true,
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let rendered = generate_prompt(&config).unwrap();
+        assert!(rendered.contains("Project Path: code.zip"));
+        assert!(rendered.contains("`code.zip/src/lib/util.rs`"));
+        assert!(rendered.contains("fn main() {}"));
+        assert!(!rendered.contains("SECRET"));
+    }
+}
@@ -0,0 +1,193 @@
+use code2prompt::budget::{
+    elide, render_within_byte_limit, render_within_token_limit, BudgetStrategy, DEFAULT_TRUNCATION_MARKER,
+};
+use code2prompt::token::get_dyn_tokenizer;
+use code2prompt::template::{handlebars_setup, render_template};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const TEMPLATE: &str = "Header\n{{#each files}}[{{path}}]\n{{code}}\n{{/each}}";
+
+    fn data() -> serde_json::Value {
+        json!({
+            "files": [
+                {"path": "a.rs", "code": "a".repeat(100)},
+                {"path": "b.rs", "code": "b".repeat(100)},
+                {"path": "c.rs", "code": "c".repeat(100)},
+            ]
+        })
+    }
+
+    #[test]
+    fn test_output_at_limit_is_untouched() {
+        let handlebars = handlebars_setup(TEMPLATE, "test").unwrap();
+        let full = render_template(&handlebars, "test", &data()).unwrap();
+
+        let (rendered, truncation) = render_within_byte_limit(
+            &handlebars,
+            "test",
+            &data(),
+            full.len(),
+            DEFAULT_TRUNCATION_MARKER,
+        )
+        .unwrap();
+        assert_eq!(rendered, full);
+        assert!(truncation.is_none());
+    }
+
+    #[test]
+    fn test_one_byte_over_limit_drops_trailing_file() {
+        let handlebars = handlebars_setup(TEMPLATE, "test").unwrap();
+        let full = render_template(&handlebars, "test", &data()).unwrap();
+
+        let (rendered, truncation) = render_within_byte_limit(
+            &handlebars,
+            "test",
+            &data(),
+            full.len() - 1,
+            DEFAULT_TRUNCATION_MARKER,
+        )
+        .unwrap();
+        let truncation = truncation.expect("Expected the output to be truncated");
+
+        assert!(rendered.len() < full.len());
+        assert_eq!(truncation.kept_files, 2);
+        assert_eq!(truncation.dropped_files, 1);
+        assert!(rendered.contains("[b.rs]"));
+        assert!(!rendered.contains("[c.rs]"));
+        assert!(rendered.contains("... [truncated] ... 1 file(s) omitted"));
+    }
+
+    #[test]
+    fn test_tiny_limit_cuts_content() {
+        let handlebars = handlebars_setup(TEMPLATE, "test").unwrap();
+
+        let (rendered, truncation) =
+            render_within_byte_limit(&handlebars, "test", &data(), 90, "<cut>").unwrap();
+        let truncation = truncation.expect("Expected the output to be truncated");
+
+        assert!(rendered.len() <= 90);
+        assert!(rendered.contains("<cut>"));
+        assert_eq!(truncation.kept_files, 0);
+        assert_eq!(truncation.dropped_files, 3);
+    }
+
+    #[test]
+    fn test_limit_smaller_than_note_keeps_marker() {
+        let handlebars = handlebars_setup(TEMPLATE, "test").unwrap();
+
+        let (rendered, _) =
+            render_within_byte_limit(&handlebars, "test", &data(), 12, "<cut>").unwrap();
+        assert!(rendered.len() <= 12);
+        assert!(rendered.ends_with("\n<cut>"));
+    }
+
+    #[test]
+    fn test_elide() {
+        assert_eq!(elide("short", 10, "<cut>"), "short");
+        assert_eq!(elide("a longer text", 10, "<cut>"), "a lo\n<cut>");
+        assert_eq!(elide("a longer text", 3, "<cut>"), "<cu");
+        // Cuts land on character boundaries
+        assert_eq!(elide("ééééé", 7, "~"), "éé\n~");
+    }
+
+    #[test]
+    fn test_sample_each_stays_within_a_tiny_limit() {
+        let handlebars = handlebars_setup(TEMPLATE, "test").unwrap();
+        let tokenizer = get_dyn_tokenizer(&Some("cl100k".to_string()));
+
+        for max_tokens in [0, 1, 3, 8] {
+            let (rendered, truncation) = render_within_token_limit(
+                &handlebars,
+                "test",
+                &data(),
+                max_tokens,
+                tokenizer.as_ref(),
+                BudgetStrategy::SampleEach,
+                DEFAULT_TRUNCATION_MARKER,
+            )
+            .unwrap();
+            assert!(tokenizer.count(&rendered) <= max_tokens);
+            assert!(truncation.is_some());
+        }
+    }
+
+    #[test]
+    fn test_sample_each_keeps_every_file() {
+        let code = |name: &str| (0..40).map(|i| format!("let {}_{} = {};", name, i, i)).collect::<Vec<_>>().join("\n");
+        let data = json!({
+            "files": [
+                {"path": "a.rs", "code": code("alpha")},
+                {"path": "b.rs", "code": code("beta")},
+                {"path": "c.rs", "code": code("gamma")},
+            ]
+        });
+        let handlebars = handlebars_setup(TEMPLATE, "test").unwrap();
+        let tokenizer = get_dyn_tokenizer(&Some("cl100k".to_string()));
+        let full_tokens = tokenizer.count(&render_template(&handlebars, "test", &data).unwrap());
+        let max_tokens = full_tokens / 3;
+
+        let (rendered, truncation) = render_within_token_limit(
+            &handlebars,
+            "test",
+            &data,
+            max_tokens,
+            tokenizer.as_ref(),
+            BudgetStrategy::SampleEach,
+            DEFAULT_TRUNCATION_MARKER,
+        )
+        .unwrap();
+        let truncation = truncation.expect("Expected the output to be truncated");
+
+        assert!(tokenizer.count(&rendered) <= max_tokens);
+        assert_eq!((truncation.kept_files, truncation.dropped_files, truncation.sampled_files), (3, 0, 3));
+        for (path, name) in [("[a.rs]", "alpha"), ("[b.rs]", "beta"), ("[c.rs]", "gamma")] {
+            assert!(rendered.contains(path));
+            // Each file keeps its head and tail
+            assert!(rendered.contains(&format!("let {}_0 = 0;", name)));
+            assert!(rendered.contains(&format!("let {}_39 = 39;", name)));
+        }
+        assert_eq!(rendered.matches(DEFAULT_TRUNCATION_MARKER).count(), 3);
+
+        // Dropping trailing files loses some of them instead
+        let (rendered, _) = render_within_token_limit(
+            &handlebars,
+            "test",
+            &data,
+            max_tokens,
+            tokenizer.as_ref(),
+            BudgetStrategy::DropTrailing,
+            DEFAULT_TRUNCATION_MARKER,
+        )
+        .unwrap();
+        assert!(!rendered.contains("[c.rs]"));
+    }
+
+    #[test]
+    fn test_drop_trailing_bounds_a_single_file_over_budget() {
+        let data = json!({"files": [{"path": "a.rs", "code": "let value = 1;\n".repeat(200)}]});
+        let handlebars = handlebars_setup(TEMPLATE, "test").unwrap();
+        let tokenizer = get_dyn_tokenizer(&Some("cl100k".to_string()));
+
+        for max_tokens in [0, 1, 5, 20, 100] {
+            let (rendered, truncation) = render_within_token_limit(
+                &handlebars,
+                "test",
+                &data,
+                max_tokens,
+                tokenizer.as_ref(),
+                BudgetStrategy::DropTrailing,
+                DEFAULT_TRUNCATION_MARKER,
+            )
+            .unwrap();
+            let truncation = truncation.expect("Expected the output to be truncated");
+
+            assert!(tokenizer.count(&rendered) <= max_tokens, "{} tokens over {}", tokenizer.count(&rendered), max_tokens);
+            assert_eq!((truncation.kept_files, truncation.dropped_files), (0, 1));
+            assert!(!rendered.contains("let value"));
+        }
+    }
+}
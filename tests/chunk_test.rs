@@ -0,0 +1,172 @@
+use code2prompt::chunk::{chunk_files, chunk_prompt_exact};
+use code2prompt::token::{get_dyn_tokenizer, Tokenizer};
+use code2prompt::{render_prompt, Code2PromptConfig, MemorySource};
+use std::path::PathBuf;
+use serde_json::json;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts one token per word, so that the budgets are easy to follow.
+    struct Words;
+
+    impl Tokenizer for Words {
+        fn count(&self, text: &str) -> usize {
+            text.split_whitespace().count()
+        }
+    }
+
+    fn file(path: &str, code: &str) -> serde_json::Value {
+        json!({ "path": path, "code": code })
+    }
+
+    #[test]
+    fn test_chunks_keep_whole_files() {
+        // Each file is 1 token of path and 4 tokens of code
+        let files = vec![
+            file("a.rs", "one two\nthree four"),
+            file("b.rs", "one two\nthree four"),
+            file("c.rs", "one two\nthree four"),
+        ];
+        let chunks = chunk_files(&files, 12, 0, &Words).unwrap();
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].files, vec!["a.rs", "b.rs"]);
+        assert_eq!((chunks[0].start_token, chunks[0].end_token), (0, 10));
+        assert_eq!(chunks[1].files, vec!["c.rs"]);
+        assert_eq!((chunks[1].start_token, chunks[1].end_token), (10, 15));
+        assert!(chunks[1].content.starts_with("`c.rs`:\n\none two\n"));
+    }
+
+    #[test]
+    fn test_oversized_file_is_split_with_overlap() {
+        let code = (1..=10).map(|n| format!("line {}", n)).collect::<Vec<_>>().join("\n");
+        let files = vec![file("big.rs", &code)];
+        // 21 tokens: the path, then 2 tokens per line
+        let chunks = chunk_files(&files, 8, 4, &Words).unwrap();
+
+        assert!(chunks.len() > 1);
+        for pair in chunks.windows(2) {
+            let (previous, next) = (&pair[0], &pair[1]);
+            assert_eq!(next.start_token, previous.end_token - 4);
+            assert!(next.end_token > previous.end_token);
+            // The last two lines of a chunk start the next one
+            let shared: Vec<&str> = previous.content.lines().rev().take(2).collect();
+            let carried: Vec<&str> = next.content.lines().take(2).collect();
+            assert_eq!(shared.into_iter().rev().collect::<Vec<_>>(), carried);
+            assert_eq!(next.files, vec!["big.rs"]);
+        }
+        assert!(chunks.iter().all(|chunk| chunk.end_token - chunk.start_token <= 8));
+        assert_eq!(chunks.last().unwrap().end_token, 21);
+        assert!(chunks.last().unwrap().content.contains("line 10"));
+    }
+
+    #[test]
+    fn test_overlap_must_be_lower_than_the_chunk_size() {
+        let files = vec![file("a.rs", "fn main() {}")];
+        assert!(chunk_files(&files, 10, 10, &Words).is_err());
+        assert!(chunk_files(&files, 10, 9, &Words).is_ok());
+    }
+
+    /// Counts one token per word, and one more per blank line separator, so that a text counts more tokens
+    /// than the sum of its lines.
+    struct Separators;
+
+    impl Tokenizer for Separators {
+        fn count(&self, text: &str) -> usize {
+            text.split_whitespace().count() + text.matches("\n\n").count()
+        }
+    }
+
+    #[test]
+    fn test_chunks_count_their_whole_text() {
+        // Each file is 5 tokens line by line, and 7 as a whole
+        let files = vec![
+            file("a.rs", "one two\nthree four"),
+            file("b.rs", "one two\nthree four"),
+            file("c.rs", "one two\nthree four"),
+        ];
+        let chunks = chunk_files(&files, 10, 0, &Separators).unwrap();
+
+        assert_eq!(chunks.len(), 3);
+        for chunk in &chunks {
+            assert!(Separators.count(&chunk.content) <= 10, "{:?}", chunk.content);
+        }
+        let joined: String = chunks.iter().map(|chunk| chunk.content.as_str()).collect();
+        assert!(joined.contains("`b.rs`:\n\none two\nthree four\n\n"));
+    }
+
+    #[test]
+    fn test_long_line_is_cut_into_token_windows() {
+        let code = (1..=30).map(|n| format!("w{}", n)).collect::<Vec<_>>().join(" ");
+        let files = vec![file("min.js", &code)];
+        let chunks = chunk_files(&files, 8, 2, &Words).unwrap();
+
+        assert!(chunks.len() > 3);
+        assert!(chunks.iter().all(|chunk| Words.count(&chunk.content) <= 8));
+        assert!(chunks.last().unwrap().content.contains("w30"));
+
+        let chunks = chunk_files(&files, 8, 0, &Words).unwrap();
+        let joined: String = chunks.iter().map(|chunk| chunk.content.as_str()).collect();
+        assert_eq!(joined, format!("`min.js`:\n\n{}\n\n", code));
+    }
+
+    #[test]
+    fn test_exact_chunks_are_within_the_budget_and_lossless() {
+        let tokenizer = get_dyn_tokenizer(&Some("cl100k".to_string()));
+        let files = vec![
+            file("main.rs", &"fn main() {\n    println!(\"Hello, world!\");\n}\n".repeat(20)),
+            file("notes.md", &"Des notes en français, avec des caractères accentués: é, è, ü, 日本語.\n".repeat(10)),
+        ];
+        let prompt: String = files
+            .iter()
+            .map(|f| format!("Path: {}\n\n{}\n", f["path"].as_str().unwrap(), f["code"].as_str().unwrap()))
+            .collect();
+        let chunks = chunk_prompt_exact(&prompt, &files, 50, 0, tokenizer.as_ref()).unwrap();
+
+        assert!(chunks.len() > 2);
+        for chunk in &chunks {
+            assert!(chunk.end_token - chunk.start_token <= 50);
+        }
+        let joined: String = chunks.iter().map(|chunk| chunk.content.as_str()).collect();
+        assert_eq!(joined, prompt);
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[1].start_token, pair[0].end_token);
+        }
+        assert_eq!(chunks.last().unwrap().end_token, tokenizer.count(&prompt));
+        assert_eq!(chunks[0].files, vec!["main.rs"]);
+        assert_eq!(chunks.last().unwrap().files, vec!["notes.md"]);
+
+        // Overlapping chunks start before the end of the previous one
+        let chunks = chunk_prompt_exact(&prompt, &files, 50, 10, tokenizer.as_ref()).unwrap();
+        for pair in chunks.windows(2) {
+            assert!(pair[1].start_token < pair[0].end_token);
+        }
+        assert!(chunks.iter().all(|chunk| chunk.end_token - chunk.start_token <= 50));
+    }
+
+    #[test]
+    fn test_exact_chunks_of_the_rendered_prompt() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("main.rs", "fn main() {\n    helper();\n}\n".repeat(30))
+            .with_file("helper.rs", "pub fn helper() {}\n".repeat(30));
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            chunk_tokens: Some(64),
+            chunk_exact: true,
+            ..Default::default()
+        };
+        let prompt = render_prompt(&source, &config).unwrap();
+
+        assert!(prompt.chunks.len() > 2);
+        let joined: String = prompt.chunks.iter().map(|chunk| chunk.content.as_str()).collect();
+        assert_eq!(joined, prompt.prompt);
+    }
+
+    #[test]
+    fn test_exact_chunks_need_the_tokens() {
+        let files = vec![file("a.rs", "fn main() {}")];
+        assert!(chunk_prompt_exact("fn main() {}", &files, 10, 0, &Words).is_err());
+    }
+}
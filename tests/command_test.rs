@@ -0,0 +1,105 @@
+use code2prompt::command::{redact_vars, run_commands};
+use code2prompt::spec::load_spec;
+use code2prompt::{generate_prompt, Code2PromptConfig};
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{Duration, Instant};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_run_commands_captures_output() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("marker.txt"), "").unwrap();
+
+        let outputs = run_commands(
+            &["ls".to_string(), "echo oops >&2; exit 3".to_string()],
+            dir.path(),
+            Duration::from_secs(10),
+        )
+        .unwrap();
+
+        assert_eq!(outputs[0]["stdout"], "marker.txt\n");
+        assert_eq!(outputs[0]["exit_code"], 0);
+        assert_eq!(outputs[1]["stderr"], "oops\n");
+        assert_eq!(outputs[1]["exit_code"], 3);
+        assert_eq!(outputs[1]["timed_out"], false);
+    }
+
+    #[test]
+    fn test_run_commands_timeout() {
+        let dir = tempdir().unwrap();
+        let start = Instant::now();
+        let outputs = run_commands(
+            &["echo started; sleep 5".to_string()],
+            dir.path(),
+            Duration::from_millis(200),
+        )
+        .unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(4));
+        assert_eq!(outputs[0]["timed_out"], true);
+        assert!(outputs[0]["exit_code"].is_null());
+    }
+
+    #[test]
+    fn test_redact_env() {
+        let vars = [
+            ("CODE2PROMPT_TEST_API_TOKEN".into(), "s3cr3t-value".into()),
+            ("HOME".into(), "/home/user".into()),
+        ];
+        let redacted = redact_vars("token is s3cr3t-value here, in /home/user", vars);
+        assert_eq!(redacted, "token is [REDACTED:CODE2PROMPT_TEST_API_TOKEN] here, in /home/user");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_redact_env_skips_non_utf8_entries() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let vars = [("CODE2PROMPT_TEST_BINARY_SECRET".into(), OsStr::from_bytes(b"\xff\xfe-value").to_os_string())];
+        assert_eq!(redact_vars("nothing to hide", vars), "nothing to hide");
+    }
+
+    #[test]
+    fn test_generate_prompt_includes_command_outputs() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            no_clipboard: true,
+            commands: vec!["echo build failed: missing semicolon".to_string()],
+            allow_commands: true,
+            ..Default::default()
+        };
+        let rendered = generate_prompt(&config).unwrap();
+
+        assert!(rendered.contains("Command Outputs:"));
+        assert!(rendered.contains("`$ echo build failed: missing semicolon` (exit code: 0):"));
+        assert!(rendered.contains("build failed: missing semicolon\n"));
+    }
+    #[test]
+    fn test_commands_require_an_explicit_opt_in() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+        let marker = dir.path().join("ran.txt");
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            no_clipboard: true,
+            commands: vec![format!("touch {}", marker.display())],
+            ..Default::default()
+        };
+        assert!(generate_prompt(&config).is_err());
+        assert!(!marker.exists());
+
+        // A spec can list commands, but not allow them
+        let spec = dir.path().join("spec.toml");
+        fs::write(&spec, "commands = [\"true\"]\nallow_commands = true\n").unwrap();
+        assert!(load_spec(&spec).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use code2prompt::{generate_prompt, render_prompt, Code2PromptConfig, Code2PromptError, FsSource, MemorySource};
+use std::fs;
+use std::path::PathBuf;
+use tempfile::tempdir;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn virtual_config() -> Code2PromptConfig {
+        Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            ..Default::default()
+        }
+    }
+
+    fn source() -> MemorySource {
+        MemorySource::new("/virtual/project").with_file("main.rs", "fn main() {}")
+    }
+
+    #[test]
+    fn test_missing_template() {
+        let config = Code2PromptConfig {
+            template: Some(PathBuf::from("/nonexistent/template.hbs")),
+            ..virtual_config()
+        };
+        match render_prompt(&source(), &config).unwrap_err() {
+            Code2PromptError::TemplateNotFound { path, error } => {
+                assert_eq!(path, PathBuf::from("/nonexistent/template.hbs"));
+                assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+            }
+            error => panic!("Unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn test_two_branch_validation() {
+        for (git_diff_branch, git_log_branch, option) in [
+            (Some("main"), None, "git_diff_branch"),
+            (None, Some("main,dev,feature"), "git_log_branch"),
+        ] {
+            let config = Code2PromptConfig {
+                git_diff_branch: git_diff_branch.map(str::to_string),
+                git_log_branch: git_log_branch.map(str::to_string),
+                ..virtual_config()
+            };
+            let error = render_prompt(&source(), &config).unwrap_err();
+            assert_eq!(error.to_string(), "Please provide exactly two branches separated by a comma.");
+            match error {
+                Code2PromptError::InvalidBranches { option: actual, .. } => assert_eq!(actual, option),
+                error => panic!("Unexpected error: {:?}", error),
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_variants() {
+        let config = Code2PromptConfig {
+            header_pattern: Some("(unclosed".to_string()),
+            ..virtual_config()
+        };
+        match render_prompt(&source(), &config).unwrap_err() {
+            Code2PromptError::InvalidPattern { kind, pattern, .. } => {
+                assert_eq!((kind, pattern.as_str()), ("header", "(unclosed"));
+            }
+            error => panic!("Unexpected error: {:?}", error),
+        }
+
+        let config = Code2PromptConfig {
+            template_content: Some("{{#if}}".to_string()),
+            ..virtual_config()
+        };
+        assert!(matches!(render_prompt(&source(), &config), Err(Code2PromptError::Template(_))));
+
+        let config = Code2PromptConfig {
+            max_tokens: Some(100),
+            max_output_bytes: Some(600),
+            ..virtual_config()
+        };
+        assert!(matches!(render_prompt(&source(), &config), Err(Code2PromptError::InvalidOptions(_))));
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            require_git: true,
+            no_clipboard: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            generate_prompt(&config),
+            Err(Code2PromptError::NotAGitRepository { option: "require_git", .. })
+        ));
+
+        // Other failures keep their message and context
+        let config = Code2PromptConfig {
+            path: dir.path().join("missing"),
+            ..Default::default()
+        };
+        let error = render_prompt(&FsSource, &config).unwrap_err();
+        assert!(matches!(error, Code2PromptError::Other(_)), "{:?}", error);
+        assert!(!error.to_string().is_empty());
+    }
+}
@@ -1,8 +1,13 @@
-use code2prompt::git::{get_git_diff, get_git_diff_between_branches, get_git_log};
+use code2prompt::git::{
+    get_commit_counts, get_contributors, get_git_changes, get_git_diff, get_git_diff_stat, get_git_diff_structured,
+    get_git_diff_with_settings, DiffAlgorithm, DiffSettings, get_git_diff_between_branches, get_git_head, get_git_log,
+};
+use code2prompt::Code2PromptConfig;
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use code2prompt::DiffLineKind;
     use git2::{Repository, RepositoryInitOptions, Signature};
     use std::fs;
     use tempfile::TempDir;
@@ -264,4 +269,715 @@ mod tests {
         assert!(log.contains("First commit in development"));
         assert!(log.contains("Second commit in development"));
     }
+
+    #[test]
+    fn test_get_git_head() {
+        // Create a temporary directory
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path();
+
+        // Initialize a new Git repository
+        let mut binding = RepositoryInitOptions::new();
+        let init_options = binding.initial_head("master");
+        let repo = Repository::init_opts(repo_path, init_options)
+            .expect("Failed to initialize repository");
+
+        // Without any commit there is no HEAD to report
+        assert!(get_git_head(repo_path).is_err());
+
+        // Create and commit a file
+        let file_path = repo_path.join("test_file.txt");
+        fs::write(&file_path, "Initial content").expect("Failed to write to test file");
+
+        let mut index = repo.index().expect("Failed to get repository index");
+        index
+            .add_path(file_path.strip_prefix(repo_path).unwrap())
+            .expect("Failed to add file to index");
+        index.write().expect("Failed to write index");
+
+        let tree_id = index.write_tree().expect("Failed to write tree");
+        let tree = repo.find_tree(tree_id).expect("Failed to find tree");
+        let signature =
+            Signature::now("Test", "test@example.com").expect("Failed to create signature");
+
+        let commit = repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "Initial commit",
+                &tree,
+                &[],
+            )
+            .expect("Failed to commit");
+
+        // Get the head from a subdirectory of the repository
+        let sub_dir = repo_path.join("sub");
+        fs::create_dir_all(&sub_dir).expect("Failed to create subdirectory");
+        let (short_sha, branch) = get_git_head(&sub_dir).expect("Failed to get git head");
+
+        assert_eq!(short_sha, commit.to_string()[..7]);
+        assert_eq!(branch, "master");
+    }
+
+    #[test]
+    fn test_get_git_changes() {
+        // Create a temporary directory
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path();
+
+        // Initialize a new Git repository
+        let repo = Repository::init(repo_path).expect("Failed to initialize repository");
+
+        // Commit three files
+        for name in ["kept.txt", "modified.txt", "deleted.txt"] {
+            fs::write(repo_path.join(name), format!("{} content\n", name))
+                .expect("Failed to write to test file");
+        }
+        let mut index = repo.index().expect("Failed to get repository index");
+        index
+            .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+            .expect("Failed to add files to index");
+        index.write().expect("Failed to write index");
+
+        let tree_id = index.write_tree().expect("Failed to write tree");
+        let tree = repo.find_tree(tree_id).expect("Failed to find tree");
+        let signature =
+            Signature::now("Test", "test@example.com").expect("Failed to create signature");
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial commit", &tree, &[])
+            .expect("Failed to commit");
+
+        // Modify, delete and add files in the working tree
+        fs::write(repo_path.join("modified.txt"), "new content\n").expect("Failed to modify file");
+        fs::remove_file(repo_path.join("deleted.txt")).expect("Failed to delete file");
+        fs::write(repo_path.join("added.txt"), "added content\n").expect("Failed to add file");
+
+        let changes = get_git_changes(repo_path, "HEAD").expect("Failed to get git changes");
+        let statuses: Vec<(String, String)> = changes
+            .iter()
+            .map(|c| (c.path.display().to_string(), c.status.clone()))
+            .collect();
+
+        assert_eq!(
+            statuses,
+            vec![
+                ("added.txt".to_string(), "added".to_string()),
+                ("deleted.txt".to_string(), "deleted".to_string()),
+                ("modified.txt".to_string(), "modified".to_string()),
+            ]
+        );
+        assert!(changes[2].diff.contains("-modified.txt content"));
+        assert!(changes[2].diff.contains("+new content"));
+        assert!(changes[1].diff.contains("-deleted.txt content"));
+    }
+
+    #[test]
+    fn test_get_commit_counts() {
+        // Create a temporary directory
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path();
+
+        // Initialize a new Git repository
+        let repo = Repository::init(repo_path).expect("Failed to initialize repository");
+        let signature =
+            Signature::now("Test", "test@example.com").expect("Failed to create signature");
+        fs::create_dir(repo_path.join("src")).expect("Failed to create directory");
+
+        // Commit a frequently changed file and a file changed once
+        let history = [
+            ("src/hot.rs", "1"),
+            ("src/cold.rs", "1"),
+            ("src/hot.rs", "2"),
+            ("src/hot.rs", "3"),
+        ];
+        for (name, content) in history {
+            fs::write(repo_path.join(name), content).expect("Failed to write to test file");
+            let mut index = repo.index().expect("Failed to get repository index");
+            index
+                .add_path(std::path::Path::new(name))
+                .expect("Failed to add file to index");
+            index.write().expect("Failed to write index");
+
+            let tree_id = index.write_tree().expect("Failed to write tree");
+            let tree = repo.find_tree(tree_id).expect("Failed to find tree");
+            let parents: Vec<git2::Commit> = repo
+                .head()
+                .ok()
+                .and_then(|head| head.peel_to_commit().ok())
+                .into_iter()
+                .collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            repo.commit(Some("HEAD"), &signature, &signature, name, &tree, &parents)
+                .expect("Failed to commit");
+        }
+
+        let counts = get_commit_counts(repo_path).expect("Failed to count commits");
+        assert_eq!(counts[&std::path::PathBuf::from("src/hot.rs")], 3);
+        assert_eq!(counts[&std::path::PathBuf::from("src/cold.rs")], 1);
+
+        // Paths are relative to the requested directory
+        let counts = get_commit_counts(&repo_path.join("src")).expect("Failed to count commits");
+        assert_eq!(counts[&std::path::PathBuf::from("hot.rs")], 3);
+        assert_eq!(counts.len(), 2);
+
+        // Files are annotated and sorted by their commit count
+        let config = code2prompt::Code2PromptConfig {
+            path: repo_path.to_path_buf(),
+            sort_by_churn: true,
+            ..Default::default()
+        };
+        let prompt = code2prompt::render_prompt(&code2prompt::FsSource, &config)
+            .expect("Failed to render prompt");
+        let files: Vec<(&str, u64)> = prompt
+            .files
+            .iter()
+            .map(|f| (f["path"].as_str().unwrap(), f["commit_count"].as_u64().unwrap()))
+            .collect();
+        assert!(files[0].0.ends_with("hot.rs"));
+        assert_eq!(files[0].1, 3);
+        assert!(files[1].0.ends_with("cold.rs"));
+        assert_eq!(files[1].1, 1);
+    }
+
+    #[test]
+    fn test_get_git_diff_ignore_whitespace() {
+        // Create a temporary directory
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path();
+
+        // Initialize a new Git repository and commit a file
+        let repo = Repository::init(repo_path).expect("Failed to initialize repository");
+        let file_path = repo_path.join("main.rs");
+        fs::write(&file_path, "fn main() {\n    run();\n}\n").expect("Failed to write to test file");
+
+        let mut index = repo.index().expect("Failed to get repository index");
+        index
+            .add_path(std::path::Path::new("main.rs"))
+            .expect("Failed to add file to index");
+        index.write().expect("Failed to write index");
+        let tree_id = index.write_tree().expect("Failed to write tree");
+        let tree = repo.find_tree(tree_id).expect("Failed to find tree");
+        let signature =
+            Signature::now("Test", "test@example.com").expect("Failed to create signature");
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial commit", &tree, &[])
+            .expect("Failed to commit");
+
+        // Stage a whitespace-only change
+        fs::write(&file_path, "fn main() {\n        run();\n}\n").expect("Failed to modify file");
+        let mut index = repo.index().expect("Failed to get repository index");
+        index
+            .add_path(std::path::Path::new("main.rs"))
+            .expect("Failed to add file to index");
+        index.write().expect("Failed to write index");
+
+        let settings = DiffSettings {
+            ignore_whitespace: false,
+            algorithm: "patience".parse().expect("Failed to parse algorithm"),
+        };
+        let diff = get_git_diff_with_settings(repo_path, &settings).expect("Failed to get git diff");
+        assert!(diff.contains("        run();"));
+
+        let settings = DiffSettings {
+            ignore_whitespace: true,
+            ..settings
+        };
+        let diff = get_git_diff_with_settings(repo_path, &settings).expect("Failed to get git diff");
+        assert!(!diff.contains("run();"));
+
+        assert_eq!("Patience".parse::<DiffAlgorithm>().unwrap(), DiffAlgorithm::Patience);
+        assert!("fastest".parse::<DiffAlgorithm>().is_err());
+        assert!("histogram".parse::<DiffAlgorithm>().is_err());
+        assert!(Code2PromptConfig::default().diff_ignore_whitespace());
+    }
+
+    #[test]
+    fn test_get_git_diff_structured() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path();
+        let repo = Repository::init(repo_path).expect("Failed to initialize repository");
+        let lines: Vec<String> = (1..=10).map(|i| format!("line {}", i)).collect();
+        fs::write(repo_path.join("a.txt"), lines.join("\n") + "\n").expect("Failed to write test file");
+        fs::write(repo_path.join("b.txt"), "alpha\nbeta\n").expect("Failed to write test file");
+
+        let stage = || {
+            let mut index = repo.index().expect("Failed to get repository index");
+            for name in ["a.txt", "b.txt"] {
+                index
+                    .add_path(std::path::Path::new(name))
+                    .expect("Failed to add file to index");
+            }
+            index.write().expect("Failed to write index");
+            index.write_tree().expect("Failed to write tree")
+        };
+        let tree = repo.find_tree(stage()).expect("Failed to find tree");
+        let signature =
+            Signature::now("Test", "test@example.com").expect("Failed to create signature");
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial commit", &tree, &[])
+            .expect("Failed to commit");
+
+        // Change the middle line of the first file and append a line to the second one
+        let changed = lines.join("\n").replace("line 5", "line five") + "\n";
+        fs::write(repo_path.join("a.txt"), changed).expect("Failed to modify test file");
+        fs::write(repo_path.join("b.txt"), "alpha\nbeta\ngamma\n").expect("Failed to modify test file");
+        stage();
+
+        let files =
+            get_git_diff_structured(repo_path, &DiffSettings::default()).expect("Failed to get git diff");
+        assert_eq!(files.len(), 2);
+
+        assert_eq!(files[0].path(), "a.txt");
+        assert_eq!(files[0].hunks.len(), 1);
+        let hunk = &files[0].hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (2, 7, 2, 7));
+        let removed: Vec<&str> = hunk
+            .lines
+            .iter()
+            .filter(|line| line.kind == DiffLineKind::Removed)
+            .map(|line| line.content.as_str())
+            .collect();
+        assert_eq!(removed, vec!["line 5"]);
+
+        assert_eq!(files[1].path(), "b.txt");
+        let hunk = &files[1].hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (1, 2, 1, 3));
+        assert_eq!(hunk.lines.last().unwrap().kind, DiffLineKind::Added);
+        assert_eq!(hunk.lines.last().unwrap().content, "gamma");
+    }
+
+    #[test]
+    fn test_get_git_diff_stat() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path();
+        let repo = Repository::init(repo_path).expect("Failed to initialize repository");
+        fs::write(repo_path.join("a.txt"), "one\ntwo\nthree\n").expect("Failed to write test file");
+
+        let stage = |names: &[&str]| {
+            let mut index = repo.index().expect("Failed to get repository index");
+            for name in names {
+                index
+                    .add_path(std::path::Path::new(name))
+                    .expect("Failed to add file to index");
+            }
+            index.write().expect("Failed to write index");
+            index.write_tree().expect("Failed to write tree")
+        };
+        let tree = repo.find_tree(stage(&["a.txt"])).expect("Failed to find tree");
+        let signature =
+            Signature::now("Test", "test@example.com").expect("Failed to create signature");
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial commit", &tree, &[])
+            .expect("Failed to commit");
+
+        // Change a line of the committed file and add a new one
+        fs::write(repo_path.join("a.txt"), "one\n2\nthree\n").expect("Failed to modify test file");
+        fs::write(repo_path.join("c.txt"), "four\nfive\n").expect("Failed to write test file");
+        stage(&["a.txt", "c.txt"]);
+
+        let stat = get_git_diff_stat(repo_path, &DiffSettings::default()).expect("Failed to get diff stat");
+        assert!(stat.contains("a.txt | 2 +-"), "Unexpected stat: {}", stat);
+        assert!(stat.contains("c.txt | 2 ++"), "Unexpected stat: {}", stat);
+        assert!(stat.contains("2 files changed, 3 insertions(+), 1 deletion(-)"), "Unexpected stat: {}", stat);
+
+        // The default template shows the summary above the diffs
+        let config = code2prompt::Code2PromptConfig {
+            path: repo_path.to_path_buf(),
+            diff: true,
+            diff_stat: true,
+            ..Default::default()
+        };
+        let prompt = code2prompt::render_prompt(&code2prompt::FsSource, &config)
+            .expect("Failed to render prompt")
+            .prompt;
+        let summary = prompt.find("Git Diff Stat:").expect("Missing diff stat");
+        assert!(summary < prompt.find("Git Diffs:").expect("Missing diffs"));
+        assert!(prompt[summary..].contains("2 files changed"));
+    }
+
+    #[test]
+    fn test_git_tracked_only() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path();
+        let repo = Repository::init(repo_path).expect("Failed to initialize repository");
+        fs::create_dir(repo_path.join("src")).expect("Failed to create directory");
+        fs::write(repo_path.join("src/tracked.rs"), "fn tracked() {}").expect("Failed to write test file");
+        fs::write(repo_path.join("src/untracked.rs"), "fn untracked() {}").expect("Failed to write test file");
+        fs::create_dir(repo_path.join("scratch")).expect("Failed to create directory");
+        fs::write(repo_path.join("scratch/notes.rs"), "fn notes() {}").expect("Failed to write test file");
+
+        let mut index = repo.index().expect("Failed to get repository index");
+        index
+            .add_path(std::path::Path::new("src/tracked.rs"))
+            .expect("Failed to add file to index");
+        index.write().expect("Failed to write index");
+
+        let config = code2prompt::Code2PromptConfig {
+            path: repo_path.to_path_buf(),
+            git_tracked_only: true,
+            ..Default::default()
+        };
+        let rendered = code2prompt::render_prompt(&code2prompt::FsSource, &config).expect("Failed to render prompt");
+        assert_eq!(rendered.files.len(), 1);
+        assert!(rendered.prompt.contains("fn tracked() {}"));
+        assert!(!rendered.prompt.contains("untracked.rs"));
+        assert!(!rendered.prompt.contains("scratch"));
+
+        // Without the flag, untracked files are included
+        let config = code2prompt::Code2PromptConfig {
+            git_tracked_only: false,
+            ..config
+        };
+        let rendered = code2prompt::render_prompt(&code2prompt::FsSource, &config).expect("Failed to render prompt");
+        assert_eq!(rendered.files.len(), 3);
+    }
+
+    #[test]
+    fn test_uncommitted_and_branch_diffs_together() {
+        let temp_dir = TempDir::new().expect("Failed to create a temp directory");
+        let repo_path = temp_dir.path();
+        let mut options = RepositoryInitOptions::new();
+        options.initial_head("master");
+        let repo = Repository::init_opts(repo_path, &options).expect("Failed to initialize repository");
+        let signature = Signature::now("Test", "test@example.com").expect("Failed to create signature");
+
+        // Commits the given content of `a.rs` on top of HEAD, without moving HEAD when `update_ref` is unset
+        let commit = |content: &str, update_ref: Option<&str>| {
+            fs::write(repo_path.join("a.rs"), content).expect("Failed to write test file");
+            let mut index = repo.index().expect("Failed to get index");
+            index.add_path(std::path::Path::new("a.rs")).expect("Failed to add file");
+            index.write().expect("Failed to write index");
+            let tree = repo.find_tree(index.write_tree().expect("Failed to write tree")).unwrap();
+            let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+            let parents: Vec<&git2::Commit> = parent.iter().collect();
+            repo.commit(update_ref, &signature, &signature, "Commit", &tree, &parents)
+                .expect("Failed to commit")
+        };
+        commit("fn base() {}\n", Some("HEAD"));
+        let feature = commit("fn feature() {}\n", None);
+        repo.branch("development", &repo.find_commit(feature).unwrap(), false)
+            .expect("Failed to create branch");
+
+        // Stage an uncommitted change on master
+        fs::write(repo_path.join("a.rs"), "fn uncommitted() {}\n").expect("Failed to write test file");
+        let mut index = repo.index().expect("Failed to get index");
+        index.add_path(std::path::Path::new("a.rs")).expect("Failed to add file");
+        index.write().expect("Failed to write index");
+
+        let config = code2prompt::Code2PromptConfig {
+            path: repo_path.to_path_buf(),
+            diff: true,
+            git_diff_branch: Some("master,development".to_string()),
+            ..Default::default()
+        };
+        let prompt = code2prompt::render_prompt(&code2prompt::FsSource, &config)
+            .expect("Failed to render prompt")
+            .prompt;
+
+        let uncommitted = prompt.find("Uncommitted changes:").expect("Missing uncommitted changes");
+        let branches = prompt
+            .find("Changes between master and development:")
+            .expect("Missing branch changes");
+        assert!(uncommitted < branches);
+        assert!(prompt[uncommitted..branches].contains("fn uncommitted() {}"));
+        assert!(prompt[branches..].contains("fn feature() {}"));
+    }
+
+    #[test]
+    fn test_require_git() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        fs::write(temp_dir.path().join("main.rs"), "fn main() {}").expect("Failed to write test file");
+        let config = code2prompt::Code2PromptConfig {
+            path: temp_dir.path().to_path_buf(),
+            require_git: true,
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let error = code2prompt::generate_prompt(&config).expect_err("Expected an error outside of a git repository");
+        assert!(error.to_string().starts_with("Not inside a git repository"));
+
+        // A subdirectory of a repository is inside it
+        Repository::init(temp_dir.path()).expect("Failed to initialize repository");
+        fs::create_dir(temp_dir.path().join("src")).expect("Failed to create directory");
+        fs::write(temp_dir.path().join("src/lib.rs"), "pub fn lib() {}").expect("Failed to write test file");
+        let config = code2prompt::Code2PromptConfig {
+            path: temp_dir.path().join("src"),
+            ..config
+        };
+        let prompt = code2prompt::generate_prompt(&config).expect("Failed to generate prompt");
+        assert!(prompt.contains("pub fn lib() {}"));
+    }
+
+    #[test]
+    fn test_git_status_filter() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path();
+        let repo = Repository::init(repo_path).expect("Failed to initialize repository");
+        let signature = Signature::now("Test", "test@example.com").expect("Failed to create signature");
+        fs::write(repo_path.join("modified.rs"), "fn before() {}").expect("Failed to write test file");
+        fs::write(repo_path.join("unchanged.rs"), "fn unchanged() {}").expect("Failed to write test file");
+        let mut index = repo.index().expect("Failed to get index");
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .expect("Failed to add files");
+        index.write().expect("Failed to write index");
+        let tree = repo.find_tree(index.write_tree().expect("Failed to write tree")).unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial commit", &tree, &[])
+            .expect("Failed to commit");
+
+        fs::write(repo_path.join("modified.rs"), "fn after() {}").expect("Failed to modify file");
+        fs::write(repo_path.join("untracked.rs"), "fn untracked() {}").expect("Failed to write test file");
+
+        let config = code2prompt::Code2PromptConfig {
+            path: repo_path.to_path_buf(),
+            git_status_filter: Some("M".to_string()),
+            ..Default::default()
+        };
+        let rendered = code2prompt::render_prompt(&code2prompt::FsSource, &config).expect("Failed to render prompt");
+        assert_eq!(rendered.files.len(), 1);
+        assert_eq!(rendered.files[0]["git_status"], " M");
+        assert!(rendered.prompt.contains("fn after() {}"));
+        assert!(!rendered.prompt.contains("fn unchanged() {}"));
+        assert!(!rendered.prompt.contains("fn untracked() {}"));
+
+        let config = code2prompt::Code2PromptConfig {
+            git_status_filter: Some("??".to_string()),
+            ..config
+        };
+        let rendered = code2prompt::render_prompt(&code2prompt::FsSource, &config).expect("Failed to render prompt");
+        assert_eq!(rendered.files.len(), 1);
+        assert!(rendered.prompt.contains("fn untracked() {}"));
+
+        // Outside of a git repository, the filter is an error
+        let outside = TempDir::new().expect("Failed to create temp dir");
+        fs::write(outside.path().join("main.rs"), "fn main() {}").expect("Failed to write test file");
+        let config = code2prompt::Code2PromptConfig {
+            path: outside.path().to_path_buf(),
+            ..config
+        };
+        assert!(code2prompt::render_prompt(&code2prompt::FsSource, &config).is_err());
+    }
+
+    #[test]
+    fn test_sort_files_by_recency() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path();
+        let repo = Repository::init(repo_path).expect("Failed to initialize repository");
+
+        // Commits the file at the given time, in seconds
+        let commit = |name: &str, seconds: i64| {
+            fs::write(repo_path.join(name), format!("// {} at {}", name, seconds)).expect("Failed to write test file");
+            let mut index = repo.index().expect("Failed to get index");
+            index.add_path(std::path::Path::new(name)).expect("Failed to add file");
+            index.write().expect("Failed to write index");
+            let tree = repo.find_tree(index.write_tree().expect("Failed to write tree")).unwrap();
+            let signature = Signature::new("Test", "test@example.com", &git2::Time::new(seconds, 0))
+                .expect("Failed to create signature");
+            let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+            let parents: Vec<&git2::Commit> = parent.iter().collect();
+            repo.commit(Some("HEAD"), &signature, &signature, "Commit", &tree, &parents)
+                .expect("Failed to commit");
+        };
+        commit("a.rs", 1_700_000_000);
+        commit("b.rs", 1_700_000_100);
+        commit("c.rs", 1_700_000_200);
+        commit("a.rs", 1_700_000_300);
+
+        let config = code2prompt::Code2PromptConfig {
+            path: repo_path.to_path_buf(),
+            sort_files: Some(code2prompt::FileSort::ByRecency),
+            ..Default::default()
+        };
+        let rendered = code2prompt::render_prompt(&code2prompt::FsSource, &config).expect("Failed to render prompt");
+        let names: Vec<&str> = rendered
+            .files
+            .iter()
+            .map(|file| file["path"].as_str().unwrap().rsplit('/').next().unwrap())
+            .collect();
+        assert_eq!(names, vec!["a.rs", "c.rs", "b.rs"]);
+
+        // Outside of a git repository, the modification times are used
+        let outside = TempDir::new().expect("Failed to create temp dir");
+        for (name, seconds) in [("old.rs", 1_600_000_000), ("new.rs", 1_600_000_200), ("mid.rs", 1_600_000_100)] {
+            fs::write(outside.path().join(name), format!("// {}", name)).expect("Failed to write test file");
+            let file = fs::File::options()
+                .write(true)
+                .open(outside.path().join(name))
+                .expect("Failed to open test file");
+            file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds))
+                .expect("Failed to set the modification time");
+        }
+        let config = code2prompt::Code2PromptConfig {
+            path: outside.path().to_path_buf(),
+            ..config
+        };
+        let rendered = code2prompt::render_prompt(&code2prompt::FsSource, &config).expect("Failed to render prompt");
+        let names: Vec<&str> = rendered
+            .files
+            .iter()
+            .map(|file| file["path"].as_str().unwrap().rsplit('/').next().unwrap())
+            .collect();
+        assert_eq!(names, vec!["new.rs", "mid.rs", "old.rs"]);
+    }
+
+    #[test]
+    fn test_contributors() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path();
+        let repo = Repository::init(repo_path).expect("Failed to initialize repository");
+
+        let authors = ["Alice", "Bob", "Alice", "Carol", "Alice", "Bob"];
+        for (number, author) in authors.iter().enumerate() {
+            fs::write(repo_path.join("main.rs"), format!("// {}", number)).expect("Failed to write test file");
+            let mut index = repo.index().expect("Failed to get index");
+            index.add_path(std::path::Path::new("main.rs")).expect("Failed to add file");
+            index.write().expect("Failed to write index");
+            let tree = repo.find_tree(index.write_tree().expect("Failed to write tree")).unwrap();
+            let signature = Signature::now(author, "dev@example.com").expect("Failed to create signature");
+            let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+            let parents: Vec<&git2::Commit> = parent.iter().collect();
+            repo.commit(Some("HEAD"), &signature, &signature, "Commit", &tree, &parents)
+                .expect("Failed to commit");
+        }
+
+        let contributors = get_contributors(repo_path).expect("Failed to list contributors");
+        let counts: Vec<(&str, usize)> = contributors.iter().map(|c| (c.name.as_str(), c.commits)).collect();
+        assert_eq!(counts, vec![("Alice", 3), ("Bob", 2), ("Carol", 1)]);
+
+        let config = code2prompt::Code2PromptConfig {
+            path: repo_path.to_path_buf(),
+            contributors: true,
+            template_content: Some("{{#each contributors}}{{name}}={{commits}} {{/each}}".to_string()),
+            ..Default::default()
+        };
+        let rendered = code2prompt::render_prompt(&code2prompt::FsSource, &config).expect("Failed to render prompt");
+        assert_eq!(rendered.prompt.trim(), "Alice=3 Bob=2 Carol=1");
+
+        // Outside of a git repository, the list is empty
+        let outside = TempDir::new().expect("Failed to create temp dir");
+        let config = code2prompt::Code2PromptConfig {
+            path: outside.path().to_path_buf(),
+            ..config
+        };
+        let rendered = code2prompt::render_prompt(&code2prompt::FsSource, &config).expect("Failed to render prompt");
+        assert_eq!(rendered.prompt.trim(), "");
+    }
+
+    #[test]
+    fn test_mark_changes_since() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path();
+        let repo = Repository::init(repo_path).expect("Failed to initialize repository");
+        let signature = Signature::now("Test", "test@example.com").expect("Failed to create signature");
+        fs::write(repo_path.join("lib.rs"), "fn a() {}\nfn b() {}\nfn c() {}\n").expect("Failed to write test file");
+        fs::write(repo_path.join("util.rs"), "fn util() {}\n").expect("Failed to write test file");
+        let mut index = repo.index().expect("Failed to get index");
+        index
+            .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+            .expect("Failed to add files");
+        index.write().expect("Failed to write index");
+        let tree = repo.find_tree(index.write_tree().expect("Failed to write tree")).unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial commit", &tree, &[])
+            .expect("Failed to commit");
+
+        fs::write(repo_path.join("lib.rs"), "fn a() {}\nfn b() { todo!() }\nfn c() {}\nfn d() {}\n")
+            .expect("Failed to modify test file");
+
+        let config = code2prompt::Code2PromptConfig {
+            path: repo_path.to_path_buf(),
+            mark_changes_since: Some("HEAD".to_string()),
+            ..Default::default()
+        };
+        let rendered = code2prompt::render_prompt(&code2prompt::FsSource, &config).expect("Failed to render prompt");
+        let code = |name: &str| {
+            let file = rendered.files.iter().find(|f| f["path"].as_str().unwrap().ends_with(name)).unwrap();
+            file["code"].as_str().unwrap().to_string()
+        };
+
+        assert_eq!(code("lib.rs"), "```rs\nfn a() {}\n> fn b() { todo!() }\nfn c() {}\n> fn d() {}\n\n```");
+        // Files without changes are rendered plain
+        assert_eq!(code("util.rs"), "```rs\nfn util() {}\n\n```");
+
+        // Outside of a git repository, marking fails
+        let outside = TempDir::new().expect("Failed to create temp dir");
+        let config = code2prompt::Code2PromptConfig {
+            path: outside.path().to_path_buf(),
+            ..config
+        };
+        assert!(code2prompt::render_prompt(&code2prompt::FsSource, &config).is_err());
+    }
+
+    #[test]
+    fn test_mark_changes_since_with_other_content_options() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path();
+        let repo = Repository::init(repo_path).expect("Failed to initialize repository");
+        let signature = Signature::now("Test", "test@example.com").expect("Failed to create signature");
+        fs::write(repo_path.join("lib.rs"), "fn a() {}\nfn b() {}\nfn c() {}\n").expect("Failed to write test file");
+        let mut index = repo.index().expect("Failed to get index");
+        index
+            .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+            .expect("Failed to add files");
+        index.write().expect("Failed to write index");
+        let tree = repo.find_tree(index.write_tree().expect("Failed to write tree")).unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial commit", &tree, &[])
+            .expect("Failed to commit");
+
+        // A lone `\r` doesn't end a line for git, so it doesn't shift the marks
+        fs::write(repo_path.join("lib.rs"), "fn a() {}\r// a\nfn b() {}\nfn c() { todo!() }\n")
+            .expect("Failed to modify test file");
+        let config = code2prompt::Code2PromptConfig {
+            path: repo_path.to_path_buf(),
+            mark_changes_since: Some("HEAD".to_string()),
+            ..Default::default()
+        };
+        let rendered = code2prompt::render_prompt(&code2prompt::FsSource, &config).expect("Failed to render prompt");
+        assert_eq!(
+            rendered.files[0]["code"].as_str().unwrap(),
+            "```rs\n> fn a() {}\r// a\nfn b() {}\n> fn c() { todo!() }\n\n```"
+        );
+
+        // The public API of a file doesn't keep its lines, so it can't be marked
+        let config = code2prompt::Code2PromptConfig {
+            pub_only: true,
+            ..config
+        };
+        assert!(code2prompt::render_prompt(&code2prompt::FsSource, &config).is_err());
+    }
+
+    #[test]
+    fn test_mark_changes_since_shares_the_cache_safely() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path().join("repo");
+        fs::create_dir(&repo_path).expect("Failed to create repo dir");
+        let repo = Repository::init(&repo_path).expect("Failed to initialize repository");
+        let signature = Signature::now("Test", "test@example.com").expect("Failed to create signature");
+        fs::write(repo_path.join("lib.rs"), "// Copyright header\n\nfn lib() {}\n").expect("Failed to write test file");
+        let mut index = repo.index().expect("Failed to get index");
+        index
+            .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+            .expect("Failed to add files");
+        index.write().expect("Failed to write index");
+        let tree = repo.find_tree(index.write_tree().expect("Failed to write tree")).unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial commit", &tree, &[])
+            .expect("Failed to commit");
+
+        // The unchanged file is cached with its header while marking changes
+        let config = code2prompt::Code2PromptConfig {
+            path: repo_path.clone(),
+            mark_changes_since: Some("HEAD".to_string()),
+            strip_leading_comment_block: true,
+            cache_dir: Some(temp_dir.path().join("cache")),
+            ..Default::default()
+        };
+        let rendered = code2prompt::render_prompt(&code2prompt::FsSource, &config).expect("Failed to render prompt");
+        assert!(rendered.files[0]["code"].as_str().unwrap().contains("Copyright header"));
+
+        // A later run sharing the cache still strips it
+        let config = code2prompt::Code2PromptConfig {
+            mark_changes_since: None,
+            ..config
+        };
+        let rendered = code2prompt::render_prompt(&code2prompt::FsSource, &config).expect("Failed to render prompt");
+        let code = rendered.files[0]["code"].as_str().unwrap();
+        assert!(!code.contains("Copyright header"), "{}", code);
+        assert!(code.contains("fn lib() {}"));
+    }
 }
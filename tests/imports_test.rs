@@ -0,0 +1,172 @@
+use code2prompt::imports::{extract_imports, sort_topologically};
+use code2prompt::{generate_prompt, Code2PromptConfig};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn paths(files: &[serde_json::Value]) -> Vec<&str> {
+        files.iter().map(|f| f["path"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_extract_imports_per_language() {
+        let rust = "mod path;\npub mod filter;\nuse crate::path::label;\nuse std::fs;\n";
+        assert_eq!(
+            extract_imports(rust, "rs"),
+            vec!["self::path", "self::filter", "crate::path::label"]
+        );
+
+        let python = "import os\nfrom .utils import helper\nfrom pkg.models import User\n";
+        assert_eq!(extract_imports(python, "py"), vec!["os", ".utils", "pkg.models"]);
+
+        let js = "import a from './a';\nconst b = require(\"../b\");\nimport React from 'react';\n";
+        assert_eq!(extract_imports(js, "js"), vec!["./a", "../b"]);
+
+        let numbered = "   1 | mod path;\n   2 | use crate::filter;\n";
+        assert_eq!(extract_imports(numbered, "rs"), vec!["self::path", "crate::filter"]);
+
+        assert!(extract_imports("package main\nimport \"fmt\"\n", "go").is_empty());
+    }
+
+    #[test]
+    fn test_sort_topologically_puts_dependencies_first() {
+        let files = vec![
+            json!({"path": "app/a.js", "extension": "js", "code": "import b from './b';"}),
+            json!({"path": "app/b.js", "extension": "js", "code": "import c from './c';"}),
+            json!({"path": "app/c.js", "extension": "js", "code": "export const c = 1;"}),
+            json!({"path": "app/d.txt", "extension": "txt", "code": "notes"}),
+        ];
+        let sorted = sort_topologically(files);
+        assert_eq!(paths(&sorted), vec!["app/c.js", "app/b.js", "app/a.js", "app/d.txt"]);
+    }
+
+    #[test]
+    fn test_sort_topologically_breaks_cycles_by_incoming_order() {
+        let files = vec![
+            json!({"path": "src/a.py", "extension": "py", "code": "from .b import y"}),
+            json!({"path": "src/b.py", "extension": "py", "code": "from .a import x"}),
+            json!({"path": "src/c.py", "extension": "py", "code": "from .a import x"}),
+        ];
+        let sorted = sort_topologically(files.clone());
+        assert_eq!(paths(&sorted), vec!["src/a.py", "src/b.py", "src/c.py"]);
+
+        let mut swapped = files;
+        swapped.swap(0, 1);
+        assert_eq!(paths(&sort_topologically(swapped)), vec!["src/b.py", "src/a.py", "src/c.py"]);
+    }
+
+    #[test]
+    fn test_sort_topologically_keeps_incoming_order_of_ties() {
+        // Files sorted by churn, with only the most changed one importing another
+        let files = vec![
+            json!({"path": "app/z.js", "extension": "js", "code": "import m from './m';"}),
+            json!({"path": "app/y.js", "extension": "js", "code": "export const y = 1;"}),
+            json!({"path": "app/m.js", "extension": "js", "code": "export const m = 1;"}),
+            json!({"path": "app/a.js", "extension": "js", "code": "export const a = 1;"}),
+        ];
+        let sorted = sort_topologically(files);
+        assert_eq!(paths(&sorted), vec!["app/y.js", "app/m.js", "app/z.js", "app/a.js"]);
+    }
+
+    #[test]
+    fn test_generate_prompt_topological() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("lib.rs"), "mod zeta;\nmod alpha;\n").unwrap();
+        fs::write(src.join("alpha.rs"), "use crate::zeta::Z;\npub fn alpha() {}\n").unwrap();
+        fs::write(src.join("zeta.rs"), "pub struct Z;\n").unwrap();
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            no_clipboard: true,
+            topological: true,
+            ..Default::default()
+        };
+        let rendered = generate_prompt(&config).unwrap();
+
+        let zeta = rendered.find("zeta.rs`").unwrap();
+        let alpha = rendered.find("alpha.rs`").unwrap();
+        let lib = rendered.find("lib.rs`").unwrap();
+        assert!(zeta < alpha && alpha < lib);
+    }
+
+    #[test]
+    fn test_dependency_graph_dot() {
+        use code2prompt::{render_prompt, MemorySource};
+
+        let source = MemorySource::new("/virtual/app")
+            .with_file("src/index.js", "import { a } from './a';\nimport React from 'react';")
+            .with_file("src/a.js", "const b = require('../lib/b');\nimport { missing } from './missing';")
+            .with_file("lib/b.js", "import { a } from '../src/a';\nexport const b = 1;");
+        let config = Code2PromptConfig {
+            path: std::path::PathBuf::from("/virtual/app"),
+            graph: true,
+            ..Default::default()
+        };
+        let prompt = render_prompt(&source, &config).unwrap().prompt;
+
+        let start = prompt.find("digraph dependencies {").expect("Missing dependency graph");
+        let dot = &prompt[start..start + prompt[start..].find('}').unwrap() + 1];
+        let edges: Vec<&str> = dot.lines().filter(|line| line.contains("->")).map(str::trim).collect();
+        // The cycle between `a.js` and `b.js` is kept, while unresolved imports are left out
+        assert_eq!(
+            edges,
+            vec![
+                "\"lib/b.js\" -> \"src/a.js\";",
+                "\"src/a.js\" -> \"lib/b.js\";",
+                "\"src/index.js\" -> \"src/a.js\";",
+            ]
+        );
+        assert!(dot.contains("    \"src/index.js\";"));
+    }
+
+    #[test]
+    fn test_entry_follows_imports_transitively() {
+        use code2prompt::{render_prompt, MemorySource};
+
+        let source = MemorySource::new("/virtual/app")
+            .with_file("src/index.js", "import { a } from './a';\nimport React from 'react';")
+            .with_file("src/a.js", "const b = require('../lib/b');\nimport { missing } from './missing';")
+            .with_file("lib/b.js", "import { a } from '../src/a';\nexport const b = 1;")
+            .with_file("lib/unused.js", "export const unused = 1;");
+        let config = code2prompt::Code2PromptConfig {
+            path: std::path::PathBuf::from("/virtual/app"),
+            entry: Some("src/index.js".into()),
+            follow_imports: true,
+            quiet: true,
+            ..Default::default()
+        };
+        let prompt = render_prompt(&source, &config).unwrap();
+        assert_eq!(
+            paths(&prompt.files),
+            vec!["/virtual/app/lib/b.js", "/virtual/app/src/a.js", "/virtual/app/src/index.js"]
+        );
+
+        let files = prompt.files.clone();
+        let all = render_prompt(&source, &Code2PromptConfig { entry: None, ..config.clone() }).unwrap().files;
+        let entry = all.iter().position(|f| f["path"] == "/virtual/app/src/index.js").unwrap();
+        let (reachable, unresolved) = code2prompt::reachable_files(&all, entry);
+        assert_eq!(reachable.len(), files.len());
+        let unresolved: Vec<&str> = unresolved.iter().map(|u| u.import.as_str()).collect();
+        assert_eq!(unresolved, vec!["./missing"]);
+
+        // Without following imports, only the entry file is kept
+        let config = Code2PromptConfig {
+            follow_imports: false,
+            ..config
+        };
+        let prompt = render_prompt(&source, &config).unwrap();
+        assert_eq!(paths(&prompt.files), vec!["/virtual/app/src/index.js"]);
+
+        let config = Code2PromptConfig {
+            entry: Some("src/nope.js".into()),
+            ..config
+        };
+        assert!(render_prompt(&source, &config).is_err());
+    }
+}
@@ -24,11 +24,11 @@ fn init_logger() {
 fn create_temp_file(dir: &Path, name: &str, content: &str) {
     let file_path = dir.join(name);
     let parent_dir = file_path.parent().unwrap();
-    fs::create_dir_all(parent_dir).expect(&format!("Failed to create directory: {:?}", parent_dir));
+    fs::create_dir_all(parent_dir).unwrap_or_else(|_| panic!("Failed to create directory: {:?}", parent_dir));
     let mut file =
-        File::create(&file_path).expect(&format!("Failed to create temp file: {:?}", file_path));
+        File::create(&file_path).unwrap_or_else(|_| panic!("Failed to create temp file: {:?}", file_path));
     //debug!("Writing to file: {:?}", file_path);
-    writeln!(file, "{}", content).expect(&format!("Failed to write to temp file: {:?}", file_path));
+    writeln!(file, "{}", content).unwrap_or_else(|_| panic!("Failed to write to temp file: {:?}", file_path));
 }
 
 fn create_test_hierarchy(base_path: &Path) {
@@ -67,7 +67,7 @@ fn create_test_hierarchy(base_path: &Path) {
 
 fn read_output_file(dir: &Path, file_name: &str) -> String {
     let file_path = dir.join(file_name);
-    read_to_string(&file_path).expect(&format!("Failed to read output file: {:?}", file_path))
+    read_to_string(&file_path).unwrap_or_else(|_| panic!("Failed to read output file: {:?}", file_path))
 }
 
 mod tests {
@@ -91,7 +91,7 @@ mod tests {
         fn command(&self) -> Command {
             let mut cmd =
                 Command::cargo_bin("code2prompt").expect("Failed to find code2prompt binary");
-            cmd.arg(&self.dir.path().to_str().unwrap())
+            cmd.arg(self.dir.path().to_str().unwrap())
                 .arg("--output")
                 .arg(&self.output_file)
                 .arg("--no-clipboard");
@@ -252,4 +252,73 @@ mod tests {
         assert!(contains("BAZ.py").eval(&output));
         assert!(contains("CONTENT BAZ.PY").eval(&output));
     }
+
+    #[test]
+    fn test_warns_about_secret_files() {
+        let env = TestEnv::new();
+        create_temp_file(env.dir.path(), "config/credentials.json", "{\"key\": \"value\"}");
+        let mut cmd = env.command();
+        cmd.assert()
+            .success()
+            .stderr(contains("Likely secret file included in the prompt"))
+            .stderr(contains("credentials.json"));
+
+        let output = env.read_output();
+        assert!(contains("\"key\": \"value\"").eval(&output));
+    }
+
+    #[test]
+    fn test_machine_stats() {
+        let env = TestEnv::new();
+        let mut cmd = env.command();
+        cmd.arg("--machine-stats")
+            .arg("--encoding=cl100k")
+            .env("NO_COLOR", "1")
+            .env("OPENAI_MODEL", "gpt-3.5-turbo");
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::is_match(r"(?m)^tokens=\d+ model=gpt-3.5-turbo chars=\d+ files=12$").unwrap())
+            .stderr(contains("Prompt statistics").not())
+            .stdout(contains("tokens=").not());
+    }
+
+    #[test]
+    fn test_max_output_bytes() {
+        let env = TestEnv::new();
+        let mut cmd = env.command();
+        cmd.arg("--max-output-bytes=600")
+            .assert()
+            .success()
+            .stderr(contains("Output exceeded 600 bytes"));
+
+        let output = env.read_output();
+        assert!(output.len() <= 600);
+        assert!(contains("file(s) omitted").eval(&output));
+
+        let mut cmd = env.command();
+        cmd.arg("--max-output-bytes=600")
+            .arg("--quiet")
+            .assert()
+            .success()
+            .stderr(contains("Output exceeded").not());
+    }
+
+    #[test]
+    fn test_max_output_bytes_conflicts_with_max_tokens() {
+        let env = TestEnv::new();
+        let mut cmd = env.command();
+        cmd.arg("--max-output-bytes=600").arg("--max-tokens=100").assert().failure();
+    }
+
+    #[test]
+    fn test_topological() {
+        let env = TestEnv::new();
+        create_temp_file(env.dir.path(), "app/a_main.py", "import app.z_util");
+        create_temp_file(env.dir.path(), "app/z_util.py", "VALUE = 1");
+        let mut cmd = env.command();
+        cmd.arg("--include=**/app/**").arg("--topological").assert().success();
+
+        let output = env.read_output();
+        assert!(output.find("VALUE = 1").unwrap() < output.find("import app.z_util").unwrap());
+    }
 }
@@ -0,0 +1,52 @@
+use code2prompt::language::{language_name, strip_leading_comment_block};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_name() {
+        assert_eq!(language_name("rs"), Some("rust"));
+        assert_eq!(language_name("PY"), Some("python"));
+        assert_eq!(language_name("unknown"), None);
+    }
+
+    #[test]
+    fn test_strip_rust_line_header() {
+        let code = "// Copyright (c) Example\n// Licensed under MIT\n\nuse std::io;\n// not a header\nfn main() {}\n";
+        assert_eq!(
+            strip_leading_comment_block(code, "rs").unwrap(),
+            "use std::io;\n// not a header\nfn main() {}\n"
+        );
+    }
+
+    #[test]
+    fn test_strip_rust_block_header() {
+        let code = "/*\n * Copyright (c) Example\n */\n\n/* second */\nfn main() {}\n";
+        assert_eq!(
+            strip_leading_comment_block(code, "rs").unwrap(),
+            "/* second */\nfn main() {}\n"
+        );
+    }
+
+    #[test]
+    fn test_keep_rust_doc_comments() {
+        let code = "//! Module documentation\nfn main() {}\n";
+        assert!(strip_leading_comment_block(code, "rs").is_none());
+    }
+
+    #[test]
+    fn test_strip_python_header_keeps_shebang() {
+        let code = "#!/usr/bin/env python\n# Copyright (c) Example\n# SPDX-License-Identifier: MIT\n\nimport os\n# comment\n";
+        assert_eq!(
+            strip_leading_comment_block(code, "py").unwrap(),
+            "#!/usr/bin/env python\nimport os\n# comment\n"
+        );
+    }
+
+    #[test]
+    fn test_no_header() {
+        assert!(strip_leading_comment_block("import os\n# comment\n", "py").is_none());
+        assert!(strip_leading_comment_block("/* unterminated\nfn main() {}\n", "rs").is_none());
+    }
+}
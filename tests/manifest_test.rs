@@ -0,0 +1,104 @@
+use code2prompt::{generate_prompt, render_prompt, Code2PromptConfig, Manifest, MemorySource};
+use std::path::PathBuf;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn manifest_of(source: &MemorySource) -> Manifest {
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            ..Default::default()
+        };
+        let prompt = render_prompt(source, &config).unwrap();
+        Manifest::from_files(&prompt.files, &config.path, false, &None)
+    }
+
+    #[test]
+    fn test_manifest_round_trip_detects_changes() {
+        let before = MemorySource::new("/virtual/project")
+            .with_file("src/main.rs", "fn main() {}")
+            .with_file("src/util.rs", "pub fn util() {}")
+            .with_file("old.rs", "fn old() {}");
+        let after = MemorySource::new("/virtual/project")
+            .with_file("src/main.rs", "fn main() { println!(\"changed\"); }")
+            .with_file("src/util.rs", "pub fn util() {}")
+            .with_file("new.rs", "fn new() {}");
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("manifest.json");
+        let saved = manifest_of(&before);
+        saved.write(&path).unwrap();
+        let saved = Manifest::read(&path).unwrap();
+        assert_eq!(saved, manifest_of(&before));
+        assert_eq!(saved.files["src/util.rs"].hash.len(), 64);
+
+        let current = manifest_of(&after);
+        let diff = saved.compare(&current);
+        assert_eq!(diff.added, vec!["new.rs"]);
+        assert_eq!(diff.removed, vec!["old.rs"]);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].path, "src/main.rs");
+        assert!(diff.changed[0].new_tokens > diff.changed[0].old_tokens);
+        assert_eq!(
+            diff.token_delta,
+            current.total_tokens() as i64 - saved.total_tokens() as i64
+        );
+        assert!(saved.compare(&saved).is_empty());
+    }
+
+    #[test]
+    fn test_json_output_reports_manifest_diff() {
+        let dir = tempdir().unwrap();
+        let out = tempdir().unwrap();
+        let manifest = out.path().join("manifest.json");
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            manifest: Some(manifest.clone()),
+            compare_manifest: Some(manifest.clone()),
+            no_clipboard: true,
+            json: true,
+            ..Default::default()
+        };
+
+        // The first run has no manifest to compare to
+        assert!(generate_prompt(&config).is_err());
+        let config = Code2PromptConfig {
+            compare_manifest: None,
+            ..config
+        };
+        generate_prompt(&config).unwrap();
+
+        fs::write(dir.path().join("main.rs"), "fn main() { run(); }").unwrap();
+        let config = Code2PromptConfig {
+            compare_manifest: Some(manifest),
+            ..config
+        };
+        let output: serde_json::Value = serde_json::from_str(&generate_prompt(&config).unwrap()).unwrap();
+        assert_eq!(output["manifest_diff"]["changed"][0]["path"], "main.rs");
+        assert_eq!(output["manifest_diff"]["added"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_manifest_tokens_follow_count_special_tokens() {
+        let source = MemorySource::new("/virtual/project").with_file("notes.txt", "<|endoftext|>");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            count_special_tokens: Some(false),
+            ..Default::default()
+        };
+        let mut files = render_prompt(&source, &config).unwrap().files;
+        for file in &mut files {
+            file.as_object_mut().unwrap().remove("tokens");
+        }
+        let tokenizer = config.tokenizer();
+        let plain = Manifest::from_files_with_tokenizer(&files, &config.path, false, tokenizer.as_ref());
+        let special = Manifest::from_files(&files, &config.path, false, &None);
+
+        assert!(plain.files["notes.txt"].tokens > special.files["notes.txt"].tokens);
+    }
+}
@@ -0,0 +1,39 @@
+use code2prompt::{repo_map, Code2PromptConfig, MemorySource};
+use std::path::PathBuf;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_lists_included_files_with_sizes() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("src/main.rs", "fn main() { println!(\"hello\"); }")
+            .with_file("src/lib/util.rs", "pub fn util() {}")
+            .with_file("README.md", "# Project")
+            .with_file("build.log", "noise");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            exclude: Some("*.log".to_string()),
+            ..Default::default()
+        };
+        let map = repo_map(&source, &config).unwrap();
+
+        let files: Vec<(&str, u64)> = map.files.iter().map(|f| (f.path.as_str(), f.size)).collect();
+        assert_eq!(
+            files,
+            vec![("src/main.rs", 32), ("src/lib/util.rs", 16), ("README.md", 9)]
+        );
+        assert!(map.files.iter().all(|f| f.tokens > 0));
+
+        let directories: Vec<(&str, u64)> = map.directories.iter().map(|d| (d.path.as_str(), d.size)).collect();
+        assert_eq!(directories, vec![("src", 48), ("src/lib", 16)]);
+        assert_eq!(map.total_size, 57);
+        assert_eq!(map.total_tokens, map.files.iter().map(|f| f.tokens).sum::<usize>());
+
+        let table = map.to_table();
+        assert!(table.contains("32"));
+        assert!(table.contains("src/lib/"));
+        assert!(!table.contains("build.log"));
+    }
+}
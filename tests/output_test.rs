@@ -0,0 +1,438 @@
+use code2prompt::{generate_prompt, generate_prompt_from_source, render_prompt, Code2PromptConfig, FileSort, MemorySource, OutputFormat};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    /// Asserts that every tag of an HTML document is closed in order.
+    fn assert_well_formed(html: &str) {
+        let mut open = Vec::new();
+        for tag in html.split('<').skip(1).map(|rest| &rest[..rest.find('>').unwrap()]) {
+            if tag.starts_with('!') || tag.ends_with('/') {
+                continue;
+            }
+            let name = tag.trim_start_matches('/').split_whitespace().next().unwrap();
+            if tag.starts_with('/') {
+                assert_eq!(open.pop(), Some(name), "Unexpected closing tag: {}", name);
+            } else {
+                open.push(name);
+            }
+        }
+        assert!(open.is_empty(), "Unclosed tags: {:?}", open);
+    }
+
+    #[test]
+    fn test_json_files_schema() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+        fs::write(dir.path().join("notes.txt"), "plain notes").unwrap();
+        fs::write(dir.path().join("skip.log"), "ignored").unwrap();
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            exclude: Some("*.log".to_string()),
+            output_format: OutputFormat::JsonFiles,
+            path_url_template: Some("https://example.com/{path}".to_string()),
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let output = generate_prompt(&config).unwrap();
+        let records: Vec<serde_json::Value> = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(records.len(), 2);
+        for record in &records {
+            assert!(record["path"].is_string());
+            assert!(record["content"].is_string());
+            assert!(record["language"].is_string());
+            assert!(record["tokens"].as_u64().unwrap() > 0);
+            assert_eq!(record["hash"].as_str().unwrap().len(), 64);
+            assert!(record["url"].as_str().unwrap().starts_with("https://example.com/"));
+        }
+
+        let main = records
+            .iter()
+            .find(|r| r["path"].as_str().unwrap().ends_with("main.rs"))
+            .unwrap();
+        assert_eq!(main["content"], "fn main() {}");
+        assert_eq!(main["language"], "rust");
+        assert_eq!(
+            main["hash"],
+            "ef32637cb9c3ec2e3968c9cbdf26a5e9c172be94f88af533e14bd43f892d5297"
+        );
+    }
+
+    #[test]
+    fn test_json_files_tokens_follow_count_special_tokens() {
+        let source = MemorySource::new("/virtual/project").with_file("notes.txt", "<|endoftext|>");
+        let tokens = |count_special_tokens| {
+            let config = Code2PromptConfig {
+                path: PathBuf::from("/virtual/project"),
+                output_format: OutputFormat::JsonFiles,
+                count_special_tokens,
+                no_clipboard: true,
+                ..Default::default()
+            };
+            let output = generate_prompt_from_source(&source, &config).unwrap();
+            let records: Vec<serde_json::Value> = serde_json::from_str(&output).unwrap();
+            records[0]["tokens"].as_u64().unwrap()
+        };
+
+        assert_eq!(tokens(None), 1);
+        assert!(tokens(Some(false)) > 1);
+    }
+
+    #[test]
+    fn test_html_output_escapes_code() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("src/main.rs", "fn main() { if 1 < 2 && true { println!(\"<b>\"); } }")
+            .with_file("index.html", "<p>Tom & Jerry</p>");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            html: true,
+            ..Default::default()
+        };
+        let html = render_prompt(&source, &config).unwrap().prompt;
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert_well_formed(&html);
+        assert!(html.contains("<code class=\"language-rust\">fn main() { if 1 &lt; 2 &amp;&amp; true"));
+        assert!(html.contains("<code class=\"language-html\">&lt;p&gt;Tom &amp; Jerry&lt;/p&gt;</code>"));
+        assert!(!html.contains("```"));
+
+        // The tree links to the blocks of the files
+        let anchor = html.find("<section id=\"file-1\">").unwrap();
+        assert!(html[anchor..].contains("main.rs"));
+        assert!(html.contains("<li>src/\n<ul>\n<li><a href=\"#file-1\">main.rs</a></li>"));
+    }
+
+    #[test]
+    fn test_line_numbers_outside_the_code() {
+        let source = MemorySource::new("/virtual/project").with_file("main.rs", "fn main() {\n    run();\n}");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            line_number: true,
+            line_numbers_outside: true,
+            ..Default::default()
+        };
+        let prompt = render_prompt(&source, &config).unwrap().prompt;
+        assert!(prompt.contains("```rs showLineNumbers\nfn main() {\n    run();\n}\n```"), "{}", prompt);
+        assert!(!prompt.contains("   1 | "));
+
+        // The HTML output gets a column of its own, out of the code
+        let config = Code2PromptConfig { html: true, ..config };
+        let html = render_prompt(&source, &config).unwrap().prompt;
+        assert_well_formed(&html);
+        assert!(html.contains(
+            "<pre class=\"line-numbers\" aria-hidden=\"true\" style=\"user-select: none\">1\n2\n3</pre>\
+             <pre><code class=\"language-rust\">fn main() {\n    run();\n}</code></pre>"
+        ), "{}", html);
+
+        // Without code blocks, the numbers prefix the lines
+        let config = Code2PromptConfig {
+            html: false,
+            no_codeblock: true,
+            ..config
+        };
+        let prompt = render_prompt(&source, &config).unwrap().prompt;
+        assert!(prompt.contains("   1 | fn main() {\n   2 |     run();\n   3 | }"));
+    }
+
+    #[test]
+    fn test_html_output_excludes_json() {
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            html: true,
+            output_format: OutputFormat::JsonFiles,
+            ..Default::default()
+        };
+        assert!(render_prompt(&MemorySource::new("/virtual/project"), &config).is_err());
+    }
+
+    #[test]
+    fn test_output_file_and_stdout_in_one_run() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+        let out = tempdir().unwrap();
+        let output_path = out.path().join("prompt.md");
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            output: Some(output_path.to_string_lossy().to_string()),
+            stdout: true,
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let prompt = generate_prompt(&config).unwrap();
+
+        assert!(prompt.contains("fn main() {}"));
+        assert_eq!(fs::read_to_string(&output_path).unwrap(), prompt);
+    }
+
+    #[test]
+    fn test_output_parent_directories_are_created_unless_disabled() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+        let out = tempdir().unwrap();
+        let output_path = out.path().join("nested/prompt.md");
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            output: Some(output_path.to_string_lossy().to_string()),
+            create_dirs: Some(false),
+            no_clipboard: true,
+            ..Default::default()
+        };
+        assert!(generate_prompt(&config).is_err());
+        assert!(!output_path.exists());
+
+        let config = Code2PromptConfig {
+            create_dirs: None,
+            ..config
+        };
+        let prompt = generate_prompt(&config).unwrap();
+        assert_eq!(fs::read_to_string(&output_path).unwrap(), prompt);
+    }
+
+    #[test]
+    fn test_output_inside_the_codebase_is_excluded() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        let output_path = dir.path().join("docs/prompt.md");
+        let template_path = dir.path().join("prompt.hbs");
+        fs::write(&template_path, "{{source_tree}}\n{{#each files}}{{path}}\n{{code}}\n{{/each}}").unwrap();
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            output: Some(output_path.to_string_lossy().to_string()),
+            template: Some(template_path.clone()),
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let first = generate_prompt(&config).unwrap();
+        // The previous prompt isn't swept into the next one, which stays the same
+        let second = generate_prompt(&config).unwrap();
+        assert_eq!(first, second);
+        assert!(second.contains("fn main() {}"));
+        assert!(!second.contains("prompt.md"));
+        assert!(!second.contains("prompt.hbs"));
+
+        let config = Code2PromptConfig {
+            self_exclude: Some(false),
+            ..config
+        };
+        let prompt = generate_prompt(&config).unwrap();
+        assert!(prompt.contains("prompt.md"));
+        assert!(prompt.contains("prompt.hbs"));
+    }
+
+    #[test]
+    fn test_repository_template_is_excluded() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let template = "{{source_tree}}\n{{#each files}}{{path}}\n{{code}}\n{{/each}}";
+        fs::create_dir(root.join(".code2prompt")).unwrap();
+        fs::write(root.join(".code2prompt/template.hbs"), template).unwrap();
+        // The in-memory tree keeps the hidden directory that the filesystem walk would skip
+        let source = MemorySource::new(&root)
+            .with_file("main.rs", "fn main() {}")
+            .with_file(".code2prompt/template.hbs", template);
+
+        let config = Code2PromptConfig {
+            path: root.clone(),
+            no_clipboard: true,
+            ..Default::default()
+        };
+        assert_eq!(config.template_path(), Some(root.join(".code2prompt/template.hbs")));
+        let prompt = generate_prompt_from_source(&source, &config).unwrap();
+        assert!(prompt.contains("fn main() {}"));
+        assert!(!prompt.contains("template.hbs"));
+
+        let config = Code2PromptConfig {
+            self_exclude: Some(false),
+            ..config
+        };
+        let prompt = generate_prompt_from_source(&source, &config).unwrap();
+        assert!(prompt.contains("template.hbs"));
+    }
+
+    #[test]
+    fn test_stable_output_is_byte_identical() {
+        let render = || {
+            let dir = tempdir().unwrap();
+            fs::create_dir(dir.path().join("src")).unwrap();
+            fs::write(dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+            fs::write(dir.path().join("src/lib.rs"), "pub fn run() {}").unwrap();
+            fs::write(dir.path().join("README.md"), "# Project").unwrap();
+            let template_path = dir.path().join("prompt.hbs");
+            fs::write(&template_path, "{{generated_at}}\n{{absolute_code_path}}\n{{source_tree}}\n{{#each files}}{{path}} {{tokens}}\n{{code}}\n{{/each}}").unwrap();
+            let config = Code2PromptConfig {
+                path: dir.path().to_path_buf(),
+                template: Some(template_path),
+                exclude: Some("*.hbs".to_string()),
+                tokens: true,
+                stable: true,
+                ..Default::default()
+            };
+            render_prompt(&code2prompt::FsSource, &config).unwrap().prompt
+        };
+
+        // Two runs over copies of the tree in directories of different names
+        let first = render();
+        let second = render();
+        assert_eq!(first.as_bytes(), second.as_bytes());
+        assert!(first.starts_with(".\n.\n├── README.md"), "{}", first);
+        assert!(first.contains("./src/main.rs \n```rs\nfn main() {}"), "{}", first);
+    }
+
+    #[test]
+    fn test_stable_output_ignores_token_counts_and_modification_times() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.rs"), "fn a() {}").unwrap();
+        fs::write(dir.path().join("b.rs"), "fn b() {}").unwrap();
+        let touch = |name: &str, seconds: u64| {
+            let file = fs::File::options().write(true).open(dir.path().join(name)).unwrap();
+            file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds)).unwrap();
+        };
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            file_header_template: Some("## {{path}} {{token_count}} tokens".to_string()),
+            sort_files: Some(FileSort::ByRecency),
+            stable: true,
+            ..Default::default()
+        };
+
+        touch("a.rs", 1_000);
+        touch("b.rs", 2_000);
+        let first = render_prompt(&code2prompt::FsSource, &config).unwrap().prompt;
+        touch("a.rs", 2_000);
+        touch("b.rs", 1_000);
+        let second = render_prompt(&code2prompt::FsSource, &config).unwrap().prompt;
+        assert_eq!(first, second);
+        assert!(first.contains("## ./a.rs 0 tokens"), "{}", first);
+    }
+
+    #[test]
+    fn test_include_hashes_match_sha256_of_the_file() {
+        let dir = tempdir().unwrap();
+        // The hash is of the file as read, before its line endings are normalized
+        fs::write(dir.path().join("main.rs"), "fn main() {\r\n    println!(\"hi\");\r\n}\r\n").unwrap();
+        let expected = "7bbb080938a3af7f29b0e97881b26afa55d6f335b2af9639e34f03d51186a71b";
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            include_hashes: true,
+            json: true,
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let rendered = render_prompt(&code2prompt::FsSource, &config).unwrap();
+        assert_eq!(rendered.files[0]["sha256"], expected);
+
+        let output: serde_json::Value = serde_json::from_str(&generate_prompt(&config).unwrap()).unwrap();
+        let path = output["files"][0].as_str().unwrap();
+        assert_eq!(output["file_hashes"][path], expected);
+
+        let config = Code2PromptConfig {
+            json: false,
+            output_format: OutputFormat::JsonFiles,
+            ..config
+        };
+        let records: Vec<serde_json::Value> = serde_json::from_str(&generate_prompt(&config).unwrap()).unwrap();
+        assert_eq!(records[0]["sha256"], expected);
+
+        // Off by default
+        let config = Code2PromptConfig {
+            include_hashes: false,
+            ..config
+        };
+        let records: Vec<serde_json::Value> = serde_json::from_str(&generate_prompt(&config).unwrap()).unwrap();
+        assert!(records[0].get("sha256").is_none());
+
+        // Cached blocks keep the hash, and runs with and without hashes don't share entries
+        let cache = tempdir().unwrap();
+        let config = Code2PromptConfig {
+            cache_dir: Some(cache.path().to_path_buf()),
+            ..config
+        };
+        let records: Vec<serde_json::Value> = serde_json::from_str(&generate_prompt(&config).unwrap()).unwrap();
+        assert!(records[0].get("sha256").is_none());
+        let config = Code2PromptConfig {
+            include_hashes: true,
+            ..config
+        };
+        for _ in 0..2 {
+            let records: Vec<serde_json::Value> = serde_json::from_str(&generate_prompt(&config).unwrap()).unwrap();
+            assert_eq!(records[0]["sha256"], expected);
+        }
+    }
+
+    #[test]
+    fn test_csv_report() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {\n    run();\n}\n").unwrap();
+        fs::write(dir.path().join("notes, draft.txt"), "one line").unwrap();
+        let out = tempdir().unwrap();
+        let report_path = out.path().join("report.csv");
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            report_csv: Some(report_path.clone()),
+            tokens: true,
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let rendered = render_prompt(&code2prompt::FsSource, &config).unwrap();
+        generate_prompt(&config).unwrap();
+
+        let report = fs::read_to_string(&report_path).unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "path,size,lines,tokens");
+        assert_eq!(lines.len(), rendered.files.len() + 1);
+        let tokens = |name: &str| {
+            let file = rendered.files.iter().find(|f| f["path"].as_str().unwrap().ends_with(name)).unwrap();
+            file["tokens"].as_u64().unwrap()
+        };
+        assert!(lines.contains(&format!("\"main.rs\",25,3,{}", tokens("main.rs")).as_str()), "{}", report);
+        // The comma of the path doesn't add a column
+        assert!(lines.contains(&format!("\"notes, draft.txt\",8,1,{}", tokens("draft.txt")).as_str()), "{}", report);
+    }
+
+    #[test]
+    fn test_binary_as_base64_round_trip() {
+        use base64::prelude::{Engine, BASE64_STANDARD};
+
+        let dir = tempdir().unwrap();
+        let icon: Vec<u8> = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0xff, 0xfe, 0x10];
+        fs::write(dir.path().join("icon.png"), &icon).unwrap();
+        fs::write(dir.path().join("large.bin"), vec![0xffu8; 1024]).unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            output_format: OutputFormat::JsonFiles,
+            binary_as_base64: true,
+            max_binary_bytes: Some(64),
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let records: Vec<serde_json::Value> = serde_json::from_str(&generate_prompt(&config).unwrap()).unwrap();
+
+        // The binary file over the cap is left out
+        assert_eq!(records.len(), 2);
+        let record = records
+            .iter()
+            .find(|r| r["path"].as_str().unwrap().ends_with("icon.png"))
+            .unwrap();
+        assert_eq!(record["encoding"], "base64");
+        assert_eq!(BASE64_STANDARD.decode(record["content"].as_str().unwrap()).unwrap(), icon);
+        let text = records
+            .iter()
+            .find(|r| r["path"].as_str().unwrap().ends_with("main.rs"))
+            .unwrap();
+        assert!(text.get("encoding").is_none());
+    }
+}
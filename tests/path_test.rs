@@ -0,0 +1,1071 @@
+use code2prompt::source::{FileMetadata, FileSource, FsSource, MemorySource};
+use code2prompt::path::CONTENT_DENY_SCAN_BYTES;
+use code2prompt::{
+    files_tree, files_tree_with_config, iter_source_files, render_prompt, traverse_source, Code2PromptConfig, FileEntry, LineEnding, SkippedEntry,
+    Traversal,
+};
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source() -> MemorySource {
+        MemorySource::new("/virtual/project")
+            .with_file("src/main.rs", "fn main() {}")
+            .with_file("src/util.rs", "pub fn util() {}")
+            .with_file("docs/guide.md", "# Guide")
+            .with_file("build.log", "noise")
+    }
+
+    #[test]
+    fn test_content_paths_keep_full_tree() {
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            content_paths: vec!["src/main.rs".to_string(), "./docs/guide.md".to_string()],
+            ..Default::default()
+        };
+        let exclude = vec!["*.log".to_string()];
+        let Traversal { tree, files, .. } =
+            traverse_source(&source(), &config, &[], &exclude).unwrap();
+
+        // Files without a content block still appear in the tree
+        assert!(tree.contains("util.rs"));
+        assert!(tree.contains("guide.md"));
+
+        let paths: Vec<&str> = files.iter().map(|f| f["path"].as_str().unwrap()).collect();
+        assert_eq!(
+            paths,
+            vec!["/virtual/project/docs/guide.md", "/virtual/project/src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn test_content_paths_respect_exclude() {
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            content_paths: vec!["build.log".to_string()],
+            ..Default::default()
+        };
+        let exclude = vec!["*.log".to_string()];
+        let Traversal { files, .. } = traverse_source(&source(), &config, &[], &exclude).unwrap();
+        assert!(files.is_empty());
+    }
+
+    #[test]
+    fn test_tree_deeper_than_content() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("top.rs", "// level 1")
+            .with_file("one/second.rs", "// level 2")
+            .with_file("one/two/third.rs", "// level 3")
+            .with_file("one/two/three/four/leaf.rs", "// level 5");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            tree_max_depth: Some(4),
+            content_max_depth: Some(2),
+            ..Default::default()
+        };
+        let Traversal { tree, files, .. } = traverse_source(&source, &config, &[], &[]).unwrap();
+
+        // The tree shows the level 4 directory, but not the file below it
+        assert!(tree.contains("third.rs"));
+        assert!(tree.contains("four"));
+        assert!(!tree.contains("leaf.rs"));
+
+        let paths: Vec<&str> = files.iter().map(|f| f["path"].as_str().unwrap()).collect();
+        assert_eq!(
+            paths,
+            vec!["/virtual/project/one/second.rs", "/virtual/project/top.rs"]
+        );
+    }
+
+    #[test]
+    fn test_max_tree_entries_collapses_wide_directories() {
+        let mut source = MemorySource::new("/virtual/project").with_file("README.md", "# Project");
+        for index in 0..10 {
+            source = source.with_file(format!("fixtures/case_{}.json", index), "{}");
+        }
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            max_tree_entries: Some(3),
+            ..Default::default()
+        };
+        let Traversal { tree, files, .. } = traverse_source(&source, &config, &[], &[]).unwrap();
+
+        assert!(tree.contains("└── fixtures\n    ├── case_0.json\n    ├── case_1.json\n    ├── case_2.json\n    └── ... [truncated] ... (7 more)"), "{}", tree);
+        assert!(!tree.contains("case_3.json"));
+        // The root has two entries only, so nothing is collapsed there
+        assert_eq!(tree.matches("more)").count(), 1);
+        // The contents of the collapsed files are still included
+        assert_eq!(files.len(), 11);
+
+        // The collapsed entries carry the truncation marker
+        let config = Code2PromptConfig {
+            truncation_marker: Some("[cut]".to_string()),
+            ..config
+        };
+        let Traversal { tree, .. } = traverse_source(&source, &config, &[], &[]).unwrap();
+        assert!(tree.contains("└── [cut] (7 more)"), "{}", tree);
+    }
+
+    #[test]
+    fn test_max_tree_entries_sorts_every_directory_by_name() {
+        let file = |path: &str, executable: bool| serde_json::json!({"path": path, "code": "", "executable": executable});
+        let files = vec![
+            file("bin/zz", false),
+            file("bin/run$", false),
+            file("bin/run", true),
+            file("lib/b.rs", false),
+            file("lib/a.rs", false),
+        ];
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            relative_paths: true,
+            max_tree_entries: Some(2),
+            ..Default::default()
+        };
+        let tree = files_tree_with_config(&files, &config);
+
+        // Capped by bare name, not by the label of the executable
+        assert!(tree.contains("├── bin\n│   ├── run*\n│   ├── run$\n│   └── ... [truncated] ... (1 more)"), "{}", tree);
+        assert!(tree.contains("└── lib\n    ├── a.rs\n    └── b.rs"), "{}", tree);
+
+        // The tree without a configuration keeps its signature
+        assert_eq!(
+            files_tree(&files[3..], Path::new("/virtual/project"), true),
+            files_tree_with_config(&files[3..], &Code2PromptConfig { max_tree_entries: None, ..config })
+        );
+    }
+
+    #[test]
+    fn test_no_tree_skips_the_tree() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("top.rs", "// level 1")
+            .with_file("nested/inner.rs", "// level 2");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            no_tree: true,
+            ..Default::default()
+        };
+        let Traversal { tree, files, .. } = traverse_source(&source, &config, &[], &[]).unwrap();
+        assert!(tree.is_empty());
+        assert_eq!(files.len(), 2);
+
+        let rendered = render_prompt(&source, &config).unwrap().prompt;
+        assert!(!rendered.contains("Source Tree:"));
+        assert!(!rendered.contains("└── "));
+        assert!(rendered.contains("// level 2"));
+    }
+
+    #[test]
+    fn test_language_override_disambiguates_a_header() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("include/widget.h", "class Widget {};")
+            .with_file("include/legacy.h", "struct legacy;")
+            .with_file("src/solve.m", "x = A \\ b;");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            language_override: [
+                ("include/widget.h".to_string(), "cpp".to_string()),
+                ("src/*.m".to_string(), "matlab".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let files = traverse_source(&source, &config, &[], &[]).unwrap().files;
+        let file = |name: &str| files.iter().find(|f| f["path"].as_str().unwrap().ends_with(name)).unwrap();
+
+        assert_eq!(file("widget.h")["code"], "```cpp\nclass Widget {};\n```");
+        assert_eq!(file("widget.h")["language"], "cpp");
+        assert_eq!(file("solve.m")["language"], "matlab");
+
+        // Files without an override keep the language of their extension
+        assert_eq!(file("legacy.h")["code"], "```h\nstruct legacy;\n```");
+        assert_eq!(file("legacy.h")["language"], "c");
+    }
+
+    #[test]
+    fn test_strip_ansi_cleans_color_codes() {
+        let log = "\x1b[1;32mPASS\x1b[0m tests::parse\n\x1b]0;title\x07\x1b[31mFAIL\x1b[0m tests::render\n";
+        let source = MemorySource::new("/virtual/project").with_file("build.log", log);
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            strip_ansi: true,
+            ..Default::default()
+        };
+        let files = traverse_source(&source, &config, &[], &[]).unwrap().files;
+        assert_eq!(files[0]["code"], "```log\nPASS tests::parse\nFAIL tests::render\n\n```");
+
+        // Off by default
+        let config = Code2PromptConfig {
+            strip_ansi: false,
+            ..config
+        };
+        let files = traverse_source(&source, &config, &[], &[]).unwrap().files;
+        assert!(files[0]["code"].as_str().unwrap().contains("\x1b[1;32m"));
+    }
+
+    #[test]
+    fn test_tree_line_stats() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("main.rs", "fn main() {\n    run();\n}\n")
+            .with_file("src/a.rs", "// a\n")
+            .with_file("src/nested/b.rs", "// b\n// b\n// b\n// b\n// b\n// b\n")
+            .with_file("src/empty.rs", "");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            tree_line_stats: true,
+            ..Default::default()
+        };
+        let tree = traverse_source(&source, &config, &[], &[]).unwrap().tree;
+
+        assert!(tree.contains("main.rs (3 lines, 30%)"), "{}", tree);
+        assert!(tree.contains("a.rs (1 line, 10%)"), "{}", tree);
+        assert!(tree.contains("b.rs (6 lines, 60%)"), "{}", tree);
+        // Directories sum their files, and files left out of the prompt aren't annotated
+        assert!(tree.contains("nested (6 lines)"), "{}", tree);
+        assert!(tree.contains("src (7 lines)"), "{}", tree);
+        assert!(tree.contains("empty.rs\n") || tree.ends_with("empty.rs"), "{}", tree);
+
+        let config = Code2PromptConfig {
+            tree_line_stats: false,
+            ..config
+        };
+        let tree = traverse_source(&source, &config, &[], &[]).unwrap().tree;
+        assert!(!tree.contains("lines"));
+    }
+
+    #[test]
+    fn test_fence_is_longer_than_the_backticks_of_the_content() {
+        let readme = "# Usage\n\n```sh\ncargo run\n```\n";
+        let source = MemorySource::new("/virtual/project")
+            .with_file("README.md", readme)
+            .with_file("nested.md", "`````\n````md\n```\n````\n`````\n")
+            .with_file("plain.rs", "let s = \"`\";");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            ..Default::default()
+        };
+        let files = traverse_source(&source, &config, &[], &[]).unwrap().files;
+        let code = |name: &str| {
+            let file = files.iter().find(|f| f["path"].as_str().unwrap().ends_with(name)).unwrap();
+            file["code"].as_str().unwrap().to_string()
+        };
+
+        // The outer fence only closes on its last line
+        let readme_block = code("README.md");
+        assert_eq!(readme_block, format!("````md\n{}\n````", readme));
+        let closing = readme_block.lines().filter(|line| line.starts_with("````")).count();
+        assert_eq!(closing, 2);
+
+        assert!(code("nested.md").starts_with("``````md\n"));
+        assert!(code("nested.md").ends_with("\n``````"));
+        assert!(code("plain.rs").starts_with("```rs\n"));
+    }
+
+    #[test]
+    fn test_fence_info_template() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("src/lib.rs", "pub fn lib() {}")
+            .with_file("build.gradle", "apply plugin: 'java'");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            fence_info_template: Some("{{language}} title=\"{{path}}\"".to_string()),
+            language_override: [("build.gradle".to_string(), "groovy".to_string())].into(),
+            ..Default::default()
+        };
+        let files = traverse_source(&source, &config, &[], &[]).unwrap().files;
+        let first_line = |name: &str| {
+            let file = files.iter().find(|f| f["path"].as_str().unwrap().ends_with(name)).unwrap();
+            file["code"].as_str().unwrap().lines().next().unwrap().to_string()
+        };
+
+        assert_eq!(first_line("lib.rs"), "```rs title=\"src/lib.rs\"");
+        assert_eq!(first_line("build.gradle"), "```groovy title=\"build.gradle\"");
+    }
+
+    /// A source whose `secret` directory can't be read.
+    struct UnreadableSource(MemorySource);
+
+    impl FileSource for UnreadableSource {
+        fn walk<'a>(
+            &'a self,
+            root: &Path,
+        ) -> Box<dyn Iterator<Item = anyhow::Result<PathBuf>> + 'a> {
+            self.0.walk(root)
+        }
+
+        fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+            if path.to_string_lossy().contains("/secret/") {
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, "denied"));
+            }
+            self.0.read(path)
+        }
+
+        fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+            self.0.metadata(path)
+        }
+    }
+
+    #[test]
+    fn test_unreadable_file_is_skipped() {
+        let source = UnreadableSource(
+            MemorySource::new("/virtual/project")
+                .with_file("main.rs", "fn main() {}")
+                .with_file("secret/data.rs", "const DATA: u8 = 0;"),
+        );
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            ..Default::default()
+        };
+        let traversal = traverse_source(&source, &config, &[], &[]).unwrap();
+
+        assert_eq!(traversal.files.len(), 1);
+        assert_eq!(
+            traversal.skipped,
+            vec![SkippedEntry {
+                path: "/virtual/project/secret/data.rs".to_string(),
+                reason: "io-error".to_string(),
+            }]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unreadable_directory_is_skipped() {
+        use code2prompt::traverse_with_config;
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+        let locked = dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::write(locked.join("hidden.rs"), "fn hidden() {}").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        // Privileged users can read the directory anyway, so there is nothing to simulate
+        if fs::read_dir(&locked).is_ok() {
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+            return;
+        }
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let result = traverse_with_config(&config, &[], &[]);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let (_, files) = result.unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0]["path"].as_str().unwrap().ends_with("main.rs"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks_annotates_links() {
+        use std::fs;
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("project");
+        let shared = dir.path().join("shared");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&shared).unwrap();
+        fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+        fs::write(shared.join("util.rs"), "pub fn util() {}").unwrap();
+        symlink("main.rs", root.join("alias.rs")).unwrap();
+        symlink(&shared, root.join("vendor")).unwrap();
+
+        let config = Code2PromptConfig {
+            path: root.clone(),
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        let traversal = traverse_source(&FsSource, &config, &[], &[]).unwrap();
+
+        assert!(traversal.tree.contains("alias.rs -> main.rs"));
+        assert!(traversal.tree.contains(&format!("vendor -> {}", shared.display())));
+        assert!(traversal.tree.contains("util.rs"));
+
+        let alias = traversal
+            .files
+            .iter()
+            .find(|f| f["path"].as_str().unwrap().ends_with("alias.rs"))
+            .unwrap();
+        assert_eq!(alias["is_symlink"], true);
+        assert_eq!(alias["link_target"], "main.rs");
+        let main = traversal
+            .files
+            .iter()
+            .find(|f| f["path"].as_str().unwrap().ends_with("main.rs"))
+            .unwrap();
+        assert!(main.get("is_symlink").is_none());
+
+        // Without following, links are listed as plain entries
+        let config = Code2PromptConfig {
+            follow_symlinks: false,
+            ..config
+        };
+        let traversal = traverse_source(&FsSource, &config, &[], &[]).unwrap();
+        assert!(!traversal.tree.contains("->"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_links_are_annotated_along_with_line_stats() {
+        use std::fs;
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("project");
+        let shared = dir.path().join("shared");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&shared).unwrap();
+        fs::write(root.join("main.rs"), "fn main() {}
+").unwrap();
+        fs::write(shared.join("util.rs"), "pub fn util() {}
+").unwrap();
+        symlink("main.rs", root.join("alias.rs")).unwrap();
+        symlink(&shared, root.join("vendor")).unwrap();
+
+        let config = Code2PromptConfig {
+            path: root.clone(),
+            follow_symlinks: true,
+            tree_line_stats: true,
+            ..Default::default()
+        };
+        let tree = traverse_source(&FsSource, &config, &[], &[]).unwrap().tree;
+
+        assert!(tree.contains("alias.rs (1 line, 33%) -> main.rs"), "{}", tree);
+        assert!(tree.contains(&format!("vendor (1 line) -> {}", shared.display())), "{}", tree);
+        assert!(tree.contains("util.rs (1 line, 33%)"), "{}", tree);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_show_permissions_marks_executables() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("scripts")).unwrap();
+        fs::write(dir.path().join("scripts/build.sh"), "#!/bin/sh\necho build\n").unwrap();
+        fs::set_permissions(dir.path().join("scripts/build.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+        fs::set_permissions(dir.path().join("main.rs"), fs::Permissions::from_mode(0o644)).unwrap();
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            show_permissions: true,
+            tree_line_stats: true,
+            ..Default::default()
+        };
+        let traversal = traverse_source(&FsSource, &config, &[], &[]).unwrap();
+        let file = |name: &str| {
+            traversal
+                .files
+                .iter()
+                .find(|f| f["path"].as_str().unwrap().ends_with(name))
+                .unwrap()
+                .clone()
+        };
+
+        assert_eq!(file("build.sh")["executable"], true);
+        assert_eq!(file("build.sh")["mode"], "755");
+        assert_eq!(file("main.rs")["executable"], false);
+        assert_eq!(file("main.rs")["mode"], "644");
+        // The mark follows the name, before the line stats
+        assert!(traversal.tree.contains("build.sh* (2 lines"), "{}", traversal.tree);
+        assert!(!traversal.tree.contains("main.rs*"));
+
+        // Without the option, neither the metadata nor the marks are added
+        let config = Code2PromptConfig {
+            show_permissions: false,
+            ..config
+        };
+        let traversal = traverse_source(&FsSource, &config, &[], &[]).unwrap();
+        assert!(traversal.files.iter().all(|f| f.get("executable").is_none() && f.get("mode").is_none()));
+        assert!(!traversal.tree.contains('*'));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_tree_legend_lists_active_annotations() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("build.sh"), "#!/bin/sh\necho build\n").unwrap();
+        fs::set_permissions(dir.path().join("build.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            show_permissions: true,
+            tree_line_stats: true,
+            tree_legend: true,
+            ..Default::default()
+        };
+        let tree = traverse_source(&FsSource, &config, &[], &[]).unwrap().tree;
+        assert!(tree.starts_with("Legend:\n"), "{}", tree);
+        assert!(tree.contains("(N lines, P%)"));
+        assert!(tree.contains("name*"));
+        // Symbolic links aren't followed, so their annotation isn't listed
+        assert!(!tree.contains("->"));
+
+        let config = Code2PromptConfig {
+            show_permissions: false,
+            ..config
+        };
+        let tree = traverse_source(&FsSource, &config, &[], &[]).unwrap().tree;
+        assert!(tree.contains("(N lines, P%)"));
+        assert!(!tree.contains("name*"));
+
+        // Without annotations, there is nothing to explain
+        let config = Code2PromptConfig {
+            tree_line_stats: false,
+            ..config
+        };
+        let tree = traverse_source(&FsSource, &config, &[], &[]).unwrap().tree;
+        assert!(!tree.contains("Legend:"));
+
+        // The executable collapsed out of the tree isn't explained, unlike the collapsed entry
+        let config = Code2PromptConfig {
+            show_permissions: true,
+            max_tree_entries: Some(0),
+            ..config
+        };
+        let tree = traverse_source(&FsSource, &config, &[], &[]).unwrap().tree;
+        assert!(!tree.contains("name*"), "{}", tree);
+        assert!(tree.contains("  ... [truncated] ... (N more)  the other N entries"), "{}", tree);
+    }
+
+    #[test]
+    fn test_inaccessible_root_is_an_error() {
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/nonexistent/code2prompt/root"),
+            ..Default::default()
+        };
+        assert!(code2prompt::traverse_with_config(&config, &[], &[]).is_err());
+    }
+
+    #[test]
+    fn test_strip_leading_comment_block() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("main.rs", "// License header
+
+fn main() {}")
+            .with_file("app.py", "# HEADER v1
+import os");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            strip_leading_comment_block: true,
+            no_codeblock: true,
+            ..Default::default()
+        };
+        let Traversal { files, .. } = traverse_source(&source, &config, &[], &[]).unwrap();
+        assert_eq!(files[0]["code"], "import os");
+        assert_eq!(files[1]["code"], "fn main() {}");
+
+        // Line numbers would be off, so nothing is stripped with them
+        let config = Code2PromptConfig {
+            line_number: true,
+            ..config
+        };
+        let Traversal { files, .. } = traverse_source(&source, &config, &[], &[]).unwrap();
+        assert!(files[1]["code"].as_str().unwrap().contains("License header"));
+    }
+
+    #[test]
+    fn test_header_pattern() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("app.py", "# HEADER v1
+# more
+import os");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            header_pattern: Some(r"# HEADER v\d+\n".to_string()),
+            no_codeblock: true,
+            ..Default::default()
+        };
+        let Traversal { files, .. } = traverse_source(&source, &config, &[], &[]).unwrap();
+        assert_eq!(files[0]["code"], "# more\nimport os");
+    }
+
+    #[test]
+    fn test_baseline_skips_unchanged_files() {
+        let baseline = tempfile::tempdir().unwrap();
+        std::fs::create_dir(baseline.path().join("src")).unwrap();
+        std::fs::write(baseline.path().join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(baseline.path().join("src/app.rs"), "pub fn app() {}").unwrap();
+        std::fs::write(baseline.path().join("src/icon.bin"), [0xff, 0x00, 0x01]).unwrap();
+
+        // The binary files differ only by invalid UTF-8 bytes
+        let source = MemorySource::new("/virtual/project")
+            .with_file("src/main.rs", "fn main() {}")
+            .with_file("src/app.rs", "pub fn app() { run(); }")
+            .with_file("src/custom.rs", "pub fn custom() {}")
+            .with_file("src/icon.bin", vec![0xfe, 0x00, 0x01]);
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            baseline: Some(baseline.path().to_path_buf()),
+            binary_as_base64: true,
+            ..Default::default()
+        };
+        let traversal = traverse_source(&source, &config, &[], &[]).unwrap();
+
+        let paths: Vec<&str> = traversal.files.iter().map(|f| f["path"].as_str().unwrap()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/virtual/project/src/app.rs",
+                "/virtual/project/src/custom.rs",
+                "/virtual/project/src/icon.bin"
+            ]
+        );
+        assert_eq!(
+            traversal.skipped,
+            vec![SkippedEntry {
+                path: "/virtual/project/src/main.rs".to_string(),
+                reason: "unchanged-from-baseline".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_skip_empty_lists_blank_files() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("main.rs", "fn main() {}")
+            .with_file("empty.rs", "")
+            .with_file("blank.rs", "  \n\t\n");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            skip_empty: true,
+            ..Default::default()
+        };
+        let traversal = traverse_source(&source, &config, &[], &[]).unwrap();
+
+        assert_eq!(traversal.files.len(), 1);
+        assert!(traversal.tree.contains("empty.rs"));
+        assert_eq!(
+            traversal.skipped,
+            vec![
+                SkippedEntry {
+                    path: "/virtual/project/blank.rs".to_string(),
+                    reason: "empty".to_string(),
+                },
+                SkippedEntry {
+                    path: "/virtual/project/empty.rs".to_string(),
+                    reason: "empty".to_string(),
+                },
+            ]
+        );
+
+        // Without the option, they are left out silently
+        let config = Code2PromptConfig {
+            skip_empty: false,
+            ..config
+        };
+        let traversal = traverse_source(&source, &config, &[], &[]).unwrap();
+        assert_eq!(traversal.files.len(), 1);
+        assert!(traversal.skipped.is_empty());
+    }
+
+    #[test]
+    fn test_min_file_size_skips_files_below_the_floor() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("below.rs", "x".repeat(9))
+            .with_file("at.rs", "x".repeat(10))
+            .with_file("above.rs", "x".repeat(11));
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            min_file_size: Some(10),
+            ..Default::default()
+        };
+        let traversal = traverse_source(&source, &config, &[], &[]).unwrap();
+
+        let mut paths: Vec<&str> = traversal.files.iter().map(|f| f["path"].as_str().unwrap()).collect();
+        paths.sort();
+        assert_eq!(paths, vec!["/virtual/project/above.rs", "/virtual/project/at.rs"]);
+        assert_eq!(
+            traversal.skipped,
+            vec![SkippedEntry {
+                path: "/virtual/project/below.rs".to_string(),
+                reason: "below-min-size".to_string(),
+            }]
+        );
+
+        // An include pattern matching the file overrides the floor
+        let include = vec!["**/below.rs".to_string()];
+        let traversal = traverse_source(&source, &config, &include, &[]).unwrap();
+        assert_eq!(traversal.files.len(), 1);
+        assert!(traversal.skipped.is_empty());
+    }
+
+    #[test]
+    fn test_min_file_tokens_skips_files_below_the_floor() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("one_line.rs", "mod a;")
+            .with_file("lib.rs", "pub fn add(left: u64, right: u64) -> u64 {\n    left + right\n}\n");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            min_file_tokens: Some(5),
+            ..Default::default()
+        };
+        let traversal = traverse_source(&source, &config, &[], &[]).unwrap();
+
+        assert_eq!(traversal.files.len(), 1);
+        assert_eq!(traversal.files[0]["path"], "/virtual/project/lib.rs");
+        assert_eq!(traversal.skipped[0].reason, "below-min-tokens");
+    }
+
+    #[test]
+    fn test_content_deny_skips_matching_files() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("main.rs", "fn main() {}")
+            .with_file("generated.rs", "// Code generated by protoc. DO NOT EDIT.\nfn generated() {}");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            content_deny: vec![r"DO NOT EDIT".to_string()],
+            ..Default::default()
+        };
+        let traversal = traverse_source(&source, &config, &[], &[]).unwrap();
+
+        assert!(traversal.tree.contains("generated.rs"));
+        assert_eq!(traversal.files.len(), 1);
+        assert_eq!(
+            traversal.skipped,
+            vec![SkippedEntry {
+                path: "/virtual/project/generated.rs".to_string(),
+                reason: "denied-content".to_string(),
+            }]
+        );
+
+        // Only the beginning of each file is checked
+        let late_banner = format!("{}\n// DO NOT EDIT", "\n".repeat(CONTENT_DENY_SCAN_BYTES));
+        let source = MemorySource::new("/virtual/project").with_file("late.rs", late_banner);
+        let traversal = traverse_source(&source, &config, &[], &[]).unwrap();
+        assert!(traversal.skipped.is_empty());
+
+        let config = Code2PromptConfig {
+            content_deny: vec!["(".to_string()],
+            ..config
+        };
+        assert!(traverse_source(&source, &config, &[], &[]).is_err());
+    }
+
+    #[test]
+    fn test_readme_first_within_directory() {
+        let source = source()
+            .with_file("src/cli/args.rs", "pub struct Args;")
+            .with_file("src/cli/usage.md", "# Usage");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            readme_first: true,
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let prompt = code2prompt::render_prompt(&source, &config).unwrap();
+        let paths: Vec<&str> = prompt.files.iter().map(|f| f["path"].as_str().unwrap()).collect();
+
+        // The doc file moves ahead of the code of its own directory only
+        assert_eq!(
+            paths,
+            vec![
+                "/virtual/project/build.log",
+                "/virtual/project/docs/guide.md",
+                "/virtual/project/src/cli/usage.md",
+                "/virtual/project/src/cli/args.rs",
+                "/virtual/project/src/main.rs",
+                "/virtual/project/src/util.rs",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_max_files_caps_after_sorting() {
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            order: vec!["src/util.rs".to_string(), "docs/*".to_string()],
+            max_files: Some(2),
+            no_clipboard: true,
+            quiet: true,
+            ..Default::default()
+        };
+        let prompt = code2prompt::render_prompt(&source(), &config).unwrap();
+        let paths: Vec<&str> = prompt.files.iter().map(|f| f["path"].as_str().unwrap()).collect();
+
+        assert_eq!(
+            paths,
+            vec!["/virtual/project/src/util.rs", "/virtual/project/docs/guide.md"]
+        );
+        assert_eq!(prompt.omitted_files, 2);
+        assert!(prompt.prompt.contains("main.rs"), "The tree shows every file by default");
+
+        // The tree only shows the included files when excluding from the tree
+        let config = Code2PromptConfig {
+            exclude_from_tree: true,
+            ..config
+        };
+        let prompt = code2prompt::render_prompt(&source(), &config).unwrap();
+        assert_eq!(prompt.omitted_files, 2);
+        assert!(prompt.prompt.contains("util.rs"));
+        assert!(!prompt.prompt.contains("main.rs"));
+        assert!(!prompt.prompt.contains("build.log"));
+
+        // The rebuilt tree keeps its annotations and their legend
+        let config = Code2PromptConfig {
+            tree_line_stats: true,
+            tree_legend: true,
+            ..config
+        };
+        let prompt = code2prompt::render_prompt(&source(), &config).unwrap();
+        assert!(prompt.prompt.contains("Legend:\n  (N lines, P%)"), "{}", prompt.prompt);
+        assert!(prompt.prompt.contains("util.rs (1 line, "), "{}", prompt.prompt);
+    }
+
+    #[test]
+    fn test_on_file_events_match_files() {
+        let events = std::cell::RefCell::new(Vec::new());
+        let on_file = |file: &FileEntry| events.borrow_mut().push(file.clone());
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            tokens: true,
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let prompt = code2prompt::render_prompt_with_callback(&source(), &config, Some(&on_file)).unwrap();
+        let events = events.into_inner();
+
+        assert_eq!(events.len(), prompt.files.len());
+        for (event, file) in events.iter().zip(&prompt.files) {
+            assert_eq!(event.path, file["path"].as_str().unwrap());
+            assert_eq!(event.tokens, Some(file["tokens"].as_u64().unwrap() as usize));
+        }
+        let main = events.iter().find(|e| e.relative_path == "src/main.rs").unwrap();
+        assert_eq!(main.size, "fn main() {}".len() as u64);
+        assert_eq!(main.language.as_deref(), Some("rust"));
+        assert!(!main.tokens_estimated);
+    }
+
+    #[test]
+    fn test_explicit_order_overrides_default() {
+        let source = source()
+            .with_file("src/cli/args.rs", "pub struct Args;")
+            .with_file("src/a_first.rs", "pub fn first() {}");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            order: vec![
+                "./src/main.rs".to_string(),
+                "src/*.rs".to_string(),
+                "docs/guide.md".to_string(),
+            ],
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let prompt = code2prompt::render_prompt(&source, &config).unwrap();
+        let paths: Vec<&str> = prompt.files.iter().map(|f| f["path"].as_str().unwrap()).collect();
+
+        // Globs keep the discovery order, and unlisted files come last
+        assert_eq!(
+            paths,
+            vec![
+                "/virtual/project/src/main.rs",
+                "/virtual/project/src/a_first.rs",
+                "/virtual/project/src/cli/args.rs",
+                "/virtual/project/src/util.rs",
+                "/virtual/project/docs/guide.md",
+                "/virtual/project/build.log",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_tests_excludes_test_files() {
+        let source = MemorySource::new("/virtual/tests/project")
+            .with_file("main.go", "package main")
+            .with_file("main_test.go", "package main")
+            .with_file("tests/integration.py", "import app")
+            .with_file("web/app.test.ts", "test()");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/tests/project"),
+            no_tests: true,
+            ..Default::default()
+        };
+        let paths = |include: &[String]| {
+            let Traversal { files, .. } = traverse_source(&source, &config, include, &[]).unwrap();
+            files
+                .iter()
+                .map(|f| f["path"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // The root directory is not considered, even though it is named like a test directory
+        assert_eq!(paths(&[]), vec!["/virtual/tests/project/main.go"]);
+
+        // Explicit includes override the heuristic
+        assert_eq!(
+            paths(&["*.go".to_string()]),
+            vec!["/virtual/tests/project/main.go", "/virtual/tests/project/main_test.go"]
+        );
+    }
+
+    #[test]
+    fn test_exclude_languages() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("main.go", "package main")
+            .with_file("api/service.proto", "syntax = \"proto3\";")
+            .with_file("db/dump.SQL", "INSERT INTO t VALUES (1);")
+            .with_file("db/schema.sql", "CREATE TABLE t (id INT);")
+            .with_file("README.md", "# Project");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            exclude_languages: vec!["SQL".to_string(), "protobuf".to_string()],
+            ..Default::default()
+        };
+        let paths = |config: &Code2PromptConfig, include: &[String]| {
+            let Traversal { files, .. } = traverse_source(&source, config, include, &[]).unwrap();
+            files
+                .iter()
+                .map(|f| f["path"].as_str().unwrap().trim_start_matches("/virtual/project/").to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(paths(&config, &[]), vec!["README.md", "main.go"]);
+
+        // Excluded languages win over include patterns, unless they take priority
+        let include = ["*.sql".to_string(), "*.go".to_string()];
+        assert_eq!(paths(&config, &include), vec!["main.go"]);
+        let config = Code2PromptConfig {
+            include_priority: true,
+            ..config
+        };
+        assert_eq!(paths(&config, &include), vec!["db/schema.sql", "main.go"]);
+    }
+
+    #[test]
+    fn test_crlf_file_normalized_to_lf() {
+        let source = MemorySource::new("/virtual/project").with_file("main.rs", "fn main() {\r\n    run();\r\n}\r\n");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            line_number: true,
+            ..Default::default()
+        };
+        let code = |config: &Code2PromptConfig| {
+            let Traversal { files, .. } = traverse_source(&source, config, &[], &[]).unwrap();
+            files[0]["code"].as_str().unwrap().to_string()
+        };
+
+        let lf = code(&config);
+        assert!(!lf.contains('\r'), "{:?}", lf);
+        assert!(lf.contains("   1 | fn main() {\n   2 |     run();\n   3 | }\n"), "{:?}", lf);
+
+        // Converted to CRLF, every line ends the same way, numbered like the LF ones
+        let config = Code2PromptConfig {
+            line_ending: LineEnding::Crlf,
+            ..config
+        };
+        let crlf = code(&config);
+        assert_eq!(crlf, lf.replace('\n', "\r\n"));
+
+        let config = Code2PromptConfig {
+            line_ending: LineEnding::Preserve,
+            line_number: false,
+            ..config
+        };
+        assert!(code(&config).contains("fn main() {\r\n    run();\r\n}\r\n"));
+    }
+
+    #[test]
+    fn test_iter_files_matches_traversal() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "pub fn lib() {}").unwrap();
+        std::fs::write(dir.path().join("notes.md"), "# Notes").unwrap();
+        std::fs::write(dir.path().join("build.log"), "noise").unwrap();
+        std::fs::write(dir.path().join("empty.txt"), "").unwrap();
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            exclude: Some("*.log".to_string()),
+            line_number: true,
+            ..Default::default()
+        };
+        let exclude = vec!["*.log".to_string()];
+        let (_, collected) = code2prompt::traverse_with_config(&config, &[], &exclude).unwrap();
+        let streamed: Vec<serde_json::Value> = code2prompt::iter_files(&config)
+            .map(|file| file.unwrap().to_json())
+            .collect();
+        assert_eq!(streamed.len(), 3);
+        assert_eq!(streamed, collected);
+    }
+
+    #[test]
+    fn test_iter_source_files_yields_read_errors() {
+        let source = UnreadableSource(
+            MemorySource::new("/virtual/project")
+                .with_file("main.rs", "fn main() {}")
+                .with_file("secret/data.rs", "const DATA: u8 = 0;"),
+        );
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            ..Default::default()
+        };
+        let results: Vec<_> = iter_source_files(&source, &config, &[], &[]).unwrap().collect();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap().relative_path, "main.rs");
+        let error = results[1].as_ref().unwrap_err().to_string();
+        assert!(error.contains("/virtual/project/secret/data.rs"));
+
+        // Invalid options are reported before anything is read
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/nonexistent/code2prompt/root"),
+            ..Default::default()
+        };
+        let results: Vec<_> = code2prompt::iter_files(&config).collect();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }
+
+    #[test]
+    fn test_cache_dir_regenerates_modified_files_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("project");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(root.join("a.rs"), "fn a() {}").unwrap();
+        std::fs::write(root.join("b.rs"), "fn b() {}").unwrap();
+        std::fs::write(root.join("c.rs"), "fn c() {}").unwrap();
+        let config = Code2PromptConfig {
+            path: root.clone(),
+            cache_dir: Some(dir.path().join("cache")),
+            ..Default::default()
+        };
+
+        let first = traverse_source(&FsSource, &config, &[], &[]).unwrap();
+        assert_eq!((first.cache_hits, first.cache_misses), (0, 3));
+
+        std::fs::write(root.join("b.rs"), "fn b() { todo!() }").unwrap();
+        let second = traverse_source(&FsSource, &config, &[], &[]).unwrap();
+        assert_eq!((second.cache_hits, second.cache_misses), (2, 1));
+        let code = |traversal: &Traversal, name: &str| {
+            let file = traversal.files.iter().find(|f| f["path"].as_str().unwrap().ends_with(name));
+            file.unwrap()["code"].as_str().unwrap().to_string()
+        };
+        assert_eq!(code(&first, "a.rs"), code(&second, "a.rs"));
+        assert!(code(&second, "b.rs").contains("todo!()"));
+
+        // Formatting options are part of the key
+        let config = Code2PromptConfig {
+            line_number: true,
+            ..config
+        };
+        let third = traverse_source(&FsSource, &config, &[], &[]).unwrap();
+        assert_eq!((third.cache_hits, third.cache_misses), (0, 3));
+    }
+}
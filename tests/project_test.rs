@@ -0,0 +1,223 @@
+use code2prompt::project::{detect_project, parse_manifest, workspace_package_path, ProjectDependency, ProjectMetadata};
+use code2prompt::{render_prompt, Code2PromptConfig, MemorySource};
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependency(name: &str, version: &str) -> ProjectDependency {
+        ProjectDependency {
+            name: name.to_string(),
+            version: Some(version.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_parse_cargo_manifest() {
+        let manifest = r#"
+[package]
+name = "demo"
+version = "0.3.1"
+description = "A demo crate"
+
+[dependencies]
+anyhow = "1.0"
+serde = { version = "1.0", features = ["derive"] }
+local = { path = "../local" }
+
+[dev-dependencies]
+tempfile = "3.3"
+"#;
+        assert_eq!(
+            parse_manifest("Cargo.toml", manifest).unwrap(),
+            ProjectMetadata {
+                manifest: "Cargo.toml".to_string(),
+                name: Some("demo".to_string()),
+                version: Some("0.3.1".to_string()),
+                description: Some("A demo crate".to_string()),
+                dependencies: vec![
+                    dependency("anyhow", "1.0"),
+                    ProjectDependency {
+                        name: "local".to_string(),
+                        version: None,
+                    },
+                    dependency("serde", "1.0"),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_npm_manifest() {
+        let manifest = r#"{
+  "name": "demo-app",
+  "version": "1.2.0",
+  "description": "A demo app",
+  "dependencies": { "react": "^18.2.0", "lodash": "~4.17.21" },
+  "devDependencies": { "jest": "^29.0.0" }
+}"#;
+        assert_eq!(
+            parse_manifest("package.json", manifest).unwrap(),
+            ProjectMetadata {
+                manifest: "package.json".to_string(),
+                name: Some("demo-app".to_string()),
+                version: Some("1.2.0".to_string()),
+                description: Some("A demo app".to_string()),
+                dependencies: vec![dependency("lodash", "~4.17.21"), dependency("react", "^18.2.0")],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_pyproject_requirements() {
+        let manifest = r#"
+[project]
+name = "demo"
+dependencies = ["requests>=2.31", "rich", "tomli; python_version < '3.11'"]
+"#;
+        let project = parse_manifest("pyproject.toml", manifest).unwrap();
+        assert_eq!(project.name.as_deref(), Some("demo"));
+        assert_eq!(project.version, None);
+        assert_eq!(
+            project.dependencies,
+            vec![
+                dependency("requests", ">=2.31"),
+                ProjectDependency {
+                    name: "rich".to_string(),
+                    version: None,
+                },
+                ProjectDependency {
+                    name: "tomli".to_string(),
+                    version: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unparseable_manifest_falls_back_to_the_next_one() {
+        let source = MemorySource::new("/project")
+            .with_file("Cargo.toml", "[package")
+            .with_file("package.json", r#"{ "name": "fallback" }"#);
+        let project = detect_project(&source, Path::new("/project")).unwrap();
+        assert_eq!(project.manifest, "package.json");
+        assert_eq!(project.name.as_deref(), Some("fallback"));
+
+        let source = MemorySource::new("/project").with_file("package.json", "{ invalid");
+        assert_eq!(detect_project(&source, Path::new("/project")), None);
+        let source = MemorySource::new("/project").with_file("main.rs", "fn main() {}");
+        assert_eq!(detect_project(&source, Path::new("/project")), None);
+    }
+
+    #[test]
+    fn test_default_template_renders_the_project_overview() {
+        let source = MemorySource::new("/project")
+            .with_file("Cargo.toml", "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n\n[dependencies]\nanyhow = \"1.0\"\n")
+            .with_file("src/main.rs", "fn main() {}");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/project"),
+            project_metadata: true,
+            ..Default::default()
+        };
+        let rendered = render_prompt(&source, &config).unwrap().prompt;
+        assert!(rendered.contains("Project Overview (Cargo.toml):"));
+        assert!(rendered.contains("- Name: demo\n- Version: 0.1.0\n- Dependencies: anyhow 1.0\n"));
+
+        let config = Code2PromptConfig {
+            project_metadata: false,
+            ..config
+        };
+        let rendered = render_prompt(&source, &config).unwrap().prompt;
+        assert!(!rendered.contains("Project Overview"));
+    }
+
+    #[test]
+    fn test_workspace_package_is_resolved_by_name() {
+        let source = MemorySource::new("/workspace")
+            .with_file("Cargo.toml", "[workspace]\nmembers = [\"crates/*\", \"tools/cli\"]\nexclude = [\"crates/legacy\"]\n")
+            .with_file("crates/core/Cargo.toml", "[package]\nname = \"demo-core\"\n")
+            .with_file("crates/core/src/lib.rs", "pub fn core() {}")
+            .with_file("crates/web/Cargo.toml", "[package]\nname = \"demo-web\"\n")
+            .with_file("crates/web/src/lib.rs", "pub fn web() {}")
+            .with_file("crates/legacy/Cargo.toml", "[package]\nname = \"demo-legacy\"\n")
+            .with_file("examples/nested/Cargo.toml", "[package]\nname = \"demo-fixture\"\n")
+            .with_file("tools/cli/Cargo.toml", "[package]\nname = \"demo-cli\"\n")
+            .with_file("tools/cli/src/main.rs", "fn main() {}");
+        let root = Path::new("/workspace");
+
+        assert_eq!(workspace_package_path(&source, root, "demo-web").unwrap(), root.join("crates/web"));
+        assert_eq!(workspace_package_path(&source, root, "demo-cli").unwrap(), root.join("tools/cli"));
+        // Excluded members and manifests outside of the members aren't packages of the workspace
+        assert!(workspace_package_path(&source, root, "demo-legacy").is_err());
+        assert!(workspace_package_path(&source, root, "demo-fixture").is_err());
+        assert!(workspace_package_path(&source, root, "unknown").is_err());
+
+        // The traversal is scoped to the directory of the package
+        let config = Code2PromptConfig {
+            path: root.to_path_buf(),
+            package: Some("demo-core".to_string()),
+            ..Default::default()
+        };
+        let rendered = render_prompt(&source, &config).unwrap();
+        let paths: Vec<&str> = rendered.files.iter().map(|f| f["path"].as_str().unwrap()).collect();
+        assert_eq!(paths, vec!["/workspace/crates/core/Cargo.toml", "/workspace/crates/core/src/lib.rs"]);
+        assert!(!rendered.prompt.contains("pub fn web()"));
+    }
+
+    #[test]
+    fn test_npm_workspace_package() {
+        let source = MemorySource::new("/workspace")
+            .with_file("package.json", r#"{ "private": true, "workspaces": { "packages": ["packages/*"] } }"#)
+            .with_file("packages/ui/package.json", r#"{ "name": "@demo/ui" }"#)
+            .with_file("packages/api/package.json", r#"{ "name": "@demo/api" }"#);
+        let root = Path::new("/workspace");
+        assert_eq!(workspace_package_path(&source, root, "@demo/api").unwrap(), root.join("packages/api"));
+
+        // Outside of a workspace, there is no package to scope to
+        let source = MemorySource::new("/project").with_file("Cargo.toml", "[package]\nname = \"demo\"\n");
+        assert!(workspace_package_path(&source, Path::new("/project"), "demo").is_err());
+    }
+
+    #[test]
+    fn test_root_package_of_a_workspace() {
+        let source = MemorySource::new("/workspace")
+            .with_file("Cargo.toml", "[package]\nname = \"demo\"\n\n[workspace]\nmembers = [\"crates/*\"]\n")
+            .with_file("crates/core/Cargo.toml", "[package]\nname = \"demo-core\"\n");
+        let root = Path::new("/workspace");
+        assert_eq!(workspace_package_path(&source, root, "demo").unwrap(), root.to_path_buf());
+        assert_eq!(workspace_package_path(&source, root, "demo-core").unwrap(), root.join("crates/core"));
+    }
+
+    #[test]
+    fn test_workspace_files_are_included_with_the_package() {
+        let source = MemorySource::new("/workspace")
+            .with_file("Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\n")
+            .with_file("Cargo.lock", "version = 3\n")
+            .with_file("README.md", "# Workspace")
+            .with_file("crates/core/Cargo.toml", "[package]\nname = \"demo-core\"\n")
+            .with_file("crates/core/src/lib.rs", "pub fn core() {}")
+            .with_file("crates/web/Cargo.toml", "[package]\nname = \"demo-web\"\n")
+            .with_file("crates/web/src/lib.rs", "pub fn web() {}");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/workspace"),
+            package: Some("demo-core".to_string()),
+            workspace_files: true,
+            ..Default::default()
+        };
+        let rendered = render_prompt(&source, &config).unwrap();
+        let paths: Vec<&str> = rendered.files.iter().map(|f| f["path"].as_str().unwrap()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/workspace/Cargo.lock",
+                "/workspace/Cargo.toml",
+                "/workspace/crates/core/Cargo.toml",
+                "/workspace/crates/core/src/lib.rs",
+            ]
+        );
+        // The tree only shows the files of the prompt
+        assert!(!rendered.prompt.contains("README.md"), "{}", rendered.prompt);
+        assert!(!rendered.prompt.contains("web"), "{}", rendered.prompt);
+    }
+}
@@ -0,0 +1,98 @@
+use code2prompt::rust_api::public_api;
+use code2prompt::{traverse_source, Code2PromptConfig, MemorySource};
+use std::path::PathBuf;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIXED: &str = r#"//! A counter.
+
+use std::fmt;
+
+/// Counts things.
+#[derive(Debug)]
+pub struct Counter {
+    /// The current count.
+    pub count: u32,
+    step: u32,
+}
+
+struct Internal;
+
+impl Counter {
+    /// Creates a counter.
+    pub fn new(step: u32) -> Self {
+        Self { count: 0, step }
+    }
+
+    fn bump(&mut self) {
+        self.count += self.step;
+    }
+}
+
+/// Things that can be reset.
+pub trait Reset {
+    fn reset(&mut self) {
+        println!("reset");
+    }
+}
+
+impl fmt::Display for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.count)
+    }
+}
+
+fn helper() -> u32 {
+    42
+}
+
+pub(crate) fn crate_only() {}
+
+pub const LIMIT: u32 = 10;
+"#;
+
+    #[test]
+    fn test_public_api_keeps_public_items() {
+        let api = public_api(MIXED).unwrap();
+
+        assert!(api.contains("//! A counter."));
+        assert!(api.contains("/// Counts things."));
+        assert!(api.contains("#[derive(Debug)]\npub struct Counter {"));
+        assert!(api.contains("pub count: u32,"));
+        assert!(api.contains("/// Creates a counter.\n    pub fn new(step: u32) -> Self;"));
+        assert!(api.contains("fn reset(&mut self);"));
+        assert!(api.contains("fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result;"));
+        assert!(api.contains("pub const LIMIT: u32 = 10;"));
+
+        // Private items, private fields and bodies are dropped
+        for private in ["step: u32,", "Internal", "bump", "helper", "crate_only", "use std::fmt", "println!", "write!"] {
+            assert!(!api.contains(private), "{} in:\n{}", private, api);
+        }
+    }
+
+    #[test]
+    fn test_pub_only_applies_to_rust_files() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("lib.rs", MIXED)
+            .with_file("broken.rs", "pub fn broken( {")
+            .with_file("notes.md", "fn helper() in a note");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            pub_only: true,
+            ..Default::default()
+        };
+        let files = traverse_source(&source, &config, &[], &[]).unwrap().files;
+        let code = |name: &str| {
+            let file = files.iter().find(|f| f["path"].as_str().unwrap().ends_with(name)).unwrap();
+            file["code"].as_str().unwrap().to_string()
+        };
+
+        assert!(code("lib.rs").contains("pub fn new(step: u32) -> Self;"));
+        assert!(!code("lib.rs").contains("fn helper"));
+        // Files that don't parse and other languages are included in full
+        assert!(code("broken.rs").contains("pub fn broken( {"));
+        assert!(code("notes.md").contains("fn helper() in a note"));
+    }
+}
@@ -0,0 +1,101 @@
+use code2prompt::{collapse_similar_files, render_prompt, similarity, Code2PromptConfig, MemorySource};
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migration(table: &str) -> String {
+        format!(
+            "CREATE TABLE {} (\n    id INTEGER PRIMARY KEY,\n    name TEXT NOT NULL,\n    email TEXT NOT NULL UNIQUE,\n    owner_id INTEGER REFERENCES accounts (id),\n    archived BOOLEAN NOT NULL DEFAULT FALSE,\n    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,\n    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP\n);\n",
+            table
+        )
+    }
+
+    fn source() -> MemorySource {
+        MemorySource::new("/virtual/project")
+            .with_file("migrations/001_users.sql", migration("users"))
+            .with_file("migrations/002_teams.sql", migration("teams"))
+            .with_file("migrations/003_projects.sql", migration("projects"))
+            .with_file("migrations/004_index.sql", "CREATE INDEX users_name ON users (name);\n")
+            .with_file("src/main.rs", "fn main() {}")
+    }
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(similarity(&migration("users"), &migration("users")), 1.0);
+        assert!(similarity(&migration("users"), &migration("teams")) > 0.8);
+        assert!(similarity(&migration("users"), "fn main() {}") < 0.1);
+    }
+
+    #[test]
+    fn test_near_duplicates_collapse_into_one_representative() {
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            collapse_similar: true,
+            ..Default::default()
+        };
+        let rendered = render_prompt(&source(), &config).unwrap();
+        let paths: Vec<&str> = rendered.files.iter().map(|f| f["path"].as_str().unwrap()).collect();
+        assert_eq!(paths.len(), 3, "{:?}", paths);
+        assert!(paths.iter().any(|p| p.ends_with("004_index.sql")));
+        assert!(paths.iter().any(|p| p.ends_with("main.rs")));
+
+        let representative = rendered
+            .files
+            .iter()
+            .find(|f| f.get("similar_files").is_some())
+            .unwrap();
+        let collapsed = representative["similar_files"].as_array().unwrap();
+        assert_eq!(collapsed.len(), 2);
+        assert!(rendered.prompt.contains(&format!(
+            "{}, collapsed into this one.",
+            representative["similar_summary"].as_str().unwrap()
+        )));
+        assert!(rendered.prompt.contains("2 similar files: migrations/"));
+        assert_eq!(rendered.prompt.matches("CREATE TABLE").count(), 1);
+
+        // A threshold out of reach keeps every file
+        let config = Code2PromptConfig {
+            similarity_threshold: Some(1.0),
+            ..config
+        };
+        assert_eq!(render_prompt(&source(), &config).unwrap().files.len(), 5);
+
+        let config = Code2PromptConfig {
+            similarity_threshold: Some(1.5),
+            ..config
+        };
+        assert!(render_prompt(&source(), &config).is_err());
+    }
+
+    #[test]
+    fn test_similarity_ignores_the_formatting_of_the_blocks() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("handlers/create_user.rs", "fn handle() {}\n")
+            .with_file("handlers/delete_user.rs", "fn handle() {}\n");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            collapse_similar: true,
+            line_number: true,
+            fence_info_template: Some("{{language}} title={{path}}".to_string()),
+            ..Default::default()
+        };
+        // The fences name different paths, but the contents are the same
+        let rendered = render_prompt(&source, &config).unwrap();
+        assert_eq!(rendered.files.len(), 1);
+        assert_eq!(rendered.files[0]["similar_summary"], "1 similar file: handlers/delete_user.rs");
+    }
+
+    #[test]
+    fn test_collapse_similar_files_skips_files_of_other_sizes() {
+        let files: Vec<serde_json::Value> = [migration("users"), "fn main() {}".to_string(), migration("teams")]
+            .into_iter()
+            .enumerate()
+            .map(|(index, code)| serde_json::json!({ "path": format!("{}.sql", index), "extension": "sql", "code": code }))
+            .collect();
+        let kept = collapse_similar_files(files, 0.8, Path::new(""), true);
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0]["similar_files"], serde_json::json!(["2.sql"]));
+    }
+}
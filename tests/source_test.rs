@@ -0,0 +1,182 @@
+use code2prompt::source::{FileSource, GitTreeSource, MemorySource};
+use code2prompt::{generate_prompt, traverse_source, Code2PromptConfig, Traversal};
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source() -> MemorySource {
+        MemorySource::new("/virtual/project")
+            .with_file("src/main.rs", "fn main() {}")
+            .with_file("src/lib/util.rs", "pub fn util() {}")
+            .with_file("README.md", "# Project")
+            .with_file("empty.txt", "")
+    }
+
+    #[test]
+    fn test_memory_source_walk() {
+        let source = source();
+        let entries: Vec<PathBuf> = source
+            .walk(Path::new("/virtual/project/src"))
+            .map(|e| e.unwrap())
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                PathBuf::from("/virtual/project/src"),
+                PathBuf::from("/virtual/project/src/lib"),
+                PathBuf::from("/virtual/project/src/lib/util.rs"),
+                PathBuf::from("/virtual/project/src/main.rs"),
+            ]
+        );
+        assert!(source.metadata(Path::new("/virtual/project/src/lib")).unwrap().is_dir);
+        assert!(source.read(Path::new("/virtual/project/missing.rs")).is_err());
+    }
+
+    #[test]
+    fn test_traverse_memory_source() {
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            ..Default::default()
+        };
+        let exclude = vec!["*.md".to_string()];
+        let Traversal { tree, files, .. } =
+            traverse_source(&source(), &config, &[], &exclude).unwrap();
+
+        assert!(tree.starts_with("project"));
+        assert!(tree.contains("util.rs"));
+        assert!(tree.contains("README.md"));
+
+        let paths: Vec<&str> = files.iter().map(|f| f["path"].as_str().unwrap()).collect();
+        assert_eq!(
+            paths,
+            vec!["/virtual/project/src/lib/util.rs", "/virtual/project/src/main.rs"]
+        );
+        assert_eq!(files[1]["code"], "```rs\nfn main() {}\n```");
+    }
+
+    /// Commits the given files on top of HEAD and returns the commit id.
+    fn commit(repo: &git2::Repository, files: &[(&str, &str)], message: &str) -> git2::Oid {
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        let mut index = repo.index().unwrap();
+        for (name, content) in files {
+            std::fs::write(workdir.join(name), content).unwrap();
+            index.add_path(Path::new(name)).unwrap();
+        }
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_generate_prompt_from_git_ref() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        let first = commit(
+            &repo,
+            &[
+                ("main.rs", "fn first() {}"),
+                (".gitignore", "generated.rs\n"),
+                ("generated.rs", "fn generated() {}"),
+            ],
+            "First commit",
+        );
+        commit(&repo, &[("main.rs", "fn second() {}"), ("added.rs", "fn added() {}")], "Second commit");
+        std::fs::write(dir.path().join("main.rs"), "fn uncommitted() {}").unwrap();
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            git_ref: Some(first.to_string()),
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let rendered = generate_prompt(&config).unwrap();
+        assert!(rendered.contains("fn first() {}"));
+        assert!(!rendered.contains("fn uncommitted() {}"));
+        assert!(!rendered.contains("added.rs"));
+        // Ignored and hidden files are skipped like on the filesystem
+        assert!(!rendered.contains("fn generated() {}"));
+        assert!(!rendered.contains(".gitignore"));
+
+        // A bare clone has no working copy to read from
+        let bare_dir = tempfile::tempdir().unwrap();
+        git2::build::RepoBuilder::new()
+            .bare(true)
+            .clone(dir.path().to_str().unwrap(), bare_dir.path())
+            .unwrap();
+        let source = GitTreeSource::new(bare_dir.path(), "HEAD").unwrap();
+        let root = std::fs::canonicalize(bare_dir.path()).unwrap();
+        assert_eq!(source.read(&root.join("main.rs")).unwrap(), b"fn second() {}");
+        assert!(source.read(&root.join("missing.rs")).is_err());
+    }
+
+    #[test]
+    fn test_nested_ignore_files_override_ancestors() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        std::fs::create_dir_all(dir.path().join("config/nested")).unwrap();
+        commit(
+            &repo,
+            &[
+                (".code2promptignore", "*.json\n"),
+                ("data.json", "{\"data\": true}"),
+                ("main.rs", "fn main() {}"),
+                ("config/.code2promptignore", "!*.json\n*.rs\n"),
+                ("config/settings.json", "{\"settings\": true}"),
+                ("config/loader.rs", "fn load() {}"),
+                ("config/nested/extra.json", "{\"extra\": true}"),
+            ],
+            "Initial commit",
+        );
+
+        // The working copy and the tree of a reference apply the same rules
+        for git_ref in [None, Some("HEAD".to_string())] {
+            let config = Code2PromptConfig {
+                path: dir.path().to_path_buf(),
+                git_ref,
+                no_clipboard: true,
+                ..Default::default()
+            };
+            let rendered = generate_prompt(&config).unwrap();
+            assert!(rendered.contains("fn main() {}"));
+            assert!(!rendered.contains("\"data\""));
+            // The subdirectory re-includes what the root excludes, for its whole subtree
+            assert!(rendered.contains("\"settings\""));
+            assert!(rendered.contains("\"extra\""));
+            assert!(!rendered.contains("fn load() {}"));
+        }
+    }
+
+    #[test]
+    fn test_extra_ignore_files_exclude_directory() {
+        use code2prompt::traverse_with_config;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("build/out")).unwrap();
+        std::fs::write(dir.path().join(".dockerignore"), "build/\n").unwrap();
+        std::fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+        std::fs::write(dir.path().join("build/out/generated.rs"), "fn generated() {}").unwrap();
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let (tree, files) = traverse_with_config(&config, &[], &[]).unwrap();
+        assert!(tree.contains("build"));
+        assert_eq!(files.len(), 2);
+
+        let config = Code2PromptConfig {
+            extra_ignore_files: vec![".dockerignore".to_string()],
+            ..config
+        };
+        let (tree, files) = traverse_with_config(&config, &[], &[]).unwrap();
+        assert!(!tree.contains("build"));
+        assert_eq!(files.len(), 1);
+        assert!(files[0]["path"].as_str().unwrap().ends_with("main.rs"));
+    }
+}
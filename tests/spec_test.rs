@@ -0,0 +1,87 @@
+use code2prompt::spec::load_spec;
+use code2prompt::{generate_prompt, Code2PromptConfig};
+use std::fs;
+use tempfile::tempdir;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_sets_template_patterns_and_variables() {
+        let project = tempdir().unwrap();
+        fs::write(project.path().join("main.rs"), "fn main() {}").unwrap();
+        fs::write(project.path().join("notes.txt"), "not code").unwrap();
+        let specs = tempdir().unwrap();
+        let spec = specs.path().join("review.toml");
+        fs::write(
+            &spec,
+            r#"
+include = "*.rs"
+encoding = "o200k"
+template_content = "Review the {{language}} code for {{audience}}:{{#each files}} {{path}}{{/each}}"
+
+[variables]
+language = "Rust"
+audience = "security"
+"#,
+        )
+        .unwrap();
+
+        let config = Code2PromptConfig {
+            path: project.path().to_path_buf(),
+            spec: Some(spec.clone()),
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let resolved = config.resolve_spec().unwrap();
+        assert_eq!(resolved.path, project.path());
+        assert_eq!(resolved.encoding.as_deref(), Some("o200k"));
+        assert_eq!(resolved.spec, None);
+
+        let prompt = generate_prompt(&config).unwrap();
+        assert!(prompt.starts_with("Review the Rust code for security:"), "{}", prompt);
+        assert!(prompt.contains("main.rs"));
+        assert!(!prompt.contains("notes.txt"));
+
+        // Options set on top of the spec take precedence
+        let config = Code2PromptConfig {
+            include: Some("*.txt".to_string()),
+            ..config
+        };
+        let prompt = generate_prompt(&config).unwrap();
+        assert!(prompt.contains("notes.txt"));
+        assert!(!prompt.contains("main.rs"));
+    }
+
+    #[test]
+    fn test_yaml_spec_references_a_template_relative_to_it() {
+        let specs = tempdir().unwrap();
+        fs::create_dir(specs.path().join("templates")).unwrap();
+        fs::write(specs.path().join("templates/short.hbs"), "{{greeting}}").unwrap();
+        let spec = specs.path().join("short.yaml");
+        fs::write(
+            &spec,
+            "template: templates/short.hbs\nvariables:\n  greeting: Hello\nexclude: \"*.lock\"\n",
+        )
+        .unwrap();
+
+        let config = load_spec(&spec).unwrap();
+        assert_eq!(config.template, Some(specs.path().join("templates/short.hbs")));
+        assert_eq!(config.exclude.as_deref(), Some("*.lock"));
+        assert_eq!(config.variables["greeting"], "Hello");
+    }
+
+    #[test]
+    fn test_invalid_spec() {
+        let specs = tempdir().unwrap();
+        let spec = specs.path().join("typo.toml");
+        fs::write(&spec, "inclde = \"*.rs\"\n").unwrap();
+        let error = load_spec(&spec).unwrap_err();
+        assert!(format!("{:#}", error).contains("unknown field `inclde`"), "{:#}", error);
+
+        let spec = specs.path().join("spec.ini");
+        fs::write(&spec, "").unwrap();
+        assert!(load_spec(&spec).is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use code2prompt::language::header_comment;
+use code2prompt::{directory_summaries, render_prompt, Code2PromptConfig, MemorySource};
+use std::path::PathBuf;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_comment() {
+        assert_eq!(
+            header_comment("//! Parses the config.\n//! More details.\nuse std::io;\n", "rs").as_deref(),
+            Some("Parses the config.")
+        );
+        assert_eq!(
+            header_comment("// Copyright 2024 Example\n// SPDX-License-Identifier: MIT\n\n/// The entry point.\nfn main() {}\n", "rs")
+                .as_deref(),
+            Some("The entry point.")
+        );
+        assert_eq!(
+            header_comment("/**\n * Renders the page.\n */\nexport function render() {}\n", "ts").as_deref(),
+            Some("Renders the page.")
+        );
+        assert_eq!(
+            header_comment("#!/usr/bin/env python\n\"\"\"Command line interface.\"\"\"\nimport sys\n", "py").as_deref(),
+            Some("Command line interface.")
+        );
+        assert_eq!(header_comment("fn main() {}\n// Too late\n", "rs"), None);
+        assert_eq!(header_comment("// Unknown language\n", "xyz"), None);
+    }
+
+    #[test]
+    fn test_directory_summaries() {
+        let source = MemorySource::new("/virtual/project")
+            .with_file("main.rs", "//! The command line entry point.\nfn main() {}")
+            .with_file("src/parser.rs", "/// Parses the input.\npub fn parse() {}")
+            .with_file("src/lexer.rs", "// Splits the input into tokens.\npub fn lex() {}")
+            .with_file("src/util.rs", "pub fn helper() {}");
+        let config = Code2PromptConfig {
+            path: PathBuf::from("/virtual/project"),
+            dir_summaries: true,
+            ..Default::default()
+        };
+
+        let prompt = render_prompt(&source, &config).unwrap().prompt;
+        assert!(prompt.contains("Directory Summaries:"));
+        assert!(prompt.contains("`.`:\n- main.rs: The command line entry point.\n"));
+        assert!(prompt.contains("`src`:\n- lexer.rs: Splits the input into tokens.\n- parser.rs: Parses the input.\n"));
+        assert!(!prompt.contains("- util.rs"));
+
+        let files = render_prompt(&source, &config).unwrap().files;
+        let summaries = directory_summaries(&source, &files, &config.path, config.relative_paths);
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[1].directory, "src");
+        assert_eq!(summaries[1].files[1].summary, "Parses the input.");
+
+        // Without the flag, there is no section
+        let config = Code2PromptConfig {
+            dir_summaries: false,
+            ..config
+        };
+        assert!(!render_prompt(&source, &config).unwrap().prompt.contains("Directory Summaries:"));
+    }
+}
@@ -1,4 +1,7 @@
-use code2prompt::template::{extract_undefined_variables, handlebars_setup, render_template};
+use code2prompt::template::{
+    extract_undefined_variables, handle_undefined_variables, handlebars_setup, register_file_block_template,
+    render_template, required_variables, write_to_file, write_to_file_with_options,
+};
 
 #[cfg(test)]
 mod tests {
@@ -36,6 +39,33 @@ mod tests {
         assert_eq!(variables, vec!["name", "language", "framework"]);
     }
 
+    #[test]
+    fn test_extract_undefined_variables_skips_block_context() {
+        let template_str = "{{name}}\n{{#each git_diffs}}{{title}}: {{diff}}{{/each}}\n{{footer}}";
+        let variables = extract_undefined_variables(template_str);
+        assert_eq!(variables, vec!["name", "footer"]);
+    }
+
+    #[test]
+    fn test_optional_variables_are_not_asked_for() {
+        let template_str = "{{challenge}}\n{{notes}}\n{{audience}}\n{{files}}";
+        let optional = vec!["notes".to_string(), "audience".to_string()];
+        let data = json!({ "files": [] });
+
+        assert_eq!(required_variables(&data, template_str, &optional), vec!["challenge"]);
+        assert_eq!(
+            required_variables(&data, template_str, &[]),
+            vec!["challenge", "notes", "audience"]
+        );
+
+        // Optional variables default to empty, and defined ones are kept
+        let mut data = json!({ "files": [], "challenge": "Parse", "audience": "reviewers" });
+        handle_undefined_variables(&mut data, template_str, &optional).unwrap();
+        assert_eq!(data["notes"], "");
+        assert_eq!(data["audience"], "reviewers");
+        assert_eq!(data["challenge"], "Parse");
+    }
+
     #[test]
     fn test_render_template() {
         let template_str = "{{greeting}}, {{name}}!";
@@ -49,4 +79,140 @@ mod tests {
             Err(e) => panic!("Template rendering failed: {}", e),
         }
     }
+
+    #[test]
+    fn test_file_block_partial_fallback() {
+        let template_str = "{{#each files}}{{> (file_block_partial extension)}}{{/each}}";
+        let mut handlebars = handlebars_setup(template_str, "test_template").unwrap();
+        register_file_block_template(&mut handlebars, "md", "[doc {{path}}]").unwrap();
+
+        let data = json!({
+            "files": [
+                { "path": "README.md", "extension": "md", "code": "# Title" },
+                { "path": "lib.rs", "extension": "rs", "code": "```rs\n```" }
+            ]
+        });
+        let rendered = render_template(&handlebars, "test_template", &data).unwrap();
+
+        assert!(rendered.starts_with("[doc README.md]"));
+        assert!(rendered.contains("`lib.rs`:\n\n```rs\n```"));
+    }
+
+    #[test]
+    fn test_write_to_nested_output_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("nested/deeper/prompt.txt");
+        let output = output.to_str().unwrap();
+
+        write_to_file(output, "prompt").unwrap();
+        assert_eq!(std::fs::read_to_string(output).unwrap(), "prompt");
+
+        // Without creating directories, a missing parent is an error
+        let missing = dir.path().join("missing/prompt.txt");
+        assert!(write_to_file_with_options(missing.to_str().unwrap(), "prompt", false).is_err());
+
+        // A parent that can't be created is reported clearly
+        let blocked = dir.path().join("nested/deeper/prompt.txt/child.txt");
+        let error = write_to_file(blocked.to_str().unwrap(), "prompt").unwrap_err();
+        assert!(error.to_string().contains("Failed to create output directory"));
+    }
+
+    #[test]
+    fn test_syntax_error_reports_location() {
+        let template_str = "Header\n{{#each files}}\n{{#if path}}\n{{path}}\n{{/each}}";
+        let error = handlebars_setup(template_str, "test_template").unwrap_err().to_string();
+
+        assert!(error.contains("line 5"));
+        assert!(error.contains("   5 | {{/each}}"));
+    }
+
+    #[test]
+    fn test_undefined_helper_is_reported() {
+        let template_str = "Header\n{{shout path}}";
+        let handlebars = handlebars_setup(template_str, "test_template").unwrap();
+        let error = render_template(&handlebars, "test_template", &json!({ "path": "a.rs" }))
+            .unwrap_err()
+            .to_string();
+
+        assert!(error.starts_with("Undefined helper \"shout\""));
+        assert!(error.contains("line 2"));
+    }
+
+    #[test]
+    fn test_repo_template_is_picked_up() {
+        use code2prompt::{generate_prompt, Code2PromptConfig, REPO_TEMPLATE_PATH};
+        use std::fs;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+        fs::create_dir(dir.path().join(".code2prompt")).unwrap();
+        fs::write(dir.path().join(REPO_TEMPLATE_PATH), "TEAM{{#each files}} {{path}}{{/each}}").unwrap();
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            relative_paths: true,
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let rendered = generate_prompt(&config).unwrap();
+        let root = code2prompt::label(dir.path());
+        assert_eq!(rendered, format!("TEAM {}/main.rs", root));
+
+        // An explicit template still takes precedence
+        let explicit = dir.path().join("explicit.hbs");
+        fs::write(&explicit, "EXPLICIT").unwrap();
+        let config = Code2PromptConfig {
+            template: Some(explicit),
+            ..config
+        };
+        assert_eq!(generate_prompt(&config).unwrap(), "EXPLICIT");
+    }
+
+    #[test]
+    fn test_repo_template_is_read_from_the_source() {
+        use code2prompt::{render_prompt, Code2PromptConfig, MemorySource, REPO_TEMPLATE_PATH};
+
+        let dir = tempfile::tempdir().unwrap();
+        let user_template = dir.path().join("default.hbs");
+        std::fs::write(&user_template, "USER").unwrap();
+        let config = Code2PromptConfig {
+            path: std::path::PathBuf::from("/virtual/project"),
+            user_template: Some(user_template),
+            ..Default::default()
+        };
+
+        // The repository template only exists in the source, and takes precedence over the user template
+        let source = MemorySource::new("/virtual/project")
+            .with_file("main.rs", "fn main() {}")
+            .with_file(REPO_TEMPLATE_PATH, "TEAM");
+        assert_eq!(render_prompt(&source, &config).unwrap().prompt, "TEAM");
+
+        let source = MemorySource::new("/virtual/project").with_file("main.rs", "fn main() {}");
+        assert_eq!(render_prompt(&source, &config).unwrap().prompt, "USER");
+    }
+
+    #[test]
+    fn test_custom_file_header_with_token_count() {
+        use code2prompt::{get_dyn_tokenizer, render_prompt, Code2PromptConfig, MemorySource};
+
+        let source = MemorySource::new("/virtual/project").with_file("src/main.rs", "fn main() {}");
+        let config = Code2PromptConfig {
+            path: std::path::PathBuf::from("/virtual/project"),
+            file_header_template: Some("## {{path}} [{{language}}] {{token_count}} tokens".to_string()),
+            ..Default::default()
+        };
+        let prompt = render_prompt(&source, &config).unwrap().prompt;
+
+        let tokens = get_dyn_tokenizer(&None).count("```rs\nfn main() {}\n```");
+        let header = format!("## /virtual/project/src/main.rs [rust] {} tokens\n\n```rs", tokens);
+        assert!(prompt.contains(&header), "Unexpected output: {}", prompt);
+        assert!(!prompt.contains("`/virtual/project/src/main.rs`:"));
+
+        // Invalid snippets are reported
+        let config = Code2PromptConfig {
+            file_header_template: Some("{{#if path}}".to_string()),
+            ..config
+        };
+        assert!(render_prompt(&source, &config).is_err());
+    }
 }
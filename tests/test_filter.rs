@@ -1,4 +1,7 @@
-use code2prompt::filter::should_include_file;
+use code2prompt::filter::{
+    filter_preset, is_likely_secret, is_test_file, matches_filters, read_pattern_file,
+    should_include_file, should_include_file_with_options, FILTER_PRESETS,
+};
 use colored::*;
 use once_cell::sync::Lazy;
 use std::fs::{self, File};
@@ -9,10 +12,10 @@ use tempfile::{tempdir, TempDir};
 fn create_temp_file(dir: &Path, name: &str, content: &str) {
     let file_path = dir.join(name);
     let parent_dir = file_path.parent().unwrap();
-    fs::create_dir_all(parent_dir).expect(&format!("Failed to create directory: {:?}", parent_dir));
+    fs::create_dir_all(parent_dir).unwrap_or_else(|_| panic!("Failed to create directory: {:?}", parent_dir));
     let mut file =
-        File::create(&file_path).expect(&format!("Failed to create temp file: {:?}", file_path));
-    writeln!(file, "{}", content).expect(&format!("Failed to write to temp file: {:?}", file_path));
+        File::create(&file_path).unwrap_or_else(|_| panic!("Failed to create temp file: {:?}", file_path));
+    writeln!(file, "{}", content).unwrap_or_else(|_| panic!("Failed to write to temp file: {:?}", file_path));
 }
 
 static TEST_DIR: Lazy<TempDir> = Lazy::new(|| {
@@ -265,15 +268,13 @@ mod tests {
         let exclude_patterns = vec!["**/foo.py".to_string()];
         let include_priority = true;
 
-        for file in ["lowercase/foo.py"] {
-            let path = base_path.join(file);
-            assert!(should_include_file(
-                &path,
-                &include_patterns,
-                &exclude_patterns,
-                include_priority
-            ));
-        }
+        let path = base_path.join("lowercase/foo.py");
+        assert!(should_include_file(
+            &path,
+            &include_patterns,
+            &exclude_patterns,
+            include_priority
+        ));
 
         for file in [
             "lowercase/bar.py",
@@ -348,7 +349,7 @@ mod tests {
         let exclude_patterns: Vec<String> = vec![];
         let include_priority = false;
         assert!(should_include_file(
-            &path,
+            path,
             &include_patterns,
             &exclude_patterns,
             include_priority
@@ -362,10 +363,112 @@ mod tests {
         let exclude_patterns: Vec<String> = vec!["*.rs".to_string()];
         let include_priority = false;
         assert!(!should_include_file(
-            &path,
+            path,
             &include_patterns,
             &exclude_patterns,
             include_priority
         ));
     }
+
+    #[test]
+    fn test_is_likely_secret() {
+        for file in [".env", ".env.production", "id_rsa", "certs/server.pem", "credentials.json"] {
+            assert!(is_likely_secret(Path::new(file), &[]), "{} should be flagged", file);
+        }
+        for file in ["src/main.rs", "environment.py", "id_rsa.pub", "README.md"] {
+            assert!(!is_likely_secret(Path::new(file), &[]), "{} should not be flagged", file);
+        }
+
+        // The pattern list is extensible
+        let extra = vec!["*.vault".to_string()];
+        assert!(!is_likely_secret(Path::new("prod.vault"), &[]));
+        assert!(is_likely_secret(Path::new("prod.vault"), &extra));
+    }
+
+    #[test]
+    fn test_filter_presets() {
+        let node = filter_preset("node").unwrap();
+        let exclude: Vec<String> = node.exclude.iter().map(|p| p.to_string()).collect();
+        for path in ["/app/node_modules/react/index.js", "/app/dist/bundle.js", "/app/yarn.lock"] {
+            assert!(!matches_filters(path, &[], &exclude, false), "{} should be excluded", path);
+        }
+        assert!(matches_filters("/app/src/index.js", &[], &exclude, false));
+
+        let rust = filter_preset("Rust").unwrap();
+        let exclude: Vec<String> = rust.exclude.iter().map(|p| p.to_string()).collect();
+        assert!(!matches_filters("/crate/target/debug/build.rs", &[], &exclude, false));
+        assert!(matches_filters("/crate/src/main.rs", &[], &exclude, false));
+
+        // Unknown presets list the available ones
+        let error = filter_preset("cobol").unwrap_err().to_string();
+        for preset in FILTER_PRESETS {
+            assert!(error.contains(preset.name));
+        }
+    }
+
+    #[test]
+    fn test_is_test_file() {
+        // Go
+        assert!(is_test_file("pkg/server/handler_test.go"));
+        assert!(!is_test_file("pkg/server/handler.go"));
+        // Python
+        assert!(is_test_file("app/test_models.py"));
+        assert!(is_test_file("app/models_test.py"));
+        assert!(is_test_file("tests/conftest.py"));
+        assert!(!is_test_file("app/testing_utils.py"));
+        // JavaScript / TypeScript
+        assert!(is_test_file("src/Button.test.tsx"));
+        assert!(is_test_file("src/api.spec.js"));
+        assert!(is_test_file("src/__tests__/Button.jsx"));
+        assert!(!is_test_file("src/latest.ts"));
+        // Only directories named like tests count, not any name containing "test"
+        assert!(is_test_file("spec/models/user.rb"));
+        assert!(!is_test_file("attestation/verify.rs"));
+    }
+
+    #[test]
+    fn test_patterns_from_file() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("project");
+        fs::create_dir(&project).unwrap();
+        fs::write(project.join("main.rs"), "fn main() {}").unwrap();
+        fs::write(project.join("build.log"), "noise").unwrap();
+        fs::write(project.join("notes.md"), "# Notes").unwrap();
+
+        let pattern_file = dir.path().join("exclude.txt");
+        fs::write(&pattern_file, "# Build outputs\n*.log\n\n   # Docs\n  *.md  \n").unwrap();
+        assert_eq!(read_pattern_file(&pattern_file).unwrap(), vec!["*.log", "*.md"]);
+        assert!(read_pattern_file(&dir.path().join("missing.txt")).is_err());
+
+        let config = code2prompt::Code2PromptConfig {
+            path: project.clone(),
+            exclude: Some("*.toml".to_string()),
+            exclude_from: Some(pattern_file),
+            ..Default::default()
+        };
+        let prompt = code2prompt::render_prompt(&code2prompt::FsSource, &config).unwrap();
+        let paths: Vec<&str> = prompt.files.iter().map(|f| f["path"].as_str().unwrap()).collect();
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].ends_with("main.rs"));
+    }
+
+    #[test]
+    fn test_should_include_file_with_options() {
+        let config = code2prompt::Code2PromptConfig {
+            no_tests: true,
+            exclude_languages: vec!["sql".to_string()],
+            ..Default::default()
+        };
+        let include = |path: &str, relative: &str, language: Option<&str>, patterns: &[String]| {
+            should_include_file_with_options(path, relative, language, patterns, &[], &config)
+        };
+
+        assert!(include("/p/src/main.rs", "src/main.rs", Some("rust"), &[]));
+        assert!(!include("/p/tests/api.rs", "tests/api.rs", Some("rust"), &[]));
+        assert!(!include("/p/db/schema.sql", "db/schema.sql", Some("sql"), &[]));
+        // An include pattern brings a test file back, but not a file of an excluded language
+        let patterns = vec!["/p/tests/*".to_string(), "/p/db/*".to_string()];
+        assert!(include("/p/tests/api.rs", "tests/api.rs", Some("rust"), &patterns));
+        assert!(!include("/p/db/schema.sql", "db/schema.sql", Some("sql"), &patterns));
+    }
 }
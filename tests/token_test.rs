@@ -0,0 +1,282 @@
+use code2prompt::token::{
+    count_tokens_parallel, count_tokens_with_progress, estimate_tokens, get_dyn_tokenizer, get_model_info, get_tokenizer, get_tokenizer_with_special_tokens,
+    register_tokenizer, resolve_encoding_with_env, resolve_model_with_env,
+    tokens_by_language, TokenStats, Tokenizer, CLAUDE_TOKEN_RATIO, ENCODING_ENV_VAR, MODEL_ENV_VAR,
+};
+use code2prompt::{deliver_prompt_with_callback, generate_prompt, render_prompt, Code2PromptConfig, FsSource};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, HashMap};
+
+    #[test]
+    fn test_get_tokenizer_returns_the_bpe() {
+        let encoding = Some("p50k".to_string());
+        let bpe: tiktoken_rs::CoreBPE = get_tokenizer(&encoding);
+        let text = "fn main() {\n    run();\n}";
+        assert_eq!(bpe.encode_with_special_tokens(text).len(), get_dyn_tokenizer(&encoding).count(text));
+    }
+
+    /// An environment with the given variables, as the variables of the process are shared by the tests.
+    fn env(vars: &[(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        move |name| vars.get(name).map(|value| value.to_string())
+    }
+
+    #[test]
+    fn test_resolve_encoding_lookup_order() {
+        assert_eq!(resolve_encoding_with_env(&None, &env(&[])), "cl100k");
+        assert_eq!(resolve_model_with_env(&None, &env(&[])), "gpt-4");
+
+        let model = env(&[(MODEL_ENV_VAR, "text-davinci-003")]);
+        assert_eq!(resolve_encoding_with_env(&None, &model), "p50k");
+        assert_eq!(resolve_model_with_env(&None, &model), "text-davinci-003");
+
+        let unknown = env(&[(MODEL_ENV_VAR, "unknown-model")]);
+        assert_eq!(resolve_encoding_with_env(&None, &unknown), "cl100k");
+        assert_eq!(resolve_model_with_env(&None, &unknown), "gpt-4");
+
+        let both = env(&[(MODEL_ENV_VAR, "text-davinci-003"), (ENCODING_ENV_VAR, "r50k")]);
+        assert_eq!(resolve_encoding_with_env(&None, &both), "r50k");
+        assert_eq!(resolve_model_with_env(&None, &both), "davinci");
+        assert_eq!(get_model_info(&Some("r50k".to_string())), "GPT-3 models like davinci");
+
+        // Explicit configuration always wins
+        let explicit = Some("p50k_edit".to_string());
+        assert_eq!(resolve_encoding_with_env(&explicit, &both), "p50k_edit");
+        assert_eq!(resolve_model_with_env(&explicit, &both), "text-davinci-edit-001");
+    }
+
+    #[test]
+    fn test_tokens_by_language_sum_to_total() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.rs"), "fn main() { println!(\"hi\"); }").unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "pub fn lib() {}").unwrap();
+        std::fs::write(dir.path().join("app.py"), "def app():\n    return 42\n").unwrap();
+        std::fs::write(dir.path().join("Makefile"), "all:\n\techo all\n").unwrap();
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            tokens: true,
+            encoding: Some("cl100k".to_string()),
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let prompt = render_prompt(&FsSource, &config).unwrap();
+        let totals = tokens_by_language(&prompt.files);
+
+        // Counted again from the code blocks of the rendered prompt, grouped by hand
+        let tokenizer = get_dyn_tokenizer(&config.encoding);
+        let block_tokens = |name: &str| {
+            let header = format!("{}`:\n\n", name);
+            let start = prompt.prompt.find(&header).unwrap() + header.len();
+            let block = &prompt.prompt[start..];
+            let body = block.find('\n').unwrap() + 1;
+            let end = body + block[body..].find("\n```").unwrap() + "\n```".len();
+            tokenizer.count(&block[..end])
+        };
+        let expected = BTreeMap::from([
+            ("(none)".to_string(), block_tokens("Makefile")),
+            ("py".to_string(), block_tokens("app.py")),
+            ("rs".to_string(), block_tokens("main.rs") + block_tokens("lib.rs")),
+        ]);
+        assert_eq!(totals, expected);
+        assert!(expected.values().all(|&tokens| tokens > 0));
+    }
+
+    #[test]
+    fn test_estimate_tokens_error_is_bounded() {
+        let samples = [include_str!("../src/path.rs"), include_str!("../README.md")];
+        for encoding in ["cl100k", "o200k", "p50k", "r50k", "claude"] {
+            let encoding = Some(encoding.to_string());
+            let tokenizer = get_dyn_tokenizer(&encoding);
+            for sample in samples {
+                let exact = tokenizer.count(sample) as f64;
+                let estimate = estimate_tokens(sample, &encoding) as f64;
+                let error = (estimate - exact).abs() / exact;
+                assert!(error < 0.2, "{:?}: estimate {} vs exact {}", encoding, estimate, exact);
+            }
+        }
+        assert_eq!(estimate_tokens("", &None), 0);
+    }
+
+    #[test]
+    fn test_estimated_file_tokens_are_labeled() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.rs"), "fn main() { println!(\"hi\"); }").unwrap();
+
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            tokens: true,
+            estimate_tokens: true,
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let prompt = render_prompt(&FsSource, &config).unwrap();
+        assert_eq!(prompt.files[0]["tokens_estimated"], true);
+        assert!(prompt.files[0]["tokens"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_chunked_count_matches_whole_count() {
+        let text = format!(
+            "{}\n\n  \n{}\n}};\nfn tail() {{}}",
+            include_str!("../src/path.rs"),
+            include_str!("../README.md")
+        );
+        for encoding in ["cl100k", "o200k", "p50k", "r50k"] {
+            let tokenizer = get_dyn_tokenizer(&Some(encoding.to_string()));
+            let exact = tokenizer.count(&text);
+
+            let mut reports = Vec::new();
+            let chunked = count_tokens_with_progress(&text, tokenizer.as_ref(), 1000, &mut |encoded, total| {
+                reports.push((encoded, total))
+            });
+            assert_eq!(chunked, exact, "{}", encoding);
+
+            // Progress only moves forward and ends with the whole text
+            assert!(reports.len() > 10);
+            assert!(reports.windows(2).all(|pair| pair[0].0 < pair[1].0));
+            assert_eq!(reports.last(), Some(&(text.len(), text.len())));
+        }
+    }
+
+    #[test]
+    fn test_chunked_claude_count_is_rounded_once() {
+        // One chunk per line, each of whose approximate counts would be rounded up on its own
+        let text = "fn one() {}\n".repeat(200);
+        let tokenizer = get_dyn_tokenizer(&Some("claude".to_string()));
+
+        let chunked = count_tokens_with_progress(&text, tokenizer.as_ref(), 1, &mut |_, _| {});
+        assert_eq!(chunked, tokenizer.count(&text));
+        let parallel = count_tokens_parallel(&text, tokenizer.as_ref(), 1, 4, &mut |_, _| {});
+        assert_eq!(parallel, chunked);
+    }
+
+    #[test]
+    fn test_machine_stats_line() {
+        let stats = TokenStats::new("héllo wörld", 3, &Some("o200k".to_string()), 2);
+        assert_eq!(stats.machine_line(), "tokens=3 model=gpt-4o chars=11 files=2");
+        assert_eq!(stats.model_info, "GPT-4o models");
+    }
+
+    /// A tokenizer counting whitespace-separated words.
+    struct WhitespaceTokenizer;
+
+    impl Tokenizer for WhitespaceTokenizer {
+        fn count(&self, text: &str) -> usize {
+            text.split_whitespace().count()
+        }
+    }
+
+    #[test]
+    fn test_registered_tokenizer() {
+        register_tokenizer("whitespace", WhitespaceTokenizer);
+        let encoding = Some("whitespace".to_string());
+
+        assert_eq!(get_dyn_tokenizer(&encoding).count("fn main() {\n    run();\n}"), 5);
+        assert_eq!(get_model_info(&encoding), "Custom tokenizer");
+    }
+
+    #[test]
+    fn test_special_token_modes_differ() {
+        let encoding = Some("cl100k".to_string());
+        let text = "The end of a document is marked by <|endoftext|>.";
+
+        let special = get_tokenizer_with_special_tokens(&encoding, true).count(text);
+        let plain = get_tokenizer_with_special_tokens(&encoding, false).count(text);
+        assert!(plain > special, "{} plain tokens, {} with special tokens", plain, special);
+        assert_eq!(get_dyn_tokenizer(&encoding).count(text), special);
+
+        // Without special token markers, both modes agree
+        let text = "fn main() {}";
+        assert_eq!(
+            get_tokenizer_with_special_tokens(&encoding, true).count(text),
+            get_tokenizer_with_special_tokens(&encoding, false).count(text)
+        );
+    }
+
+    #[test]
+    fn test_parallel_count_matches_serial_count() {
+        let text = format!("{}\n{}", include_str!("../src/path.rs"), include_str!("../src/lib.rs"));
+        for encoding in ["cl100k", "claude"] {
+            let tokenizer = get_dyn_tokenizer(&Some(encoding.to_string()));
+            let serial = count_tokens_with_progress(&text, tokenizer.as_ref(), 1000, &mut |_, _| {});
+
+            for threads in [1, 2, 8] {
+                let mut reports = Vec::new();
+                let parallel =
+                    count_tokens_parallel(&text, tokenizer.as_ref(), 1000, threads, &mut |encoded, total| {
+                        reports.push((encoded, total))
+                    });
+                assert_eq!(parallel, serial, "{} with {} thread(s)", encoding, threads);
+                assert!(reports.windows(2).all(|pair| pair[0].0 < pair[1].0));
+                assert_eq!(reports.last(), Some(&(text.len(), text.len())));
+            }
+        }
+    }
+
+    #[test]
+    fn test_output_is_identical_at_any_concurrency() {
+        let dir = tempfile::tempdir().unwrap();
+        // Large enough for the prompt to be counted in chunks
+        let line = "fn line() -> usize { 42 }\n";
+        std::fs::write(dir.path().join("big.rs"), line.repeat(400 * 1024 / line.len())).unwrap();
+        let output = |concurrency: usize| {
+            let config = Code2PromptConfig {
+                path: dir.path().to_path_buf(),
+                tokens: true,
+                json: true,
+                concurrency: Some(concurrency),
+                ..Default::default()
+            };
+            generate_prompt(&config).unwrap()
+        };
+
+        let serial = output(1);
+        assert_eq!(output(4), serial);
+        let json: serde_json::Value = serde_json::from_str(&serial).unwrap();
+        assert!(json["token_count"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_prompt_token_progress_goes_to_the_callback() {
+        let dir = tempfile::tempdir().unwrap();
+        let line = "fn line() -> usize { 42 }\n";
+        std::fs::write(dir.path().join("big.rs"), line.repeat(400 * 1024 / line.len())).unwrap();
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            json: true,
+            quiet: true,
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let prompt = render_prompt(&FsSource, &config).unwrap();
+
+        let reports = std::cell::RefCell::new(Vec::new());
+        let on_progress = |encoded: usize, total: usize| reports.borrow_mut().push((encoded, total));
+        deliver_prompt_with_callback(&config, &prompt, Some(&on_progress)).unwrap();
+        let reports = reports.into_inner();
+        assert!(reports.len() > 1);
+        assert_eq!(reports.last(), Some(&(prompt.prompt.len(), prompt.prompt.len())));
+    }
+
+    #[test]
+    fn test_claude_encoding() {
+        let claude = Some("claude".to_string());
+        let sample = include_str!("../src/token.rs");
+        let cl100k = get_tokenizer_with_special_tokens(&Some("cl100k".to_string()), false).count(sample) as f64;
+        let count = get_dyn_tokenizer(&claude).count(sample) as f64;
+        assert!((count - cl100k * CLAUDE_TOKEN_RATIO).abs() <= 1.0, "{} vs {}", count, cl100k);
+
+        // Special token markers are plain text for Claude
+        let with_special = get_tokenizer_with_special_tokens(&claude, true).count("<|endoftext|>");
+        assert!(with_special > 1);
+
+        let stats = TokenStats::new(sample, count as usize, &claude, 1);
+        assert_eq!(stats.encoding, "claude");
+        assert!(get_model_info(&claude).contains("Claude"));
+        assert!(get_model_info(&claude).contains("200K"));
+    }
+}
@@ -0,0 +1,56 @@
+#![cfg(feature = "watch")]
+
+use code2prompt::{watch, Code2PromptConfig};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::ops::ControlFlow;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_watch_regenerates_on_change() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn before() {}").unwrap();
+        fs::write(dir.path().join(".code2promptignore"), "generated.rs\n").unwrap();
+        fs::create_dir(dir.path().join("tests")).unwrap();
+        let config = Code2PromptConfig {
+            path: dir.path().to_path_buf(),
+            exclude: Some("*.log".to_string()),
+            no_tests: true,
+            exclude_languages: vec!["sql".to_string()],
+            no_clipboard: true,
+            quiet: true,
+            ..Default::default()
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let watcher = thread::spawn(move || {
+            let mut regenerations = 0;
+            watch(&config, |result| {
+                regenerations += 1;
+                sender.send(result.unwrap()).unwrap();
+                ControlFlow::Break(())
+            })
+            .unwrap();
+            regenerations
+        });
+
+        // Changes to the files the traversal leaves out don't trigger a regeneration
+        thread::sleep(Duration::from_millis(500));
+        fs::write(dir.path().join("build.log"), "noise").unwrap();
+        fs::write(dir.path().join("generated.rs"), "fn generated() {}").unwrap();
+        fs::write(dir.path().join("tests/api.rs"), "fn test() {}").unwrap();
+        fs::write(dir.path().join("dump.sql"), "SELECT 1;").unwrap();
+        thread::sleep(Duration::from_millis(800));
+        fs::write(dir.path().join("main.rs"), "fn after() {}").unwrap();
+
+        let prompt = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(prompt.contains("fn after() {}"));
+        assert_eq!(watcher.join().unwrap(), 1);
+    }
+}